@@ -0,0 +1,553 @@
+use std::collections::{HashSet, VecDeque};
+use std::str::FromStr;
+use serde::Deserialize;
+use crate::maze::{is_walkable, GOAL, SPAWN, STAIRS_DOWN, STAIRS_UP};
+
+// Recorridos sobre el dibujo ASCII de un laberinto. Las distancias se cuentan en celdas del
+// mapa, moviéndose en cuatro direcciones por las celdas caminables (las puertas cuentan)
+
+pub type Pos = (usize, usize);
+pub type Step = (usize, Pos); // Piso y celda
+
+const PATH: char = '.';
+
+fn open_neighbors(maze: &[Vec<char>], (x, y): Pos) -> impl Iterator<Item = Pos> + '_ {
+    let candidates = [(x.wrapping_sub(1), y), (x + 1, y), (x, y.wrapping_sub(1)), (x, y + 1)];
+    candidates.into_iter().filter(|&(nx, ny)| maze.get(ny).and_then(|row| row.get(nx)).is_some_and(|&c| is_walkable(c)))
+}
+
+fn find(maze: &[Vec<char>], target: char) -> Vec<Pos> {
+    let mut found = Vec::new();
+    for (y, row) in maze.iter().enumerate() {
+        for (x, &cell) in row.iter().enumerate() {
+            if cell == target {
+                found.push((x, y));
+            }
+        }
+    }
+    found
+}
+
+// Camino más corto desde el inicio hasta una celda que toca la meta (incluso en diagonal,
+// porque la meta se alcanza tocando una de sus esquinas). None si no hay camino
+pub fn solve(maze: &[Vec<char>]) -> Option<Vec<Pos>> {
+    solve_floors(&[maze]).map(|path| path.into_iter().map(|(_, pos)| pos).collect())
+}
+
+// Igual que `solve`, pero en un nivel de varios pisos. Cada paso del camino lleva su piso;
+// una escalera lleva a la misma celda del piso de arriba o de abajo
+pub fn solve_floors<M: AsRef<[Vec<char>]>>(floors: &[M]) -> Option<Vec<Step>> {
+    solve_linked(floors, &[], &[])
+}
+
+// Con saltos de una celda a otra además de las escaleras, como los de los paneles, y con
+// pasos entre celdas vecinas que una pared delgada corta
+pub fn solve_linked<M: AsRef<[Vec<char>]>>(floors: &[M], links: &[(Step, Step)], cuts: &[(Step, Step)]) -> Option<Vec<Step>> {
+    let cut: HashSet<(Step, Step)> = cuts.iter().flat_map(|&(a, b)| [(a, b), (b, a)]).collect();
+    let spawn = floors.iter().enumerate().find_map(|(floor, maze)| Some((floor, *find(maze.as_ref(), SPAWN).first()?)))?;
+    let goals: Vec<Vec<Pos>> = floors.iter().map(|maze| find(maze.as_ref(), GOAL)).collect();
+    let touches_goal = |(floor, pos): Step| {
+        goals[floor].iter().any(|&goal| touches(pos, goal, |a, b| cut.contains(&((floor, a), (floor, b)))))
+    };
+
+    let mut previous: Vec<Vec<Vec<Option<Step>>>> = floors.iter().map(|maze| maze.as_ref().iter().map(|row| vec![None; row.len()]).collect()).collect();
+    let mut queue = VecDeque::from([spawn]);
+    previous[spawn.0][spawn.1 .1][spawn.1 .0] = Some(spawn);
+    while let Some(step) = queue.pop_front() {
+        if touches_goal(step) {
+            let mut path = vec![step];
+            let mut current = step;
+            while current != spawn {
+                let (floor, (x, y)) = current;
+                current = previous[floor][y][x]?;
+                path.push(current);
+            }
+            path.reverse();
+            return Some(path);
+        }
+
+        let (floor, pos) = step;
+        let maze = floors[floor].as_ref();
+        let stairs = match maze[pos.1][pos.0] {
+            STAIRS_UP if floor + 1 < floors.len() => Some((floor + 1, pos)),
+            STAIRS_DOWN if floor > 0 => Some((floor - 1, pos)),
+            _ => None,
+        };
+        let jumps = links.iter().filter(|&&(from, _)| from == step).map(|&(_, to)| to);
+        let walks = open_neighbors(maze, pos).map(|next| (floor, next)).filter(|&next| !cut.contains(&(step, next)));
+        let next_steps = walks.chain(stairs).chain(jumps);
+        for (next_floor, (nx, ny)) in next_steps {
+            let Some(slot) = previous.get_mut(next_floor).and_then(|rows| rows.get_mut(ny)).and_then(|row| row.get_mut(nx)) else {
+                continue;
+            };
+            if slot.is_none() {
+                *slot = Some(step);
+                queue.push_back((next_floor, (nx, ny)));
+            }
+        }
+    }
+    None
+}
+
+// Si desde `pos` se toca `goal`, que está al lado o en diagonal. Una pared delgada entre las dos
+// no deja tocarla; en diagonal, ninguna de las cuatro celdas alrededor de la esquina puede estar
+// separada de su vecina
+fn touches(pos: Pos, goal: Pos, apart: impl Fn(Pos, Pos) -> bool) -> bool {
+    let ((x, y), (gx, gy)) = (pos, goal);
+    let blocked = if gx == x || gy == y {
+        apart((x, y), (gx, gy))
+    } else {
+        apart((x, y), (gx, y)) || apart((x, y), (x, gy)) || apart((gx, y), (gx, gy)) || apart((x, gy), (gx, gy))
+    };
+    gx.abs_diff(x) <= 1 && gy.abs_diff(y) <= 1 && !blocked
+}
+
+// Distancia desde `start` hasta cada celda, caminando; usize::MAX donde no se llega
+pub fn distances(maze: &[Vec<char>], start: Pos) -> Vec<Vec<usize>> {
+    let mut distance: Vec<Vec<usize>> = maze.iter().map(|row| vec![usize::MAX; row.len()]).collect();
+    distance[start.1][start.0] = 0;
+    let mut queue = VecDeque::from([start]);
+    while let Some(pos) = queue.pop_front() {
+        for (nx, ny) in open_neighbors(maze, pos) {
+            if distance[ny][nx] == usize::MAX {
+                distance[ny][nx] = distance[pos.1][pos.0] + 1;
+                queue.push_back((nx, ny));
+            }
+        }
+    }
+    distance
+}
+
+// Copia del laberinto con el camino marcado sobre las celdas vacías
+pub fn overlay(maze: &[Vec<char>], path: &[Pos]) -> Vec<Vec<char>> {
+    let mut marked = maze.to_vec();
+    for &(x, y) in path {
+        if marked[y][x] == ' ' {
+            marked[y][x] = PATH;
+        }
+    }
+    marked
+}
+
+// Grafo del laberinto para las métricas. Si el mapa tiene el formato de habitaciones de
+// make_maze ("+--+" con "|  |"), cada habitación es un nodo; si no, cada celda caminable.
+// Así un pasillo de dos caracteres de ancho no cuenta como una fila de bifurcaciones
+struct Graph {
+    edges: Vec<Vec<usize>>,
+    width: usize, // Nodos por fila, para ubicarlos en el plano
+    spawn: Option<usize>,
+    goals: Vec<usize>,
+    rooms: bool,
+}
+
+impl Graph {
+    // Los paneles y las paredes delgadas unen y separan celdas, no habitaciones, así que un
+    // nivel que los tiene se mide siempre por celdas
+    fn new(maze: &[Vec<char>], jumps: &[(Pos, Pos)], cuts: &HashSet<(Pos, Pos)>) -> Graph {
+        if jumps.is_empty() && cuts.is_empty() && is_room_lattice(maze) {
+            Graph::from_rooms(maze)
+        } else {
+            Graph::from_cells(maze, jumps, cuts)
+        }
+    }
+
+    fn from_rooms(maze: &[Vec<char>]) -> Graph {
+        let w = (maze[0].len() - 1) / 3;
+        let h = (maze.len() - 1) / 2;
+        let open = |x: usize, y: usize| is_walkable(maze[y][x]) || maze[y][x] == GOAL;
+        let mut graph = Graph { edges: vec![Vec::new(); w * h], width: w, spawn: None, goals: Vec::new(), rooms: true };
+
+        for j in 0..h {
+            for i in 0..w {
+                let room = j * w + i;
+                let (x, y) = (3 * i + 1, 2 * j + 1);
+                if maze[y][x..x + 2].contains(&SPAWN) {
+                    graph.spawn = Some(room);
+                }
+                if maze[y][x..x + 2].contains(&GOAL) {
+                    graph.goals.push(room);
+                }
+                // Paso a la habitación de la derecha por la pared vertical, y a la de abajo por la horizontal
+                if i + 1 < w && open(x + 2, y) {
+                    graph.connect(room, room + 1);
+                }
+                if j + 1 < h && (open(x, y + 1) || open(x + 1, y + 1)) {
+                    graph.connect(room, room + w);
+                }
+            }
+        }
+        graph
+    }
+
+    fn from_cells(maze: &[Vec<char>], jumps: &[(Pos, Pos)], cuts: &HashSet<(Pos, Pos)>) -> Graph {
+        let width = maze.first().map_or(0, |row| row.len());
+        let index = |(x, y): Pos| y * width + x;
+        let mut graph = Graph { edges: vec![Vec::new(); width * maze.len()], width, spawn: None, goals: Vec::new(), rooms: false };
+
+        let goals = find(maze, GOAL);
+        for (y, row) in maze.iter().enumerate() {
+            for (x, &cell) in row.iter().enumerate() {
+                if !is_walkable(cell) {
+                    continue;
+                }
+                if cell == SPAWN {
+                    graph.spawn = Some(index((x, y)));
+                }
+                if goals.iter().any(|&goal| touches((x, y), goal, |a, b| cuts.contains(&(a, b)))) {
+                    graph.goals.push(index((x, y)));
+                }
+                let walks = open_neighbors(maze, (x, y)).filter(|&next| !cuts.contains(&((x, y), next)));
+                let jumps = jumps.iter().filter(|&&(from, _)| from == (x, y)).map(|&(_, to)| to);
+                graph.edges[index((x, y))] = walks.chain(jumps).map(index).collect();
+            }
+        }
+        graph
+    }
+
+    fn connect(&mut self, a: usize, b: usize) {
+        self.edges[a].push(b);
+        self.edges[b].push(a);
+    }
+
+    // Nodos por los que se puede pasar (las celdas de pared quedan sin vecinos)
+    fn nodes(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.edges.len()).filter(|&node| !self.edges[node].is_empty() || Some(node) == self.spawn)
+    }
+
+    fn degree(&self, node: usize) -> usize {
+        self.edges[node].len()
+    }
+
+    // Distancia en línea recta, sin atravesar paredes pero sin rodeos
+    fn manhattan(&self, a: usize, b: usize) -> usize {
+        (a % self.width).abs_diff(b % self.width) + (a / self.width).abs_diff(b / self.width)
+    }
+
+    // Camino más corto del inicio a la meta más cercana, con los dos extremos
+    fn solution(&self) -> Option<Vec<usize>> {
+        let spawn = self.spawn?;
+        let mut previous = vec![usize::MAX; self.edges.len()];
+        previous[spawn] = spawn;
+        let mut queue = VecDeque::from([spawn]);
+        while let Some(node) = queue.pop_front() {
+            if self.goals.contains(&node) {
+                let mut path = vec![node];
+                while *path.last()? != spawn {
+                    path.push(previous[*path.last()?]);
+                }
+                path.reverse();
+                return Some(path);
+            }
+            for &next in &self.edges[node] {
+                if previous[next] == usize::MAX {
+                    previous[next] = node;
+                    queue.push_back(next);
+                }
+            }
+        }
+        None
+    }
+
+    // Largo promedio de los pasillos: tramos entre nodos que no tienen exactamente dos salidas
+    fn average_corridor(&self, nodes: &[usize]) -> f32 {
+        let mut total = 0;
+        let mut corridors = 0;
+        for &start in nodes.iter().filter(|&&node| self.degree(node) != 2) {
+            for &first in &self.edges[start] {
+                let (mut previous, mut current, mut length) = (start, first, 1);
+                // Un salto de un solo sentido puede entrar a un anillo sin puntas: se corta al volver a pasar
+                let mut seen = HashSet::from([start]);
+                while self.degree(current) == 2 && seen.insert(current) {
+                    let next = if self.edges[current][0] == previous { self.edges[current][1] } else { self.edges[current][0] };
+                    (previous, current) = (current, next);
+                    length += 1;
+                }
+                // Cada pasillo se recorre desde sus dos puntas, lo que no cambia el promedio
+                total += length;
+                corridors += 1;
+            }
+        }
+        if corridors == 0 { 0.0 } else { total as f32 / corridors as f32 }
+    }
+
+    fn distances(&self, start: usize) -> Vec<usize> {
+        let mut distance = vec![usize::MAX; self.edges.len()];
+        distance[start] = 0;
+        let mut queue = VecDeque::from([start]);
+        while let Some(node) = queue.pop_front() {
+            for &next in &self.edges[node] {
+                if distance[next] == usize::MAX {
+                    distance[next] = distance[node] + 1;
+                    queue.push_back(next);
+                }
+            }
+        }
+        distance
+    }
+}
+
+// El formato de maze.txt: una esquina '+' cada tres columnas y cada dos filas
+fn is_room_lattice(maze: &[Vec<char>]) -> bool {
+    let width = maze.first().map_or(0, |row| row.len());
+    maze.len() >= 3
+        && maze.len() % 2 == 1
+        && width >= 4
+        && width % 3 == 1
+        && maze.iter().step_by(2).all(|row| row.iter().step_by(3).all(|&c| c == '+'))
+}
+
+pub struct Stats {
+    pub rooms: bool,         // Las cantidades son habitaciones y no celdas
+    pub open_cells: usize,
+    pub dead_ends: usize,    // Con una sola salida
+    pub junctions: usize,    // Con tres salidas o más
+    pub branching: f32,      // Salidas promedio en las bifurcaciones
+    pub longest_path: usize, // El más largo de los caminos más cortos entre dos puntos
+    pub corridor: f32,       // Largo promedio de los pasillos sin desvíos
+    pub solution: Option<Solution>,
+}
+
+pub struct Solution {
+    pub length: usize,
+    pub decisions: usize, // Puntos del camino donde se puede tomar un desvío
+    pub tortuosity: f32,  // Largo del camino dividido por la distancia en línea recta
+}
+
+pub fn stats(maze: &[Vec<char>]) -> Stats {
+    stats_linked(maze, 0, &[], &[])
+}
+
+// Las métricas del piso `floor` de un nivel, con los saltos de los paneles que quedan dentro
+// del piso y los pasos que cortan las paredes delgadas, como en `solve_linked`
+pub fn stats_linked(maze: &[Vec<char>], floor: usize, links: &[(Step, Step)], cuts: &[(Step, Step)]) -> Stats {
+    let on_floor = |&((from_floor, from), (to_floor, to)): &(Step, Step)| (from_floor == floor && to_floor == floor).then_some((from, to));
+    let jumps: Vec<(Pos, Pos)> = links.iter().filter_map(on_floor).collect();
+    let cuts: HashSet<(Pos, Pos)> = cuts.iter().filter_map(on_floor).flat_map(|(a, b)| [(a, b), (b, a)]).collect();
+    let graph = Graph::new(maze, &jumps, &cuts);
+    let nodes: Vec<usize> = graph.nodes().collect();
+
+    let mut dead_ends = 0;
+    let mut junctions = 0;
+    let mut junction_exits = 0;
+    for &node in &nodes {
+        match graph.degree(node) {
+            1 => dead_ends += 1,
+            exits @ 3.. => {
+                junctions += 1;
+                junction_exits += exits;
+            }
+            _ => {}
+        }
+    }
+
+    // Un recorrido desde cada nodo: los laberintos del juego son chicos
+    let longest_path = nodes
+        .iter()
+        .map(|&start| graph.distances(start).into_iter().filter(|&d| d != usize::MAX).max().unwrap_or(0))
+        .max()
+        .unwrap_or(0);
+
+    // En el inicio basta con dos salidas para tener que elegir; más adelante, una se usó para llegar
+    let solution = graph.solution().map(|path| {
+        let (spawn, goal) = (path[0], path[path.len() - 1]);
+        let decisions = path[..path.len() - 1]
+            .iter()
+            .filter(|&&node| graph.degree(node) >= if node == spawn { 2 } else { 3 })
+            .count();
+        Solution {
+            length: path.len() - 1,
+            decisions,
+            tortuosity: (path.len() - 1) as f32 / graph.manhattan(spawn, goal).max(1) as f32,
+        }
+    });
+
+    Stats {
+        rooms: graph.rooms,
+        open_cells: nodes.len(),
+        dead_ends,
+        junctions,
+        branching: if junctions == 0 { 0.0 } else { junction_exits as f32 / junctions as f32 },
+        longest_path,
+        corridor: graph.average_corridor(&nodes),
+        solution,
+    }
+}
+
+impl Stats {
+    // Puntaje de 0 a 100: qué tanto serpentea la solución y cuántas veces hay que elegir en ella.
+    // None si no hay solución
+    pub fn difficulty(&self) -> Option<f32> {
+        let solution = self.solution.as_ref()?;
+        let winding = 1.0 - 1.0 / solution.tortuosity.max(1.0);
+        let choices = solution.decisions as f32 / (solution.decisions as f32 + 4.0);
+        Some((winding + choices) / 2.0 * 100.0)
+    }
+}
+
+// Rangos de puntaje para elegir laberintos generados
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Difficulty {
+    Easy,
+    Medium,
+    Hard,
+}
+
+impl Difficulty {
+    pub fn of(score: f32) -> Difficulty {
+        [Difficulty::Easy, Difficulty::Medium, Difficulty::Hard]
+            .into_iter()
+            .find(|difficulty| difficulty.miss(score) == 0.0)
+            .unwrap_or(Difficulty::Hard)
+    }
+
+    fn band(self) -> (f32, f32) {
+        match self {
+            Difficulty::Easy => (0.0, 30.0),
+            Difficulty::Medium => (30.0, 45.0),
+            Difficulty::Hard => (45.0, 100.0),
+        }
+    }
+
+    // Cuánto le falta al puntaje para entrar en el rango; 0 si ya está adentro
+    pub fn miss(self, score: f32) -> f32 {
+        let (low, high) = self.band();
+        if score < low {
+            low - score
+        } else if score >= high {
+            score - high + f32::EPSILON
+        } else {
+            0.0
+        }
+    }
+
+    pub fn id(self) -> &'static str {
+        match self {
+            Difficulty::Easy => "easy",
+            Difficulty::Medium => "medium",
+            Difficulty::Hard => "hard",
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Difficulty::Easy => "fácil",
+            Difficulty::Medium => "media",
+            Difficulty::Hard => "difícil",
+        }
+    }
+}
+
+impl FromStr for Difficulty {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Difficulty, String> {
+        match name {
+            "easy" => Ok(Difficulty::Easy),
+            "medium" => Ok(Difficulty::Medium),
+            "hard" => Ok(Difficulty::Hard),
+            _ => Err(format!("dificultad desconocida '{}' (easy, medium o hard)", name)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Seis habitaciones: la de arriba al medio es la única bifurcación
+    const ROOMS: &str = "+--+--+--+\n|p       |\n+--+  +  +\n|   g |  |\n+--+--+--+";
+
+    // Un pasillo recto por celdas, sin el formato de habitaciones
+    const CELLS: &str = "+----+\n|p  g|\n+----+";
+
+    fn grid(contents: &str) -> Vec<Vec<char>> {
+        contents.lines().map(|line| line.chars().collect()).collect()
+    }
+
+    #[test]
+    fn measures_a_room_lattice_by_rooms() {
+        let stats = stats(&grid(ROOMS));
+        assert!(stats.rooms);
+        assert_eq!(stats.open_cells, 6);
+        assert_eq!(stats.dead_ends, 3);
+        assert_eq!(stats.junctions, 1);
+        assert_eq!(stats.branching, 3.0);
+        assert_eq!(stats.longest_path, 4);
+
+        let solution = stats.solution.unwrap();
+        assert_eq!(solution.length, 2);
+        assert_eq!(solution.decisions, 1);
+        assert_eq!(solution.tortuosity, 1.0);
+    }
+
+    #[test]
+    fn measures_other_maps_by_cells() {
+        let stats = stats(&grid(CELLS));
+        assert!(!stats.rooms);
+        assert_eq!(stats.open_cells, 3);
+        assert_eq!(stats.dead_ends, 2);
+        assert_eq!(stats.solution.unwrap().length, 2);
+    }
+
+    #[test]
+    fn solves_to_a_cell_touching_the_goal() {
+        let maze = grid(CELLS);
+        assert_eq!(solve(&maze), Some(vec![(1, 1), (2, 1), (3, 1)]));
+        assert_eq!(overlay(&maze, &[(1, 1), (2, 1)])[1], vec!['|', 'p', '.', ' ', 'g', '|']);
+    }
+
+    #[test]
+    fn cuts_and_links_change_the_solution() {
+        let maze = grid(CELLS);
+        let cut = [((0, (2, 1)), (0, (3, 1)))];
+        assert!(solve_linked(&[&maze], &[], &cut).is_none());
+        assert!(stats_linked(&maze, 0, &[], &cut).solution.is_none());
+
+        let link = [((0, (1, 1)), (0, (3, 1)))];
+        assert_eq!(solve_linked(&[&maze], &link, &cut), Some(vec![(0, (1, 1)), (0, (3, 1))]));
+        assert_eq!(stats_linked(&maze, 0, &link, &cut).solution.unwrap().length, 1);
+        // Los saltos de otro piso no cuentan
+        let elsewhere = [((1, (1, 1)), (1, (3, 1)))];
+        assert!(stats_linked(&maze, 0, &elsewhere, &cut).solution.is_none());
+    }
+
+    #[test]
+    fn a_cut_keeps_the_goal_out_of_reach() {
+        let maze = grid(CELLS);
+        let cut = [((0, (3, 1)), (0, (4, 1)))];
+        assert_eq!(solve_linked(&[&maze], &[], &cut), None);
+    }
+
+    #[test]
+    fn stairs_lead_between_floors() {
+        let ground = grid("+----+\n|p >||\n+----+");
+        let top = grid("+----+\n|g < |\n+----+");
+        let path = solve_floors(&[&ground, &top]).unwrap();
+        assert_eq!(path.first(), Some(&(0, (1, 1))));
+        assert_eq!(path.last(), Some(&(1, (2, 1))));
+        assert!(path.contains(&(1, (3, 1))));
+    }
+
+    #[test]
+    fn a_one_way_link_into_a_ring_ends() {
+        // Las cuatro celdas de abajo forman un anillo al que solo se entra por el salto
+        let maze = grid("+----+\n|p   |\n+----+\n|  |||\n|  |||\n+----+");
+        let link = [((0, (1, 1)), (0, (1, 3)))];
+        let stats = stats_linked(&maze, 0, &link, &[]);
+        assert_eq!(stats.open_cells, 8);
+        assert_eq!(stats.dead_ends, 1);
+        assert!(stats.corridor > 0.0);
+    }
+
+    #[test]
+    fn difficulty_bands() {
+        assert_eq!(Difficulty::of(10.0), Difficulty::Easy);
+        assert_eq!(Difficulty::of(30.0), Difficulty::Medium);
+        assert_eq!(Difficulty::of(99.0), Difficulty::Hard);
+        assert_eq!(Difficulty::Medium.miss(20.0), 10.0);
+        assert_eq!(Difficulty::Easy.miss(15.0), 0.0);
+        assert_eq!("hard".parse::<Difficulty>(), Ok(Difficulty::Hard));
+        assert!("imposible".parse::<Difficulty>().is_err());
+    }
+}
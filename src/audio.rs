@@ -27,7 +27,7 @@ impl AudioPlayer {
     }
 
     pub fn play(&self) {
-        if let Ok(mut sink) = self.sink.lock() {
+        if let Ok(sink) = self.sink.lock() {
             sink.play();
         } else {
             eprintln!("Failed to lock the sink for playback.");
@@ -35,7 +35,7 @@ impl AudioPlayer {
     }
 
     pub fn pause(&self) {
-        if let Ok(mut sink) = self.sink.lock() {
+        if let Ok(sink) = self.sink.lock() {
             sink.pause();
         } else {
             eprintln!("Failed to lock the sink to stop playback.");
@@ -44,10 +44,15 @@ impl AudioPlayer {
 
     // Método público para ajustar el volumen
     pub fn set_volume(&self, volume: f32) {
-        if let Ok(mut sink) = self.sink.lock() {
+        if let Ok(sink) = self.sink.lock() {
             sink.set_volume(volume);
         } else {
             eprintln!("Failed to lock the sink to set volume.");
         }
     }
-}
\ No newline at end of file
+}
+// Música de fondo y efectos del juego
+pub struct Sounds {
+    pub music: AudioPlayer,
+    pub steps: AudioPlayer,
+}
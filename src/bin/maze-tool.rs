@@ -0,0 +1,291 @@
+use std::error::Error;
+use std::path::Path;
+use std::str::FromStr;
+use maze_proyect::analysis::{self, Difficulty};
+use maze_proyect::image_map;
+use maze_proyect::level_map::{self, LevelMap};
+use maze_proyect::make_maze::{make_floors, make_maze_for, make_maze_with, save_maze_to_file, thin_walls, Algorithm};
+
+// Herramientas para preparar niveles sin abrir la ventana del juego
+const USAGE: &str = "Uso: maze-tool <comando>
+  generate [--algorithm backtracker|prim|kruskal] [--width N] [--height N] [--seed N]
+           [--difficulty easy|medium|hard] [--floors N] [--thin] [--output archivo]
+  validate archivo...
+  solve archivo
+  stats archivo
+  render archivo salida.png [--scale N]";
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let Some((command, args)) = args.split_first() else {
+        eprintln!("{}", USAGE);
+        std::process::exit(2);
+    };
+
+    let result = match command.as_str() {
+        "generate" => generate(args),
+        "validate" => validate(args),
+        "solve" => solve(args),
+        "stats" => stats(args),
+        "render" => render(args),
+        _ => {
+            eprintln!("{}", USAGE);
+            std::process::exit(2);
+        }
+    };
+    if let Err(e) = result {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    }
+}
+
+// Valor de `--nombre valor`, si se dio
+fn option<'a>(args: &'a [String], name: &str) -> Option<&'a str> {
+    args.iter().position(|arg| arg == name).and_then(|i| args.get(i + 1)).map(String::as_str)
+}
+
+fn parsed<T: FromStr>(args: &[String], name: &str, default: T) -> Result<T, String> {
+    match option(args, name) {
+        Some(value) => value.parse().map_err(|_| format!("valor inválido para {}: '{}'", name, value)),
+        None => Ok(default),
+    }
+}
+
+// Las opciones que van seguidas de un valor; las demás (como --thin) van solas
+const VALUE_OPTIONS: [&str; 8] = ["--algorithm", "--width", "--height", "--seed", "--difficulty", "--floors", "--output", "--scale"];
+
+// Los argumentos que no son opciones ni sus valores
+fn positional(args: &[String]) -> Vec<&str> {
+    let mut files = Vec::new();
+    let mut skip = false;
+    for arg in args {
+        if skip {
+            skip = false;
+        } else if arg.starts_with("--") {
+            skip = VALUE_OPTIONS.contains(&arg.as_str());
+        } else {
+            files.push(arg.as_str());
+        }
+    }
+    files
+}
+
+fn file_arg<'a>(args: &'a [String], index: usize, what: &str) -> Result<&'a str, String> {
+    positional(args).get(index).copied().ok_or_else(|| format!("falta {}\n{}", what, USAGE))
+}
+
+fn generate(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let algorithm: Algorithm = option(args, "--algorithm").unwrap_or("backtracker").parse()?;
+    let width = parsed(args, "--width", 6)?;
+    let height = parsed(args, "--height", 4)?;
+    if width == 0 || height == 0 {
+        return Err("el laberinto necesita al menos una celda".into());
+    }
+    let floors = parsed(args, "--floors", 1)?;
+    if floors == 0 {
+        return Err("el nivel necesita al menos un piso".into());
+    }
+    let seed = match option(args, "--seed") {
+        Some(_) => parsed(args, "--seed", 0)?,
+        None => {
+            let seed = rand::random::<u64>();
+            eprintln!("Semilla: {}", seed);
+            seed
+        }
+    };
+
+    let difficulty: Option<Difficulty> = option(args, "--difficulty").map(str::parse).transpose()?;
+    if floors > 1 || args.iter().any(|arg| arg == "--thin") {
+        return generate_floors(args, algorithm, difficulty, (width, height, floors), seed);
+    }
+
+    let maze = match difficulty {
+        Some(difficulty) => {
+            let (found, maze, matched) = make_maze_for(difficulty, algorithm, width, height, seed);
+            if !matched {
+                warn_missed(difficulty, width, height);
+            }
+            if found != seed {
+                eprintln!("Semilla con esa dificultad: {}", found);
+            }
+            maze
+        }
+        None => make_maze_with(algorithm, width, height, seed),
+    };
+    match option(args, "--output") {
+        Some(output) if output.ends_with(".json") => {
+            let name = format!("{:?} {}x{} ({})", algorithm, width, height, seed);
+            LevelMap::from_ascii(&name, &maze).save(output)?;
+        }
+        Some(output) => save_maze_to_file(output, &maze)?,
+        None => print_maze(&maze),
+    }
+    Ok(())
+}
+
+fn warn_missed(difficulty: Difficulty, width: usize, height: usize) {
+    eprintln!("Ningún laberinto de {}x{} salió con dificultad {}; se usa el más cercano", width, height, difficulty.name());
+}
+
+// Los niveles de varios pisos y los de paredes delgadas solo se pueden guardar en el formato de celdas
+fn generate_floors(args: &[String], algorithm: Algorithm, difficulty: Option<Difficulty>, (width, height, floors): (usize, usize, usize), seed: u64) -> Result<(), Box<dyn Error>> {
+    let (levels, matched) = make_floors(algorithm, difficulty, width, height, floors, seed);
+    if let (false, Some(difficulty)) = (matched, difficulty) {
+        warn_missed(difficulty, width, height);
+    }
+    let thin = args.iter().any(|arg| arg == "--thin");
+    match option(args, "--output") {
+        Some(output) if output.ends_with(".json") => {
+            let name = format!("{:?} {}x{}x{} ({})", algorithm, width, height, floors, seed);
+            if !thin {
+                LevelMap::from_floors(&name, &levels).save(output)?;
+                return Ok(());
+            }
+            let (grids, walls): (Vec<_>, Vec<_>) = levels.iter().map(|level| thin_walls(level)).unzip();
+            let mut map = LevelMap::from_floors(&name, &grids);
+            for (part, walls) in map.all_floors_mut().zip(walls) {
+                part.walls = walls;
+            }
+            map.save(output)?;
+        }
+        Some(_) if thin => return Err("el formato ASCII no tiene paredes delgadas; usa una salida .json".into()),
+        Some(_) => return Err("el formato ASCII no tiene pisos; usa una salida .json".into()),
+        None if thin => return Err("las paredes delgadas no se pueden mostrar en la terminal; usa --output nivel.json".into()),
+        None => print_floors(&levels),
+    }
+    Ok(())
+}
+
+fn validate(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let files = positional(args);
+    if files.is_empty() {
+        return Err(format!("falta el archivo\n{}", USAGE).into());
+    }
+
+    let mut failed = 0;
+    for file in &files {
+        match level_map::load(file) {
+            Ok(_) => println!("{}: válido", file),
+            Err(e) => {
+                println!("{}: {}", file, e);
+                failed += 1;
+            }
+        }
+    }
+    match failed {
+        0 => Ok(()),
+        n => Err(format!("{} de {} niveles tienen errores", n, files.len()).into()),
+    }
+}
+
+fn solve(args: &[String]) -> Result<(), Box<dyn Error>> {
+    // Los paneles y las paredes delgadas también cuentan para el camino
+    let map = level_map::load(file_arg(args, 0, "el archivo")?)?;
+    let floors = map.floors_ascii();
+    let path = analysis::solve_linked(&floors, &map.warp_links(), &map.wall_cuts()).ok_or("no hay camino desde el inicio hasta la meta")?;
+    let marked: Vec<_> = floors
+        .iter()
+        .enumerate()
+        .map(|(floor, maze)| {
+            let cells: Vec<_> = path.iter().filter(|&&(f, _)| f == floor).map(|&(_, pos)| pos).collect();
+            analysis::overlay(maze, &cells)
+        })
+        .collect();
+    print_floors(&marked);
+    println!("Solución: {} celdas", path.len() - 1);
+    Ok(())
+}
+
+fn stats(args: &[String]) -> Result<(), Box<dyn Error>> {
+    // Igual que en `solve`, los paneles y las paredes delgadas cambian los caminos
+    let map = level_map::load(file_arg(args, 0, "el archivo")?)?;
+    let floors = map.floors_ascii();
+    let (links, cuts) = (map.warp_links(), map.wall_cuts());
+    if let [maze] = floors.as_slice() {
+        print_stats(&analysis::stats_linked(maze, 0, &links, &cuts));
+        return Ok(());
+    }
+
+    // Las métricas de cada piso por separado, y la solución completa
+    for (floor, maze) in floors.iter().enumerate() {
+        println!("Piso {}:", floor + 1);
+        print_structure(&analysis::stats_linked(maze, floor, &links, &cuts));
+    }
+    match analysis::solve_linked(&floors, &links, &cuts) {
+        Some(path) => println!("Solución: {} celdas", path.len() - 1),
+        None => println!("Solución: no hay camino"),
+    }
+    Ok(())
+}
+
+fn print_stats(stats: &analysis::Stats) {
+    print_structure(stats);
+    let unit = if stats.rooms { "habitaciones" } else { "celdas" };
+    match &stats.solution {
+        Some(solution) => {
+            println!("Solución: {} {}", solution.length, unit);
+            println!("Decisiones en el camino: {}", solution.decisions);
+            println!("Tortuosidad: {:.2}", solution.tortuosity);
+        }
+        None => println!("Solución: no hay camino"),
+    }
+    if let Some(score) = stats.difficulty() {
+        println!("Dificultad: {:.0} ({})", score, Difficulty::of(score).name());
+    }
+}
+
+fn print_structure(stats: &analysis::Stats) {
+    // En los laberintos de habitaciones se cuentan habitaciones; en los demás, celdas
+    let unit = if stats.rooms { "habitaciones" } else { "celdas" };
+    println!("Tamaño: {} {}", stats.open_cells, unit);
+    println!("Callejones sin salida: {}", stats.dead_ends);
+    println!("Bifurcaciones: {} (salidas promedio: {:.2})", stats.junctions, stats.branching);
+    println!("Camino más largo: {} {}", stats.longest_path, unit);
+    println!("Pasillo promedio: {:.2} {}", stats.corridor, unit);
+}
+
+// Una imagen por piso: salida.png para la planta baja, salida-2.png para el segundo, etc.
+fn render(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let map = level_map::load(file_arg(args, 0, "el archivo")?)?;
+    let output = file_arg(args, 1, "la imagen de salida")?;
+    let scale = parsed(args, "--scale", 8)?;
+    if scale == 0 {
+        return Err("la escala debe ser al menos 1".into());
+    }
+    for (floor, (maze, part)) in map.floors_ascii().iter().zip(map.all_floors()).enumerate() {
+        let path = match floor {
+            0 => output.to_string(),
+            _ => {
+                let path = Path::new(output);
+                let stem = path.file_stem().map_or(output.into(), |stem| stem.to_string_lossy());
+                let file = match path.extension() {
+                    Some(ext) => format!("{}-{}.{}", stem, floor + 1, ext.to_string_lossy()),
+                    None => format!("{}-{}", stem, floor + 1),
+                };
+                path.with_file_name(file).to_string_lossy().into_owned()
+            }
+        };
+        image_map::save_image(&path, maze, &part.walls, scale)?;
+        println!("{}", path);
+    }
+    Ok(())
+}
+
+fn print_maze(maze: &[Vec<char>]) {
+    for row in maze {
+        println!("{}", row.iter().collect::<String>());
+    }
+}
+
+// Con varios pisos, cada uno con su título y separados por una línea en blanco
+fn print_floors(floors: &[Vec<Vec<char>>]) {
+    for (floor, maze) in floors.iter().enumerate() {
+        if floors.len() > 1 {
+            if floor > 0 {
+                println!();
+            }
+            println!("Piso {}:", floor + 1);
+        }
+        print_maze(maze);
+    }
+}
@@ -0,0 +1,268 @@
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use crate::enemy::GHOST;
+use crate::level_map::{is_level_map, LevelMap};
+use crate::analysis::Difficulty;
+use crate::make_maze::{make_floors, make_maze, make_maze_for, thin_walls, Algorithm};
+use crate::maze::{self, MazeError};
+use crate::palette::Palette;
+use crate::scores;
+use crate::segments::Segment;
+use crate::world::World;
+
+pub const CAMPAIGN_FILE: &str = "campaign.json";
+pub const PROGRESS_FILE: &str = "progress.json";
+
+// Lista ordenada de niveles que se juegan uno tras otro
+#[derive(Deserialize)]
+pub struct Campaign {
+    pub name: String,
+    pub levels: Vec<LevelEntry>,
+}
+
+// Un nivel se lee de un archivo (ASCII .txt o por celdas .json) o se genera a partir de una semilla
+#[derive(Deserialize)]
+pub struct LevelEntry {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub file: Option<String>,
+    #[serde(default)]
+    pub generate: Option<GeneratorSpec>,
+    #[serde(default)]
+    pub theme: Option<String>,   // Paleta de texturas; por defecto la que acompaña al archivo
+    #[serde(default)]
+    pub requires: String,        // Objetos necesarios para la meta, además de los de la paleta
+}
+
+#[derive(Deserialize)]
+pub struct GeneratorSpec {
+    pub width: usize,
+    pub height: usize,
+    pub seed: u64,
+    #[serde(default)]
+    pub ghosts: usize,  // Fantasmas repartidos al azar
+    #[serde(default)]
+    pub items: String,  // Caracteres de los objetos repartidos al azar
+    #[serde(default)]
+    pub difficulty: Option<Difficulty>, // Buscar desde `seed` una semilla con esta dificultad
+    #[serde(default = "one_floor")]
+    pub floors: usize, // Pisos unidos por escaleras, con la meta en el último
+    #[serde(default)]
+    pub thin_walls: bool, // Paredes delgadas entre los pasillos en lugar de bloques
+}
+
+fn one_floor() -> usize {
+    1
+}
+
+impl Campaign {
+    // Sin manifiesto, la campaña es solo el laberinto original
+    pub fn load(path: &str) -> Campaign {
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(_) => return Campaign::single("maze.txt"),
+        };
+
+        match serde_json::from_str::<Campaign>(&contents) {
+            Ok(campaign) if !campaign.levels.is_empty() => campaign,
+            Ok(_) => {
+                eprintln!("La campaña {} no tiene niveles", path);
+                Campaign::single("maze.txt")
+            }
+            Err(e) => {
+                eprintln!("No se pudo leer la campaña {}: {}", path, e);
+                Campaign::single("maze.txt")
+            }
+        }
+    }
+
+    fn single(maze_file: &str) -> Campaign {
+        Campaign {
+            name: "Pueblo Lavanda".to_string(),
+            levels: vec![LevelEntry {
+                id: maze_file.to_string(),
+                name: "Pueblo Lavanda".to_string(),
+                file: Some(maze_file.to_string()),
+                generate: None,
+                theme: None,
+                requires: String::new(),
+            }],
+        }
+    }
+}
+
+impl LevelEntry {
+    // Clave de la tabla de récords: el hash del archivo o la semilla y el tamaño
+    pub fn key(&self) -> String {
+        match (&self.file, &self.generate) {
+            (Some(file), _) => scores::maze_key(&fs::read_to_string(file).unwrap_or_default()),
+            (None, Some(spec)) => {
+                let mut key = format!("seed-{}-{}x{}", spec.seed, spec.width, spec.height);
+                if let Some(difficulty) = spec.difficulty {
+                    key += &format!("-{}", difficulty.id());
+                }
+                if spec.floors > 1 {
+                    key += &format!("-{}f", spec.floors);
+                }
+                if spec.thin_walls {
+                    key += "-thin";
+                }
+                key
+            }
+            (None, None) => self.id.clone(),
+        }
+    }
+
+    pub fn build(&self) -> Result<World, MazeError> {
+        let (map, palette) = match (&self.file, &self.generate) {
+            (Some(file), _) if is_level_map(file) => {
+                let map = LevelMap::read(file)?;
+                let palette = map.palette(file, self.theme.as_deref())?;
+                map.validate(&palette.wall_chars())?;
+                (map, palette)
+            }
+            (Some(file), _) => {
+                let palette = match &self.theme {
+                    Some(theme) => Palette::load(theme)?,
+                    None => Palette::load_for(file)?,
+                };
+                let grid = maze::load_maze(file, &palette.wall_chars())?;
+                (LevelMap::from_ascii(&self.name, &grid), palette)
+            }
+            (None, Some(spec)) => {
+                if spec.width == 0 || spec.height == 0 {
+                    return Err(MazeError::Format(format!("el generador del nivel {} necesita al menos una celda", self.id)));
+                }
+                let palette = match &self.theme {
+                    Some(theme) => Palette::load(theme)?,
+                    None => Palette::builtin()?,
+                };
+                let (floors, walls) = spec.build();
+                let mut map = LevelMap::from_floors(&self.name, &floors);
+                for (part, walls) in map.all_floors_mut().zip(walls) {
+                    part.walls = walls;
+                }
+                maze::validate_linked(&floors, &[], &map.wall_cuts())?;
+                (map, palette)
+            }
+            (None, None) => {
+                eprintln!("El nivel {} no tiene archivo ni generador, se usa maze.txt", self.id);
+                let palette = Palette::load_for("maze.txt")?;
+                let grid = maze::load_maze("maze.txt", &palette.wall_chars())?;
+                (LevelMap::from_ascii(&self.name, &grid), palette)
+            }
+        };
+
+        let mut world = World::new(map, palette);
+        world.require_items(&self.requires);
+        Ok(world)
+    }
+}
+
+impl GeneratorSpec {
+    // Un dibujo por piso, de abajo hacia arriba, con sus paredes delgadas
+    fn build(&self) -> (Vec<Vec<Vec<char>>>, Vec<Vec<Segment>>) {
+        let (mut floors, matched) = match (self.floors, self.difficulty) {
+            (2.., difficulty) => make_floors(Algorithm::Backtracker, difficulty, self.width, self.height, self.floors, self.seed),
+            (_, Some(difficulty)) => {
+                let (_, maze, matched) = make_maze_for(difficulty, Algorithm::Backtracker, self.width, self.height, self.seed);
+                (vec![maze], matched)
+            }
+            (_, None) => (vec![make_maze(self.width, self.height, self.seed)], true),
+        };
+        if let (false, Some(difficulty)) = (matched, self.difficulty) {
+            eprintln!("Ningún laberinto de {}x{} salió con dificultad {}; se usa el más cercano", self.width, self.height, difficulty.name());
+        }
+        let mut walls = vec![Vec::new(); floors.len()];
+        if self.thin_walls {
+            (floors, walls) = floors.iter().map(|grid| thin_walls(grid)).unzip();
+        }
+
+        // Repartir fantasmas y objetos en celdas libres de todos los pisos, lejos del inicio, con la misma semilla.
+        // Con paredes delgadas, cada celda del laberinto ocupa menos del mapa
+        let near = if self.thin_walls { 2 } else { 4 };
+        let mut free: Vec<(usize, usize, usize)> = Vec::new();
+        for (floor, grid) in floors.iter().enumerate() {
+            for (y, row) in grid.iter().enumerate() {
+                for (x, &cell) in row.iter().enumerate() {
+                    if cell == ' ' && (floor > 0 || x + y > near) {
+                        free.push((floor, x, y));
+                    }
+                }
+            }
+        }
+        let mut rng = StdRng::seed_from_u64(self.seed.wrapping_add(1));
+        free.shuffle(&mut rng);
+
+        let entities = std::iter::repeat_n(GHOST, self.ghosts).chain(self.items.chars().filter(|c| !c.is_whitespace()));
+        for (cell, (floor, x, y)) in entities.zip(free) {
+            floors[floor][y][x] = cell;
+        }
+
+        (floors, walls)
+    }
+}
+
+// Niveles completados, guardados entre partidas
+#[derive(Serialize, Deserialize, Default)]
+pub struct Progress {
+    completed: Vec<String>,
+}
+
+impl Progress {
+    pub fn load(path: &str) -> Progress {
+        match fs::read_to_string(path) {
+            Ok(contents) => serde_json::from_str(&contents).unwrap_or_else(|e| {
+                eprintln!("No se pudo leer el progreso de {}: {}", path, e);
+                Progress::default()
+            }),
+            Err(_) => Progress::default(),
+        }
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    pub fn is_completed(&self, id: &str) -> bool {
+        self.completed.iter().any(|done| done == id)
+    }
+
+    // El primer nivel siempre está disponible; los demás al completar el anterior
+    pub fn is_unlocked(&self, campaign: &Campaign, index: usize) -> bool {
+        index == 0 || self.is_completed(&campaign.levels[index - 1].id)
+    }
+
+    pub fn complete(&mut self, id: &str) {
+        if !self.is_completed(id) {
+            self.completed.push(id.to_string());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn generated(generate: &str) -> LevelEntry {
+        serde_json::from_str(&format!(r#"{{"id": "uno", "name": "Uno", "generate": {}}}"#, generate)).unwrap()
+    }
+
+    #[test]
+    fn builds_generated_levels() {
+        assert!(generated(r#"{"width": 3, "height": 2, "seed": 1}"#).build().is_ok());
+        assert!(generated(r#"{"width": 3, "height": 2, "seed": 1, "floors": 2, "thin_walls": true}"#).build().is_ok());
+    }
+
+    #[test]
+    fn rejects_generators_without_cells() {
+        for generate in [r#"{"width": 0, "height": 2, "seed": 1}"#, r#"{"width": 3, "height": 0, "seed": 1, "floors": 2}"#] {
+            assert!(matches!(generated(generate).build(), Err(MazeError::Format(_))));
+        }
+    }
+}
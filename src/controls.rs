@@ -1,18 +1,71 @@
-use minifb::{Key, Window};
+use minifb::{Key, KeyRepeat, Window};
 use std::f32::consts::PI;
 use crate::player::Player;
-use crate::audio::AudioPlayer;
+use crate::doors::{DoorEvent, Doors};
+use crate::maze::is_stairs;
+use crate::segments::Segment;
+use nalgebra::Vector2;
 
-pub fn process_events(window: &Window, player: &mut Player, maze: &Vec<Vec<char>>, block_size: usize, steps_player: &AudioPlayer) {
+// Teclas que afectan a la simulación en un tick. Se graba una por tick para las repeticiones
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+pub struct Input {
+    pub forward: bool,
+    pub back: bool,
+    pub left: bool,
+    pub right: bool,
+    pub interact: bool, // E, solo en el tick en que se presiona
+    pub repel: bool,    // R
+}
+
+impl Input {
+    // Lee el teclado. Las acciones se acumulan hasta que un tick las consume,
+    // para no perderlas en los cuadros en que no corre ningún tick
+    pub fn read(&mut self, window: &Window) {
+        self.forward = window.is_key_down(Key::W);
+        self.back = window.is_key_down(Key::S);
+        self.left = window.is_key_down(Key::A);
+        self.right = window.is_key_down(Key::D);
+        self.interact |= window.is_key_pressed(Key::E, KeyRepeat::No);
+        self.repel |= window.is_key_pressed(Key::R, KeyRepeat::No);
+    }
+
+    pub fn clear_actions(&mut self) {
+        self.interact = false;
+        self.repel = false;
+    }
+
+    // Una tecla por bit, para guardar las repeticiones de forma compacta
+    pub fn bits(self) -> u8 {
+        [self.forward, self.back, self.left, self.right, self.interact, self.repel]
+            .iter()
+            .enumerate()
+            .fold(0, |bits, (i, &down)| bits | ((down as u8) << i))
+    }
+
+    pub fn from_bits(bits: u8) -> Input {
+        let down = |i: u8| bits & (1 << i) != 0;
+        Input {
+            forward: down(0),
+            back: down(1),
+            left: down(2),
+            right: down(3),
+            interact: down(4),
+            repel: down(5),
+        }
+    }
+}
+
+// Mueve al jugador un tick. Devuelve si se movió y el resultado de usar una puerta, si lo hubo
+pub fn process_events(input: &Input, player: &mut Player, maze: &[Vec<char>], doors: &mut Doors, walls: &[Segment]) -> (bool, Option<DoorEvent>) {
     const MOVE_SPEED: f32 = 0.05;
     const ROTATION_SPEED: f32 = PI / 45.0;
     let mut moved = false;
 
     // Rotación del jugador con A y D
-    if window.is_key_down(Key::A) {
+    if input.left {
         player.a -= ROTATION_SPEED;
     }
-    if window.is_key_down(Key::D) {
+    if input.right {
         player.a += ROTATION_SPEED;
     }
 
@@ -20,12 +73,12 @@ pub fn process_events(window: &Window, player: &mut Player, maze: &Vec<Vec<char>
     let mut next_pos_y = player.pos.y;
 
     // Movimiento del jugador con W y S
-    if window.is_key_down(Key::W) {
+    if input.forward {
         next_pos_x += player.a.cos() * MOVE_SPEED;
         next_pos_y += player.a.sin() * MOVE_SPEED;
         moved = true;  // El jugador se ha movido
     }
-    if window.is_key_down(Key::S) {
+    if input.back {
         next_pos_x -= player.a.cos() * MOVE_SPEED;
         next_pos_y -= player.a.sin() * MOVE_SPEED;
         moved = true;  // El jugador se ha movido
@@ -35,17 +88,21 @@ pub fn process_events(window: &Window, player: &mut Player, maze: &Vec<Vec<char>
     let next_cell_x = next_pos_x as usize;
     let next_cell_y = next_pos_y as usize;
 
-    // Verificar si la siguiente posición está dentro del laberinto y no es una pared
-    if next_cell_x < maze[0].len() && next_cell_y < maze.len() && maze[next_cell_y][next_cell_x] == ' ' {
+    // Verificar si la siguiente posición está dentro del laberinto y no es una pared ni una puerta cerrada,
+    // y que el paso no atraviese una pared delgada
+    let next_pos = Vector2::new(next_pos_x, next_pos_y);
+    if next_cell_x < maze[0].len() && next_cell_y < maze.len()
+        && (maze[next_cell_y][next_cell_x] == ' ' || is_stairs(maze[next_cell_y][next_cell_x]) || doors.is_passable(next_cell_x, next_cell_y))
+        && !walls.iter().any(|wall| wall.blocks(player.pos, next_pos)) {
         // Si no es una pared, actualizamos la posición del jugador
         player.pos.x = next_pos_x;
         player.pos.y = next_pos_y;
     }
 
-    // Reproducir o pausar el sonido de los pasos dependiendo si el jugador se mueve o no
-    if moved {
-        steps_player.play();  // Reproducir sonido de pasos si el jugador se mueve
-    } else {
-        steps_player.pause();  // Pausar sonido de pasos si el jugador no se mueve
+    // Abrir o cerrar la puerta que está enfrente con E
+    if input.interact {
+        return (moved, doors.interact(&player.pos, player.a, &mut player.inventory));
     }
-}
\ No newline at end of file
+
+    (moved, None)
+}
@@ -0,0 +1,282 @@
+use std::collections::HashMap;
+use nalgebra::Vector2;
+use crate::items::{Inventory, ItemKind};
+use crate::save::DoorSave;
+
+pub const DOOR: char = 'D';
+pub const LOCKED_DOOR: char = 'L';
+
+const OPEN_SPEED: f32 = 1.5;     // Fracción de la puerta que se desliza por segundo
+const PASSABLE_OPEN: f32 = 0.9;  // A partir de esta apertura se puede cruzar
+const REACH: f32 = 1.2;          // Distancia máxima para interactuar con una puerta
+
+// Resultado de intentar usar una puerta
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum DoorEvent {
+    Opened,
+    Closed,
+    Unlocked,
+    NeedsKey,
+}
+
+pub struct Door {
+    pub locked: bool,
+    pub open: f32,        // 0.0 cerrada, 1.0 totalmente abierta
+    pub horizontal: bool, // La hoja de la puerta va de este a oeste (paredes a los lados)
+    opening: bool,
+    group: usize,         // Las celdas de puerta contiguas se abren juntas
+}
+
+impl Door {
+    pub fn is_passable(&self) -> bool {
+        self.open >= PASSABLE_OPEN
+    }
+}
+
+pub struct Doors {
+    doors: HashMap<(usize, usize), Door>,
+}
+
+impl Doors {
+    pub fn from_maze(maze: &[Vec<char>]) -> Doors {
+        let mut doors = HashMap::new();
+        let is_wall = |x: isize, y: isize| {
+            if x < 0 || y < 0 {
+                return true;
+            }
+            match maze.get(y as usize).and_then(|row| row.get(x as usize)) {
+                Some(&cell) => cell != ' ' && cell != DOOR && cell != LOCKED_DOOR,
+                None => true,
+            }
+        };
+
+        for (y, row) in maze.iter().enumerate() {
+            for (x, &cell) in row.iter().enumerate() {
+                if cell != DOOR && cell != LOCKED_DOOR {
+                    continue;
+                }
+
+                let (ix, iy) = (x as isize, y as isize);
+                doors.insert((x, y), Door {
+                    locked: cell == LOCKED_DOOR,
+                    open: 0.0,
+                    horizontal: is_wall(ix - 1, iy) || is_wall(ix + 1, iy),
+                    opening: false,
+                    group: usize::MAX,
+                });
+            }
+        }
+
+        let mut doors = Doors { doors };
+        doors.assign_groups(maze);
+        doors
+    }
+
+    // Agrupa las celdas de puerta del mismo tipo que se tocan (p. ej. un portón de dos celdas)
+    fn assign_groups(&mut self, maze: &[Vec<char>]) {
+        let mut cells: Vec<(usize, usize)> = self.doors.keys().copied().collect();
+        cells.sort();
+        let mut next_group = 0;
+
+        for start in cells {
+            if self.doors[&start].group != usize::MAX {
+                continue;
+            }
+
+            let kind = maze[start.1][start.0];
+            let mut pending = vec![start];
+            while let Some((x, y)) = pending.pop() {
+                match self.doors.get_mut(&(x, y)) {
+                    Some(door) if door.group == usize::MAX && maze[y][x] == kind => door.group = next_group,
+                    _ => continue,
+                }
+                pending.push((x + 1, y));
+                pending.push((x, y + 1));
+                if x > 0 {
+                    pending.push((x - 1, y));
+                }
+                if y > 0 {
+                    pending.push((x, y - 1));
+                }
+            }
+            next_group += 1;
+        }
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> Option<&Door> {
+        self.doors.get(&(x, y))
+    }
+
+    pub fn is_passable(&self, x: usize, y: usize) -> bool {
+        self.get(x, y).is_some_and(Door::is_passable)
+    }
+
+    // Usa la puerta que está frente al jugador, si la hay
+    pub fn interact(&mut self, pos: &Vector2<f32>, angle: f32, inventory: &mut Inventory) -> Option<DoorEvent> {
+        let direction = Vector2::new(angle.cos(), angle.sin());
+        let mut step = 0.25;
+        let target = loop {
+            if step > REACH {
+                return None;
+            }
+            let probe = pos + direction * step;
+            let cell = (probe.x as usize, probe.y as usize);
+            if self.doors.contains_key(&cell) {
+                break cell;
+            }
+            step += 0.25;
+        };
+
+        let door = &self.doors[&target];
+        let group = door.group;
+        let current = (pos.x as usize, pos.y as usize);
+
+        let event = if door.locked {
+            if !inventory.take(ItemKind::Key) {
+                return Some(DoorEvent::NeedsKey);
+            }
+            DoorEvent::Unlocked
+        } else if door.opening {
+            // No cerrar la puerta sobre el jugador
+            if self.group_contains(group, current) {
+                return None;
+            }
+            DoorEvent::Closed
+        } else {
+            DoorEvent::Opened
+        };
+
+        for door in self.doors.values_mut().filter(|door| door.group == group) {
+            door.locked = false;
+            door.opening = event != DoorEvent::Closed;
+        }
+        Some(event)
+    }
+
+    fn group_contains(&self, group: usize, cell: (usize, usize)) -> bool {
+        self.doors.get(&cell).is_some_and(|door| door.group == group)
+    }
+
+    pub fn snapshot(&self) -> Vec<DoorSave> {
+        let mut saves: Vec<DoorSave> = self.doors.iter()
+            .map(|(&(x, y), door)| DoorSave { x, y, locked: door.locked, open: door.open, opening: door.opening })
+            .collect();
+        saves.sort_by_key(|save| (save.y, save.x));
+        saves
+    }
+
+    // Devuelve false si alguna puerta guardada no existe en este mapa
+    pub fn restore(&mut self, saves: &[DoorSave]) -> bool {
+        for save in saves {
+            let Some(door) = self.doors.get_mut(&(save.x, save.y)) else {
+                return false;
+            };
+            door.locked = save.locked;
+            door.open = save.open;
+            door.opening = save.opening;
+        }
+        true
+    }
+
+    // Deslizar las puertas hacia su estado objetivo
+    pub fn update(&mut self, dt: f32) {
+        for door in self.doors.values_mut() {
+            let target = if door.opening { 1.0 } else { 0.0 };
+            let step = OPEN_SPEED * dt;
+            door.open = if door.open < target {
+                (door.open + step).min(target)
+            } else {
+                (door.open - step).max(target)
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::FRAC_PI_2;
+
+    // Un portón de dos celdas arriba y, abajo, una reja con llave pegada a una puerta común
+    const MAP: [&str; 6] = ["+------+", "|  DD  |", "|      |", "|  L   |", "|  D   |", "+------+"];
+
+    fn gate_map() -> Doors {
+        let maze: Vec<Vec<char>> = MAP.iter().map(|row| row.chars().collect()).collect();
+        Doors::from_maze(&maze)
+    }
+
+    fn group(doors: &Doors, x: usize, y: usize) -> usize {
+        doors.get(x, y).unwrap().group
+    }
+
+    fn state(doors: &Doors) -> Vec<(usize, usize, bool, f32, bool)> {
+        doors.snapshot().iter().map(|save| (save.x, save.y, save.locked, save.open, save.opening)).collect()
+    }
+
+    #[test]
+    fn groups_touching_doors_of_the_same_kind() {
+        let doors = gate_map();
+        assert_eq!(group(&doors, 3, 1), group(&doors, 4, 1));
+        assert_ne!(group(&doors, 3, 1), group(&doors, 3, 3));
+        assert_ne!(group(&doors, 3, 3), group(&doors, 3, 4));
+        assert!(doors.get(3, 3).unwrap().locked);
+        assert!(!doors.get(3, 4).unwrap().locked);
+    }
+
+    #[test]
+    fn opens_and_closes_a_whole_group() {
+        let mut doors = gate_map();
+        let mut inventory = Inventory::default();
+        let below = Vector2::new(3.5, 2.5);
+
+        assert!(doors.interact(&below, -FRAC_PI_2, &mut inventory) == Some(DoorEvent::Opened));
+        assert!(!doors.is_passable(4, 1));
+        doors.update(1.0);
+        assert!(doors.is_passable(3, 1) && doors.is_passable(4, 1));
+
+        // Desde adentro de la puerta no se cierra; desde afuera sí
+        assert!(doors.interact(&Vector2::new(3.5, 1.5), -FRAC_PI_2, &mut inventory).is_none());
+        assert!(doors.interact(&below, -FRAC_PI_2, &mut inventory) == Some(DoorEvent::Closed));
+        doors.update(1.0);
+        assert!(!doors.is_passable(4, 1));
+
+        // Mirando hacia la pared no hay puerta al alcance
+        assert!(doors.interact(&below, 0.0, &mut inventory).is_none());
+    }
+
+    #[test]
+    fn locked_doors_need_a_key() {
+        let mut doors = gate_map();
+        let mut inventory = Inventory::default();
+        let above = Vector2::new(3.5, 2.5);
+
+        assert!(doors.interact(&above, FRAC_PI_2, &mut inventory) == Some(DoorEvent::NeedsKey));
+        assert!(doors.get(3, 3).unwrap().locked);
+
+        inventory.add(ItemKind::Key);
+        assert!(doors.interact(&above, FRAC_PI_2, &mut inventory) == Some(DoorEvent::Unlocked));
+        assert_eq!(inventory.count(ItemKind::Key), 0);
+        doors.update(1.0);
+        assert!(doors.is_passable(3, 3));
+        // La puerta común de al lado es otro grupo y sigue cerrada
+        assert!(!doors.is_passable(3, 4));
+    }
+
+    #[test]
+    fn restores_a_snapshot() {
+        let mut doors = gate_map();
+        let mut inventory = Inventory::default();
+        inventory.add(ItemKind::Key);
+        doors.interact(&Vector2::new(3.5, 2.5), FRAC_PI_2, &mut inventory);
+        doors.update(0.5);
+
+        let saved = doors.snapshot();
+        let mut restored = gate_map();
+        assert!(restored.restore(&saved));
+        assert_eq!(state(&restored), state(&doors));
+        assert_eq!(state(&restored)[2], (3, 3, false, 0.75, true));
+
+        let elsewhere = DoorSave { x: 1, y: 1, locked: false, open: 0.0, opening: false };
+        assert!(!restored.restore(&[elsewhere]));
+    }
+}
@@ -0,0 +1,287 @@
+use std::path::Path;
+use crate::doors::{DOOR, LOCKED_DOOR};
+use crate::enemy::GHOST;
+use crate::items::ItemKind;
+use crate::level_map::{self, Entity, LevelMap};
+use crate::make_maze::save_maze_to_file;
+use crate::maze::{self, MazeError, GOAL, SPAWN, STAIRS_DOWN, STAIRS_UP};
+use crate::palette::{self, Palette};
+use crate::segments::Segment;
+use crate::world::World;
+
+// Lo que se puede pintar, en el orden en que se recorre con las flechas
+pub const BRUSHES: [char; 13] = ['|', ' ', SPAWN, GOAL, GHOST, DOOR, LOCKED_DOOR, STAIRS_UP, STAIRS_DOWN, 'k', 'o', 'h', 's'];
+
+// Pasos que se pueden deshacer antes de olvidar los más viejos
+const HISTORY: usize = 100;
+
+// Tamaño de un laberinto nuevo, en celdas
+const NEW_WIDTH: usize = 13;
+const NEW_HEIGHT: usize = 9;
+
+pub fn brush_name(brush: char) -> &'static str {
+    match brush {
+        '|' => "Pared",
+        ' ' => "Piso",
+        SPAWN => "Inicio",
+        GOAL => "Meta",
+        GHOST => "Fantasma",
+        DOOR => "Puerta",
+        LOCKED_DOOR => "Puerta con llave",
+        STAIRS_UP => "Escalera que sube",
+        STAIRS_DOWN => "Escalera que baja",
+        _ => ItemKind::from_cell(brush).map_or("?", |kind| kind.name()),
+    }
+}
+
+// Un dibujo ASCII por piso, de abajo hacia arriba
+type Floors = Vec<Vec<Vec<char>>>;
+
+// Un cuarto cerrado y vacío
+fn closed_room(width: usize, height: usize) -> Vec<Vec<char>> {
+    let mut grid = vec![vec![' '; width]; height];
+    for (y, row) in grid.iter_mut().enumerate() {
+        for (x, cell) in row.iter_mut().enumerate() {
+            if x == 0 || y == 0 || x + 1 == width || y + 1 == height {
+                *cell = '|';
+            }
+        }
+    }
+    grid
+}
+
+// Un cuarto cerrado con el inicio en una esquina y la meta en la opuesta
+fn empty_room() -> Vec<Vec<char>> {
+    let mut grid = closed_room(NEW_WIDTH, NEW_HEIGHT);
+    grid[1][1] = SPAWN;
+    grid[NEW_HEIGHT - 2][NEW_WIDTH - 2] = GOAL;
+    grid
+}
+
+// Laberinto que se edita como dibujo ASCII, con las entidades en sus celdas
+pub struct Editor {
+    pub path: String,
+    floors: Floors,
+    pub floor: usize, // Piso que se está editando
+    brush: usize,
+    pub dirty: bool, // Hay cambios sin guardar
+    theme: Option<String>,
+    original: Option<LevelMap>, // Para no perder los datos que el dibujo no muestra
+    undo: Vec<Floors>,
+    redo: Vec<Floors>,
+    painting: bool, // Un trazo del mouse se deshace de una vez
+}
+
+impl Editor {
+    // Abre el nivel sin validarlo, para poder arreglar uno roto. Si no existe, empieza uno nuevo
+    pub fn open(path: &str, theme: Option<String>) -> Result<Editor, MazeError> {
+        let (floors, original) = if !Path::new(path).exists() {
+            (vec![empty_room()], None)
+        } else if level_map::is_level_map(path) {
+            let map = LevelMap::read(path)?;
+            (map.floors_ascii(), Some(map))
+        } else {
+            let walls = match theme.as_deref() {
+                Some(theme) => palette::wall_chars_for(theme, ""),
+                None => palette::wall_chars_for(Path::new(path).with_extension("palette"), ""),
+            };
+            (vec![maze::parse_maze(&maze::read_file(path)?, &walls)?], None)
+        };
+
+        Ok(Editor {
+            path: path.to_string(),
+            floors,
+            floor: 0,
+            brush: 0,
+            dirty: false,
+            theme,
+            original,
+            undo: Vec::new(),
+            redo: Vec::new(),
+            painting: false,
+        })
+    }
+
+    fn palette_for(path: &str, theme: Option<&str>, map: Option<&LevelMap>) -> Result<Palette, MazeError> {
+        match (map, theme) {
+            (Some(map), _) => map.palette(path, theme),
+            (None, Some(theme)) => Palette::load(theme),
+            (None, None) => Palette::load_for(path),
+        }
+    }
+
+    // El piso que se está editando
+    pub fn grid(&self) -> &[Vec<char>] {
+        &self.floors[self.floor]
+    }
+
+    pub fn floor_count(&self) -> usize {
+        self.floors.len()
+    }
+
+    pub fn change_floor(&mut self, step: isize) -> bool {
+        let floor = self.floor as isize + step;
+        if floor < 0 || floor as usize >= self.floors.len() {
+            return false;
+        }
+        self.floor = floor as usize;
+        true
+    }
+
+    // Agrega un piso vacío del mismo tamaño encima del último y pasa a editarlo
+    pub fn add_floor(&mut self) {
+        let height = self.grid().len();
+        let width = self.grid().first().map_or(0, |row| row.len());
+        self.push_undo();
+        self.floors.push(closed_room(width, height));
+        self.floor = self.floors.len() - 1;
+        self.dirty = true;
+    }
+
+    // Quita el piso que se está editando, si no es el único
+    pub fn remove_floor(&mut self) -> bool {
+        if self.floors.len() == 1 {
+            return false;
+        }
+        self.push_undo();
+        self.floors.remove(self.floor);
+        self.floor = self.floor.min(self.floors.len() - 1);
+        self.dirty = true;
+        true
+    }
+
+    pub fn brush(&self) -> char {
+        BRUSHES[self.brush]
+    }
+
+    pub fn next_brush(&mut self, step: isize) {
+        self.brush = (self.brush as isize + step).rem_euclid(BRUSHES.len() as isize) as usize;
+    }
+
+    // Todo lo pintado hasta `end_stroke` cuenta como un solo paso de la historia
+    pub fn paint(&mut self, x: usize, y: usize, cell: char) {
+        let Some(&current) = self.grid().get(y).and_then(|row| row.get(x)) else {
+            return;
+        };
+        if current == cell {
+            return;
+        }
+
+        if !self.painting {
+            self.painting = true;
+            self.push_undo();
+        }
+        // Solo puede haber un inicio en todo el nivel: pintarlo en otro lado lo mueve
+        if cell == SPAWN {
+            for old in self.floors.iter_mut().flatten().flatten().filter(|old| **old == SPAWN) {
+                *old = ' ';
+            }
+        }
+        self.floors[self.floor][y][x] = cell;
+        self.dirty = true;
+    }
+
+    pub fn end_stroke(&mut self) {
+        self.painting = false;
+    }
+
+    fn push_undo(&mut self) {
+        self.undo.push(self.floors.clone());
+        if self.undo.len() > HISTORY {
+            self.undo.remove(0);
+        }
+        self.redo.clear();
+    }
+
+    pub fn undo(&mut self) -> bool {
+        let Some(floors) = self.undo.pop() else {
+            return false;
+        };
+        self.redo.push(std::mem::replace(&mut self.floors, floors));
+        self.floor = self.floor.min(self.floors.len() - 1);
+        self.dirty = true;
+        true
+    }
+
+    pub fn redo(&mut self) -> bool {
+        let Some(floors) = self.redo.pop() else {
+            return false;
+        };
+        self.undo.push(std::mem::replace(&mut self.floors, floors));
+        self.floor = self.floor.min(self.floors.len() - 1);
+        self.dirty = true;
+        true
+    }
+
+    // Las paredes delgadas del piso actual: se muestran y se conservan, pero no se editan
+    pub fn walls(&self) -> &[Segment] {
+        let part = self.original.as_ref().and_then(|original| original.all_floors().nth(self.floor));
+        part.map_or(&[], |part| &part.walls)
+    }
+
+    // Las mismas reglas que al cargar el nivel para jugarlo; los paneles y las paredes
+    // delgadas del original cuentan al buscar el camino
+    pub fn validate(&self) -> Result<(), MazeError> {
+        let links = self.original.as_ref().map(LevelMap::warp_links).unwrap_or_default();
+        let cuts = self.original.as_ref().map(LevelMap::wall_cuts).unwrap_or_default();
+        maze::validate_linked(&self.floors, &links, &cuts)
+    }
+
+    // El nivel tal como quedaría guardado, conservando los datos del original
+    fn level_map(&self) -> LevelMap {
+        let name = Path::new(&self.path).file_stem().map_or(self.path.clone(), |stem| stem.to_string_lossy().into_owned());
+        let mut map = LevelMap::from_floors(&name, &self.floors);
+        let Some(original) = &self.original else {
+            return map;
+        };
+
+        map.name = original.name.clone();
+        map.author = original.author.clone();
+        map.theme = original.theme.clone();
+        map.palette = original.palette.clone();
+        map.spawn.angle = original.spawn.angle;
+        for (part, old_part) in map.all_floors_mut().zip(original.all_floors()) {
+            for entity in part.entities.iter_mut() {
+                if let Entity::Ghost { x, y, rate } = entity {
+                    // Un fantasma que sigue en su lugar conserva su velocidad
+                    *rate = old_part.entities.iter().find_map(|old| match *old {
+                        Entity::Ghost { x: ox, y: oy, rate } if (ox, oy) == (*x, *y) => rate,
+                        _ => None,
+                    });
+                }
+            }
+            part.entities.extend(old_part.entities.iter().filter(|entity| matches!(entity, Entity::Light { .. } | Entity::Warp { .. })).cloned());
+            part.walls = old_part.walls.clone();
+        }
+        map
+    }
+
+    // Mundo para la vista previa en 3D
+    pub fn world(&self) -> Result<World, MazeError> {
+        let map = self.level_map();
+        let palette = Editor::palette_for(&self.path, self.theme.as_deref(), self.original.as_ref())?;
+        Ok(World::new(map, palette))
+    }
+
+    // Guarda en el formato del archivo. Las imágenes y los mapas de Tiled no se pueden
+    // escribir, así que se guardan como .json a su lado. Devuelve dónde se guardó
+    pub fn save(&mut self) -> Result<String, Box<dyn std::error::Error>> {
+        self.validate()?;
+        let output = if level_map::is_level_map(&self.path) && !self.path.ends_with(".json") {
+            Path::new(&self.path).with_extension("json").to_string_lossy().into_owned()
+        } else {
+            self.path.clone()
+        };
+
+        if output.ends_with(".json") {
+            self.level_map().save(&output)?;
+        } else if self.floors.len() > 1 {
+            return Err("el formato ASCII no tiene pisos; guarda el nivel como .json".into());
+        } else {
+            save_maze_to_file(&output, &self.floors[0])?;
+        }
+        self.dirty = false;
+        self.path = output.clone();
+        Ok(output)
+    }
+}
@@ -0,0 +1,111 @@
+use nalgebra::Vector2;
+use std::f32::consts::PI;
+use crate::save::EnemySave;
+use crate::sprites::Animator;
+
+pub const GHOST: char = 'f'; // Carácter de los fantasmas en el mapa
+
+const DRIFT_RADIUS: f32 = 0.25;  // Radio del círculo que recorre el fantasma alrededor de su origen
+const DRIFT_SPEED: f32 = 0.6;    // Velocidad angular del recorrido (rad/s)
+const TURN_SPEED: f32 = PI;      // Velocidad máxima de giro (rad/s)
+const NOTICE_DISTANCE: f32 = 3.0; // A esta distancia el fantasma se gira hacia el jugador
+const FLEE_SPEED: f32 = 1.5;     // Velocidad con la que huye cuando lo ahuyentan
+const REPEL_TIME: f32 = 4.0;     // Segundos que huye un fantasma ahuyentado
+const TOUCH_DISTANCE: f32 = 0.6; // A esta distancia el fantasma toca al jugador
+
+pub struct Enemy {
+    pub pos: Vector2<f32>,
+    pub facing: f32,     // Ángulo hacia donde mira el fantasma
+    pub bob: f32,        // Desplazamiento vertical de flotación (-1.0 a 1.0)
+    pub alpha: f32,      // Opacidad actual (0.0 invisible, 1.0 opaco)
+    pub animator: Animator,
+    pub repelled: f32,   // Segundos que le quedan huyendo del jugador
+    home: Vector2<f32>,
+    phase: f32,
+}
+
+impl Enemy {
+    pub fn new(x: f32, y: f32, rate: f32) -> Enemy {
+        // Desfasar a cada fantasma para que no floten sincronizados
+        let phase = (x * 12.9898 + y * 78.233).sin().abs() * 2.0 * PI;
+
+        Enemy {
+            pos: Vector2::new(x, y),
+            facing: 0.0,
+            bob: 0.0,
+            alpha: 1.0,
+            animator: Animator::new(rate),
+            repelled: 0.0,
+            home: Vector2::new(x, y),
+            phase,
+        }
+    }
+
+    // Actualiza al fantasma y devuelve si toca al jugador (un fantasma ahuyentado no lo toca)
+    pub fn update(&mut self, dt: f32, player_pos: &Vector2<f32>) -> bool {
+        self.animator.update(dt);
+        let t = self.animator.time + self.phase;
+
+        // Huir del jugador mientras dure el efecto
+        let away = self.home - player_pos;
+        if self.repelled > 0.0 {
+            self.repelled -= dt;
+            if away.norm() > 0.0 {
+                self.home += away.normalize() * FLEE_SPEED * dt;
+            }
+        }
+
+        // Flotar: recorrer un pequeño círculo y subir y bajar
+        let drift = t * DRIFT_SPEED;
+        let previous = self.pos;
+        self.pos = self.home + Vector2::new(drift.cos(), drift.sin()) * DRIFT_RADIUS;
+        self.bob = (t * 2.0).sin();
+
+        // Desvanecerse: la opacidad oscila lentamente entre 0.35 y 0.95
+        self.alpha = 0.65 + 0.3 * (t * 0.8).sin();
+
+        // Girar: mirar hacia el jugador si está cerca, si no hacia donde se mueve
+        let to_player = player_pos - self.pos;
+        let target = if self.repelled <= 0.0 && to_player.norm() < NOTICE_DISTANCE {
+            to_player.y.atan2(to_player.x)
+        } else {
+            let motion = self.pos - previous;
+            motion.y.atan2(motion.x)
+        };
+        self.turn_towards(target, dt);
+
+        self.repelled <= 0.0 && to_player.norm() < TOUCH_DISTANCE
+    }
+
+    pub fn snapshot(&self) -> EnemySave {
+        EnemySave {
+            pos: [self.pos.x, self.pos.y],
+            home: [self.home.x, self.home.y],
+            facing: self.facing,
+            repelled: self.repelled,
+            time: self.animator.time,
+            rate: self.animator.rate,
+            phase: self.phase,
+        }
+    }
+
+    pub fn restore(save: &EnemySave) -> Enemy {
+        let mut enemy = Enemy::new(save.home[0], save.home[1], save.rate);
+        enemy.pos = Vector2::new(save.pos[0], save.pos[1]);
+        enemy.facing = save.facing;
+        enemy.repelled = save.repelled;
+        enemy.animator.time = save.time;
+        enemy.phase = save.phase;
+        enemy
+    }
+
+    pub fn repel(&mut self) {
+        self.repelled = REPEL_TIME;
+    }
+
+    fn turn_towards(&mut self, target: f32, dt: f32) {
+        let diff = (target - self.facing + PI).rem_euclid(2.0 * PI) - PI;
+        let max_step = TURN_SPEED * dt;
+        self.facing += diff.clamp(-max_step, max_step);
+    }
+}
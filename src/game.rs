@@ -0,0 +1,190 @@
+use nalgebra::Vector2;
+use crate::campaign::{Campaign, LevelEntry};
+use crate::controls::{process_events, Input};
+use crate::doors::DoorEvent;
+use crate::items::{Inventory, ItemKind};
+use crate::maze::MazeError;
+use crate::player::Player;
+use crate::scores::RunStats;
+use crate::level_map::WarpTarget;
+use crate::world::{cell_center, Floor, World};
+
+// La simulación avanza en pasos fijos para que la misma secuencia de teclas
+// produzca siempre la misma partida, sin importar los FPS
+pub const TICK_RATE: u32 = 60;
+pub const TICK: f32 = 1.0 / TICK_RATE as f32;
+
+// Duración del fundido al usar un panel: la pantalla se oscurece, el jugador aparece
+// en el destino a la mitad y la pantalla se vuelve a aclarar
+const WARP_TICKS: u32 = TICK_RATE / 2;
+
+// Viaje en curso por un panel. Mientras dura, el jugador no se mueve
+pub struct Warping {
+    pub to: WarpTarget,
+    tick: u32,
+}
+
+// Todo lo que cambia mientras se juega un nivel
+pub struct GameState {
+    pub world: World,
+    pub player: Player,
+    pub stats: RunStats,
+    pub ticks: u64, // Ticks simulados desde que empezó el nivel
+    pub warping: Option<Warping>,
+}
+
+// Lo que pasó en un tick y debe reflejarse en pantalla o en el sonido
+pub struct Tick {
+    pub moved: bool,
+    pub message: Option<String>,
+    pub finished: bool,            // El jugador entró a la meta
+    pub warp: Option<WarpTarget>, // Un panel lleva a otro nivel; quien juega debe cargarlo
+}
+
+impl GameState {
+    // Partida nueva: el nivel recién cargado y el jugador en el inicio
+    pub fn new(level: &LevelEntry) -> Result<GameState, MazeError> {
+        Ok(GameState::from_world(level.build()?))
+    }
+
+    pub fn from_world(world: World) -> GameState {
+        let player = Player {
+            pos: world.spawn,
+            a: world.spawn_angle,
+            fov: std::f32::consts::FRAC_PI_3,
+            inventory: Inventory::default(),
+        };
+        GameState { world, player, stats: RunStats::default(), ticks: 0, warping: None }
+    }
+
+    // Opacidad del fundido a negro de los paneles, de 0.0 a 1.0
+    pub fn fade(&self) -> f32 {
+        self.warping.as_ref().map_or(0.0, |warping| {
+            let half = (WARP_TICKS / 2) as f32;
+            1.0 - (warping.tick as f32 - half).abs() / half
+        })
+    }
+
+    // Pasa la partida al nivel al que lleva un panel, con el mismo jugador, tiempo y
+    // estadísticas, a mitad del fundido. Devuelve la posición del nivel en la campaña
+    pub fn enter_level(&mut self, campaign: &Campaign, to: &WarpTarget) -> Result<usize, String> {
+        let id = to.level.as_deref().unwrap_or_default();
+        let index = campaign.levels.iter().position(|level| level.id == id).ok_or_else(|| format!("El nivel {} no está en la campaña", id))?;
+        let mut next = GameState::new(&campaign.levels[index]).map_err(|e| e.to_string())?;
+
+        let free = next.world.floors.get(to.floor).and_then(|floor| floor.maze.get(to.y)).and_then(|row| row.get(to.x)) == Some(&' ');
+        if !free {
+            return Err(format!("El panel lleva a ({}, {}), que no es una celda libre de {}", to.x, to.y, id));
+        }
+        next.world.floor = to.floor;
+        next.warping = Some(Warping { to: to.clone(), tick: WARP_TICKS / 2 });
+
+        let previous = std::mem::replace(self, next);
+        self.player = Player { pos: cell_center((to.x, to.y)), ..previous.player };
+        self.stats = previous.stats;
+        self.ticks = previous.ticks;
+        Ok(index)
+    }
+
+    // Avanza el fundido; a la mitad mueve al jugador, o avisa si el destino es otro nivel
+    fn warp_tick(&mut self) -> Tick {
+        let mut tick = Tick { moved: false, message: None, finished: false, warp: None };
+        let Some(warping) = &mut self.warping else {
+            return tick;
+        };
+
+        warping.tick += 1;
+        if warping.tick == WARP_TICKS / 2 {
+            match warping.to.level {
+                Some(_) => tick.warp = Some(warping.to.clone()),
+                None => {
+                    self.world.floor = warping.to.floor;
+                    self.player.pos = cell_center((warping.to.x, warping.to.y));
+                }
+            }
+        }
+        if warping.tick >= WARP_TICKS {
+            self.warping = None;
+        }
+        self.stats.time += TICK;
+        self.ticks += 1;
+        tick
+    }
+
+    pub fn tick(&mut self, input: &Input) -> Tick {
+        if self.warping.is_some() {
+            return self.warp_tick();
+        }
+
+        let GameState { world, player, stats, ticks, warping } = self;
+        let mut message = None;
+
+        let previous_pos = player.pos;
+        let Floor { maze, doors, walls, .. } = world.current_mut();
+        let (moved, door_event) = process_events(input, player, maze, doors, walls);
+        if let Some(event) = door_event {
+            message = Some(match event {
+                DoorEvent::Opened => "La puerta se abre",
+                DoorEvent::Closed => "La puerta se cierra",
+                DoorEvent::Unlocked => "Usaste una llave",
+                DoorEvent::NeedsKey => "Necesitas una llave",
+            }.to_string());
+        }
+
+        // Al entrar a una escalera se cambia de piso. Se llega sobre la escalera contraria,
+        // que no se activa hasta salir de ella y volver a entrar
+        // Los paneles funcionan igual: el de destino no se activa hasta volver a entrar
+        let cell = |pos: &Vector2<f32>| (pos.x as usize, pos.y as usize);
+        if cell(&player.pos) != cell(&previous_pos) {
+            let from = world.floor;
+            let (x, y) = cell(&player.pos);
+            if let Some(floor) = world.take_stairs(&player.pos) {
+                let verb = if floor > from { "Subiste" } else { "Bajaste" };
+                message = Some(format!("{} al piso {}", verb, floor + 1));
+            } else if let Some(warp) = world.current().warp_at(x, y) {
+                *warping = Some(Warping { to: warp.to.clone(), tick: 0 });
+            }
+        }
+
+        // Actualizar los fantasmas y las puertas
+        stats.damage += world.update(TICK, &player.pos);
+        stats.time += TICK;
+        stats.distance += (player.pos - previous_pos).norm();
+        *ticks += 1;
+
+        // Con el Scope Silph se puede ahuyentar a los fantasmas con R
+        if input.repel {
+            message = Some(if !player.inventory.has(ItemKind::SilphScope) {
+                "Algo te observa... pero no puedes verlo".to_string()
+            } else {
+                match world.repel_ghosts(&player.pos, player.a) {
+                    0 => "No hay fantasmas cerca".to_string(),
+                    n => format!("Ahuyentaste {} fantasma(s)", n),
+                }
+            });
+        }
+
+        // Recoger los objetos al pasar sobre ellos
+        for kind in world.pick_up_items(&player.pos) {
+            player.inventory.add(kind);
+            stats.items += 1;
+            message = Some(format!("Recogiste: {}", kind.name()));
+        }
+
+        // La meta puede exigir objetos antes de dejar entrar
+        let mut finished = false;
+        if world.reached_goal(&player.pos) {
+            let missing: Vec<&str> = world.missing_requirements(&player.inventory)
+                .iter()
+                .map(|kind| kind.name())
+                .collect();
+            if missing.is_empty() {
+                finished = true;
+            } else {
+                message = Some(format!("Necesitas: {}", missing.join(", ")));
+            }
+        }
+
+        Tick { moved, message, finished, warp: None }
+    }
+}
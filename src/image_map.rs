@@ -0,0 +1,200 @@
+use std::fs;
+use std::path::Path;
+use nalgebra::Vector2;
+use crate::level_map::{parse_color, LevelMap};
+use crate::maze::MazeError;
+use crate::segments::Segment;
+use crate::textures::Texture;
+
+// Niveles dibujados como imagen: cada píxel es una celda y su color dice qué hay en ella.
+// Los colores se leen de un archivo `.colors` junto a la imagen (nivel.png -> nivel.colors),
+// con una línea `color = celda` por entrada; `floor` es el piso:
+//
+//   #000000 = |
+//   #FFFFFF = floor
+//   #FF0000 = p
+//
+// Las entradas del archivo se suman a las de `BUILTIN` y tienen prioridad.
+const BUILTIN: &str = "#000000 = |\n\
+                       #FFFFFF = floor\n\
+                       #FF0000 = p\n\
+                       #00FF00 = g\n\
+                       #0000FF = f\n\
+                       #808080 = D\n\
+                       #804000 = L\n\
+                       #FFFF00 = k\n\
+                       #FF00FF = o\n\
+                       #00FFFF = h\n\
+                       #FF8000 = s\n\
+                       #40E0D0 = >\n\
+                       #2E8B8B = <\n";
+
+// Diferencia máxima por canal para aceptar un color parecido (bordes suavizados, etc.)
+const TOLERANCE: u32 = 48;
+
+pub struct ColorTable {
+    entries: Vec<(u32, char)>,
+}
+
+impl ColorTable {
+    pub fn load_for(image_file: &str) -> ColorTable {
+        let colors_file = Path::new(image_file).with_extension("colors");
+        let contents = fs::read_to_string(&colors_file).unwrap_or_default();
+        ColorTable::parse(&format!("{}\n{}", BUILTIN, contents))
+    }
+
+    fn parse(contents: &str) -> ColorTable {
+        let mut entries: Vec<(u32, char)> = Vec::new();
+        for (number, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            // Los colores empiezan con '#', así que los comentarios llevan "# " o "//"
+            if line.is_empty() || line.starts_with("# ") || line.starts_with("//") {
+                continue;
+            }
+
+            let Some((color, cell)) = line.split_once('=') else {
+                eprintln!("Colores, línea {}: se esperaba 'color = celda'", number + 1);
+                continue;
+            };
+            let Some(color) = parse_color(color) else {
+                eprintln!("Colores, línea {}: '{}' no es un color", number + 1, color.trim());
+                continue;
+            };
+            let cell = match cell.trim() {
+                "floor" => ' ',
+                cell if cell.chars().count() == 1 => cell.chars().next().unwrap(),
+                cell => {
+                    eprintln!("Colores, línea {}: '{}' no es una celda", number + 1, cell);
+                    continue;
+                }
+            };
+
+            entries.retain(|&(c, _)| c != color);
+            entries.push((color, cell));
+        }
+        ColorTable { entries }
+    }
+
+    pub fn color(&self, cell: char) -> Option<u32> {
+        self.entries.iter().find(|&&(_, c)| c == cell).map(|&(color, _)| color)
+    }
+
+    // La celda del color más cercano, si está dentro de la tolerancia
+    fn cell(&self, color: u32) -> Option<char> {
+        let channel = |color: u32, shift: u32| (color >> shift) & 0xFF;
+        let distance = |other: u32| [16, 8, 0].iter().map(|&shift| channel(color, shift).abs_diff(channel(other, shift))).max().unwrap();
+        self.entries
+            .iter()
+            .filter(|&&(other, _)| distance(other) <= TOLERANCE)
+            .min_by_key(|&&(other, _)| distance(other))
+            .map(|&(_, cell)| cell)
+    }
+}
+
+// Dibuja el laberinto con los mismos colores, `scale` píxeles por celda. Con escala 1 la imagen
+// se puede volver a cargar como nivel. Las paredes sin color propio usan el de '|'. Las paredes
+// delgadas se dibujan como líneas sobre los bordes de las celdas, así que necesitan una escala
+// mayor y esa imagen ya no se puede cargar como nivel
+pub fn save_image(path: &str, maze: &[Vec<char>], walls: &[Segment], scale: u32) -> Result<(), Box<dyn std::error::Error>> {
+    if !walls.is_empty() && scale < 2 {
+        return Err("las paredes delgadas no caben en una imagen de escala 1; usa --scale 2 o más".into());
+    }
+    let colors = ColorTable::load_for(path);
+    let wall = colors.color('|').unwrap_or(0x000000);
+    let thickness = (scale as f32 / 4.0).max(1.0) / scale as f32 / 2.0;
+    let width = maze.first().map_or(0, |row| row.len()) as u32;
+    let image = image::RgbImage::from_fn(width * scale, maze.len() as u32 * scale, |x, y| {
+        let point = Vector2::new(x as f32 + 0.5, y as f32 + 0.5) / scale as f32;
+        let color = match walls.iter().rev().find(|segment| segment.distance_to(point) <= thickness) {
+            Some(segment) => colors.color(segment.wall).unwrap_or(wall),
+            None => colors.color(maze[(y / scale) as usize][(x / scale) as usize]).unwrap_or(wall),
+        };
+        image::Rgb([(color >> 16) as u8, (color >> 8) as u8, color as u8])
+    });
+    image.save(path)?;
+    Ok(())
+}
+
+// Convierte la imagen en un nivel sin validarlo, igual que `LevelMap::read`
+pub fn from_image(path: &str) -> Result<LevelMap, MazeError> {
+    if !Path::new(path).exists() {
+        return Err(MazeError::NotFound(path.to_string()));
+    }
+    let image = Texture::load(path).map_err(|e| MazeError::Format(format!("imagen {}: {}", path, e)))?;
+    let colors = ColorTable::load_for(path);
+
+    let mut grid = Vec::with_capacity(image.height as usize);
+    for y in 0..image.height {
+        let mut row = Vec::with_capacity(image.width as usize);
+        for x in 0..image.width {
+            let color = image.get_pixel_color(x, y);
+            let cell = colors.cell(color).ok_or_else(|| {
+                MazeError::Format(format!("el píxel ({}, {}) tiene el color #{:06X}, que no está en la tabla de colores", x, y, color))
+            })?;
+            row.push(cell);
+        }
+        grid.push(row);
+    }
+
+    let name = Path::new(path).file_stem().map_or(path.into(), |stem| stem.to_string_lossy());
+    Ok(LevelMap::from_ascii(&name, &grid))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn builtin() -> ColorTable {
+        ColorTable::parse(BUILTIN)
+    }
+
+    fn temp_path(name: &str) -> String {
+        std::env::temp_dir().join(format!("maze-{}-{}", std::process::id(), name)).to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn maps_colors_to_cells_within_the_tolerance() {
+        let colors = builtin();
+        assert_eq!(colors.cell(0x000000), Some('|'));
+        assert_eq!(colors.cell(0xFFFFFF), Some(' '));
+        assert_eq!(colors.cell(0xF0F8F4), Some(' ')); // Borde suavizado
+        assert_eq!(colors.cell(0x808080), Some('D'));
+        assert_eq!(colors.cell(0x7F007F), None);
+        assert_eq!(colors.color('k'), Some(0xFFFF00));
+    }
+
+    #[test]
+    fn later_entries_replace_a_color_and_bad_lines_are_skipped() {
+        let colors = ColorTable::parse(&format!("{}\n# comentario\n#FF0000 = g\n#12345 = k\nsin igual\n#00FF00 = dos", BUILTIN));
+        assert_eq!(colors.cell(0xFF0000), Some('g'));
+        assert_eq!(colors.color('p'), None);
+        assert_eq!(colors.cell(0x00FF00), Some('g'));
+    }
+
+    #[test]
+    fn images_saved_at_scale_one_load_back_as_the_same_level() {
+        // Las paredes sin color propio ('+', '-') volverían como '|'
+        let maze: Vec<Vec<char>> = ["||||", "|pD|", "|kg|", "||||"].iter().map(|row| row.chars().collect()).collect();
+        let path = temp_path("nivel.png");
+        save_image(&path, &maze, &[], 1).unwrap();
+        let map = from_image(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(map.to_ascii(), maze);
+    }
+
+    #[test]
+    fn thin_walls_need_a_larger_scale() {
+        let maze: Vec<Vec<char>> = ["+--+", "|pg|", "+--+"].iter().map(|row| row.chars().collect()).collect();
+        let walls = [Segment { from: [2.0, 1.0], to: [2.0, 2.0], wall: '|' }];
+        assert!(save_image(&temp_path("fina.png"), &maze, &walls, 1).is_err());
+
+        let path = temp_path("fina-4.png");
+        save_image(&path, &maze, &walls, 4).unwrap();
+        let image = Texture::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        // La pared delgada pasa por el borde entre el inicio y la meta, en x = 2 celdas
+        assert_eq!(image.get_pixel_color(8, 6), 0x000000);
+        assert_eq!(image.get_pixel_color(5, 6), 0xFF0000);
+        assert_eq!(image.get_pixel_color(10, 6), 0x00FF00);
+    }
+}
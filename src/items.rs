@@ -0,0 +1,94 @@
+use nalgebra::Vector2;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ItemKind {
+    PokeBall,
+    Potion,
+    Key,
+    SilphScope,
+}
+
+impl ItemKind {
+    // Orden en el que aparecen en la barra del inventario
+    pub const ALL: [ItemKind; 4] = [ItemKind::PokeBall, ItemKind::Potion, ItemKind::Key, ItemKind::SilphScope];
+
+    // Carácter con el que se coloca el objeto en el mapa
+    pub fn from_cell(cell: char) -> Option<ItemKind> {
+        match cell {
+            'o' => Some(ItemKind::PokeBall),
+            'h' => Some(ItemKind::Potion),
+            'k' => Some(ItemKind::Key),
+            's' => Some(ItemKind::SilphScope),
+            _ => None,
+        }
+    }
+
+    pub fn cell(self) -> char {
+        match self {
+            ItemKind::PokeBall => 'o',
+            ItemKind::Potion => 'h',
+            ItemKind::Key => 'k',
+            ItemKind::SilphScope => 's',
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ItemKind::PokeBall => "Poké Ball",
+            ItemKind::Potion => "Poción",
+            ItemKind::Key => "Llave",
+            ItemKind::SilphScope => "Scope Silph",
+        }
+    }
+
+    pub fn sprite_path(self) -> &'static str {
+        match self {
+            ItemKind::PokeBall => "sprites/pokeballSprite.png",
+            ItemKind::Potion => "sprites/pocionSprite.png",
+            ItemKind::Key => "sprites/llaveSprite.png",
+            ItemKind::SilphScope => "sprites/scopeSprite.png",
+        }
+    }
+
+    pub fn index(self) -> usize {
+        ItemKind::ALL.iter().position(|&kind| kind == self).unwrap()
+    }
+}
+
+// Objeto tirado en el piso del laberinto
+pub struct Item {
+    pub kind: ItemKind,
+    pub pos: Vector2<f32>,
+}
+
+#[derive(Default)]
+pub struct Inventory {
+    counts: [u32; ItemKind::ALL.len()],
+}
+
+impl Inventory {
+    pub fn add(&mut self, kind: ItemKind) {
+        self.counts[kind.index()] += 1;
+    }
+
+    pub fn count(&self, kind: ItemKind) -> u32 {
+        self.counts[kind.index()]
+    }
+
+    pub fn has(&self, kind: ItemKind) -> bool {
+        self.count(kind) > 0
+    }
+
+    pub fn set(&mut self, kind: ItemKind, count: u32) {
+        self.counts[kind.index()] = count;
+    }
+
+    // Gasta una unidad del objeto; devuelve false si no había
+    pub fn take(&mut self, kind: ItemKind) -> bool {
+        if !self.has(kind) {
+            return false;
+        }
+        self.counts[kind.index()] -= 1;
+        true
+    }
+}
@@ -0,0 +1,567 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use crate::analysis::Step;
+use crate::enemy::GHOST;
+use crate::image_map;
+use crate::items::ItemKind;
+use crate::make_maze::save_maze_to_file;
+use crate::maze::{self, MazeError, GOAL, SPAWN};
+use crate::palette::{self, Palette};
+use crate::player::SPAWN_ANGLE;
+use crate::segments::Segment;
+use crate::tiled;
+
+// Formato de nivel por celdas (JSON). Cada carácter de `tiles` es un bloque del mapa
+// y las entidades van aparte, con sus propiedades:
+//
+//   {
+//     "name": "Pueblo Lavanda",
+//     "author": "Edwin",
+//     "theme": "maze.palette",
+//     "palette": { "floor": "sprites/floor2Sprite.png", "requires": "s" },
+//     "tiles": ["+--+--+", "|  >  |", "+--+--+"],
+//     "spawn": { "x": 1, "y": 1, "angle": 1.047 },
+//     "goals": [{ "x": 5, "y": 1 }],
+//     "entities": [
+//       { "type": "ghost", "x": 3, "y": 1, "rate": 1.2 },
+//       { "type": "item", "item": "k", "x": 4, "y": 1 },
+//       { "type": "warp", "x": 2, "y": 1, "to": { "x": 4, "y": 1, "floor": 1 } }
+//     ],
+//     "walls": [{ "from": [3, 1], "to": [4, 2], "wall": "-" }],
+//     "floors": [
+//       { "tiles": ["+--+--+", "|  <  |", "+--+--+"], "entities": [] }
+//     ]
+//   }
+//
+// `palette` agrega entradas con la sintaxis de los archivos `.palette`, encima del tema.
+// `tiles`, `goals` y `entities` son la planta baja; `floors` son los pisos de arriba, en orden,
+// unidos por escaleras ('>' sube, '<' baja, en la misma celda de los dos pisos). El inicio
+// puede estar en cualquier piso con `"floor"` (0 es la planta baja). Los paneles (`warp`) llevan
+// a otra celda, de cualquier piso o de otro nivel de la campaña (`"level": "id"`). Las paredes
+// delgadas (`walls`) van entre dos puntos, sobre los bordes de las celdas o en diagonal.
+
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub struct Spawn {
+    pub x: usize,
+    pub y: usize,
+    #[serde(default, skip_serializing_if = "is_ground")]
+    pub floor: usize,
+    #[serde(default = "default_angle")]
+    pub angle: f32,
+}
+
+fn default_angle() -> f32 {
+    SPAWN_ANGLE
+}
+
+fn is_ground(floor: &usize) -> bool {
+    *floor == 0
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub struct Cell {
+    pub x: usize,
+    pub y: usize,
+}
+
+// A dónde lleva un panel de teletransporte
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct WarpTarget {
+    pub x: usize,
+    pub y: usize,
+    #[serde(default, skip_serializing_if = "is_ground")]
+    pub floor: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub level: Option<String>, // Id de otro nivel de la campaña; por defecto, el mismo
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Entity {
+    Ghost {
+        x: usize,
+        y: usize,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        rate: Option<f32>, // Velocidad de la animación; por defecto varía entre fantasmas
+    },
+    Item {
+        x: usize,
+        y: usize,
+        item: char, // Carácter del objeto en el formato ASCII
+    },
+    Light {
+        x: usize,
+        y: usize,
+        #[serde(default = "default_radius")]
+        radius: f32, // Alcance en celdas
+        #[serde(default = "default_light_color")]
+        color: String, // "#RRGGBB"
+    },
+    Warp {
+        x: usize,
+        y: usize,
+        to: WarpTarget,
+    },
+}
+
+pub fn default_radius() -> f32 {
+    3.0
+}
+
+pub fn default_light_color() -> String {
+    "#FFD27F".to_string()
+}
+
+// Lee un color "#RRGGBB" (o "#AARRGGBB", como los guarda Tiled, ignorando la opacidad)
+pub fn parse_color(color: &str) -> Option<u32> {
+    let hex = color.trim().trim_start_matches('#');
+    // Solo dígitos hexadecimales: así los cortes por bytes caen entre caracteres y no pasa un '+'
+    if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let hex = if hex.len() == 8 { &hex[2..] } else { hex };
+    if hex.len() != 6 {
+        return None;
+    }
+    u32::from_str_radix(hex, 16).ok()
+}
+
+impl Entity {
+    pub fn cell(&self) -> (usize, usize) {
+        match *self {
+            Entity::Ghost { x, y, .. } | Entity::Item { x, y, .. } | Entity::Light { x, y, .. } | Entity::Warp { x, y, .. } => (x, y),
+        }
+    }
+
+    // Carácter con el que aparece en el formato ASCII, si lo tiene
+    fn ascii(&self) -> Option<char> {
+        match *self {
+            Entity::Ghost { .. } => Some(GHOST),
+            Entity::Item { item, .. } => Some(item),
+            Entity::Light { .. } | Entity::Warp { .. } => None,
+        }
+    }
+
+    fn describe(&self) -> String {
+        match self {
+            Entity::Ghost { .. } => "El fantasma".to_string(),
+            Entity::Item { item, .. } => format!("El objeto '{}'", item),
+            Entity::Light { .. } => "La luz".to_string(),
+            Entity::Warp { .. } => "El panel".to_string(),
+        }
+    }
+}
+
+// Las celdas y las entidades de un piso
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct FloorMap {
+    pub tiles: Vec<String>,
+    #[serde(default)]
+    pub goals: Vec<Cell>,
+    #[serde(default)]
+    pub entities: Vec<Entity>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub walls: Vec<Segment>,
+}
+
+impl FloorMap {
+    // Solo las celdas del piso, sin entidades
+    pub fn grid(&self) -> Vec<Vec<char>> {
+        self.tiles.iter().map(|row| row.chars().collect()).collect()
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct LevelMap {
+    pub name: String,
+    #[serde(default)]
+    pub author: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub theme: Option<String>, // Archivo `.palette`; por defecto el que acompaña al nivel
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub palette: BTreeMap<String, String>,
+    #[serde(flatten)]
+    pub ground: FloorMap,
+    pub spawn: Spawn,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub floors: Vec<FloorMap>, // Pisos de arriba, de abajo hacia arriba
+}
+
+impl LevelMap {
+    // Separa las entidades del dibujo ASCII. La meta queda como celda 'g' en los dos formatos
+    pub fn from_ascii(name: &str, ascii: &[Vec<char>]) -> LevelMap {
+        LevelMap::from_floors(name, &[ascii])
+    }
+
+    // Lo mismo con un dibujo por piso, de abajo hacia arriba
+    pub fn from_floors<M: AsRef<[Vec<char>]>>(name: &str, floors: &[M]) -> LevelMap {
+        let mut spawn = Spawn { x: 1, y: 1, floor: 0, angle: SPAWN_ANGLE };
+        let mut parts = Vec::with_capacity(floors.len());
+
+        for (floor, ascii) in floors.iter().enumerate() {
+            let ascii = ascii.as_ref();
+            let mut part = FloorMap { tiles: Vec::with_capacity(ascii.len()), ..FloorMap::default() };
+            for (y, row) in ascii.iter().enumerate() {
+                let mut line = String::with_capacity(row.len());
+                for (x, &cell) in row.iter().enumerate() {
+                    if cell == SPAWN {
+                        spawn = Spawn { x, y, floor, ..spawn };
+                        line.push(' ');
+                    } else if cell == GHOST {
+                        part.entities.push(Entity::Ghost { x, y, rate: None });
+                        line.push(' ');
+                    } else if ItemKind::from_cell(cell).is_some() {
+                        part.entities.push(Entity::Item { x, y, item: cell });
+                        line.push(' ');
+                    } else {
+                        if cell == GOAL {
+                            part.goals.push(Cell { x, y });
+                        }
+                        line.push(cell);
+                    }
+                }
+                part.tiles.push(line);
+            }
+            parts.push(part);
+        }
+
+        let mut parts = parts.into_iter();
+        LevelMap {
+            name: name.to_string(),
+            author: String::new(),
+            theme: None,
+            palette: BTreeMap::new(),
+            ground: parts.next().unwrap_or_default(),
+            spawn,
+            floors: parts.collect(),
+        }
+    }
+
+    pub fn floor_count(&self) -> usize {
+        1 + self.floors.len()
+    }
+
+    // Los pisos de abajo hacia arriba, empezando por la planta baja
+    pub fn all_floors(&self) -> impl Iterator<Item = &FloorMap> {
+        std::iter::once(&self.ground).chain(&self.floors)
+    }
+
+    pub fn all_floors_mut(&mut self) -> impl Iterator<Item = &mut FloorMap> {
+        std::iter::once(&mut self.ground).chain(&mut self.floors)
+    }
+
+    // Dibujo ASCII de la planta baja. Las propiedades de las entidades no tienen lugar en él y se pierden
+    pub fn to_ascii(&self) -> Vec<Vec<char>> {
+        self.floor_ascii(0, &self.ground)
+    }
+
+    // Un dibujo ASCII por piso, de abajo hacia arriba
+    pub fn floors_ascii(&self) -> Vec<Vec<Vec<char>>> {
+        self.all_floors().enumerate().map(|(floor, part)| self.floor_ascii(floor, part)).collect()
+    }
+
+    fn floor_ascii(&self, floor: usize, part: &FloorMap) -> Vec<Vec<char>> {
+        let mut grid = part.grid();
+        let mut place = |(x, y): (usize, usize), cell: char| {
+            if let Some(slot) = grid.get_mut(y).and_then(|row| row.get_mut(x)) {
+                *slot = cell;
+            }
+        };
+
+        for goal in &part.goals {
+            place((goal.x, goal.y), GOAL);
+        }
+        for entity in &part.entities {
+            if let Some(cell) = entity.ascii() {
+                place(entity.cell(), cell);
+            }
+        }
+        if self.spawn.floor == floor {
+            place((self.spawn.x, self.spawn.y), SPAWN);
+        }
+        grid
+    }
+
+    // Lee el archivo sin validarlo; la validación necesita los caracteres de la paleta.
+    // También acepta imágenes y mapas de Tiled, que se reconocen por la extensión o por sus capas
+    pub fn read(path: &str) -> Result<LevelMap, MazeError> {
+        if has_extension(path, "png") {
+            return image_map::from_image(path);
+        }
+        let contents = maze::read_file(path)?;
+        if has_extension(path, "tmx") {
+            return tiled::from_tmx(&contents, path);
+        }
+        let value: serde_json::Value = serde_json::from_str(&contents).map_err(|e| MazeError::Format(e.to_string()))?;
+        if value.get("layers").is_some() {
+            return tiled::from_json(&value, path);
+        }
+        serde_json::from_value(value).map_err(|e| MazeError::Format(e.to_string()))
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    // Paleta del nivel: la del tema (o la que acompaña al archivo) más las entradas propias.
+    // `theme` permite que la campaña reemplace el tema del mapa
+    pub fn palette(&self, path: &str, theme: Option<&str>) -> Result<Palette, MazeError> {
+        let (palette_file, extra) = self.palette_source(path, theme);
+        Palette::load_with(palette_file, &extra)
+    }
+
+    // Los caracteres de pared de esa misma paleta, sin cargar las texturas
+    pub fn wall_chars(&self, path: &str, theme: Option<&str>) -> Vec<char> {
+        let (palette_file, extra) = self.palette_source(path, theme);
+        palette::wall_chars_for(palette_file, &extra)
+    }
+
+    fn palette_source(&self, path: &str, theme: Option<&str>) -> (PathBuf, String) {
+        let extra: Vec<String> = self.palette.iter().map(|(key, value)| format!("{} = {}", key, value)).collect();
+        let palette_file = match theme.or(self.theme.as_deref()) {
+            Some(theme) => PathBuf::from(theme),
+            None => Path::new(path).with_extension("palette"),
+        };
+        (palette_file, extra.join("\n"))
+    }
+
+    // Mismas reglas que el formato ASCII, y además cada entidad debe estar sobre una celda libre
+    pub fn validate(&self, walls: &[char]) -> Result<(), MazeError> {
+        if self.spawn.floor >= self.floor_count() {
+            return Err(MazeError::Format(format!("el inicio está en el piso {}, pero el nivel tiene {}", self.spawn.floor + 1, self.floor_count())));
+        }
+        for (floor, part) in self.all_floors().enumerate() {
+            self.validate_floor(floor, part, walls)?;
+        }
+
+        // Los paneles del mismo nivel deben llevar a una celda libre
+        let grids: Vec<_> = self.all_floors().map(FloorMap::grid).collect();
+        for part in self.all_floors() {
+            for entity in &part.entities {
+                if let Entity::Warp { to, .. } = entity {
+                    let free = grids.get(to.floor).and_then(|grid| grid.get(to.y)).and_then(|row| row.get(to.x)) == Some(&' ');
+                    if to.level.is_none() && !free {
+                        return Err(MazeError::MisplacedEntity { what: "El destino del panel".to_string(), x: to.x, y: to.y });
+                    }
+                }
+            }
+        }
+        maze::validate_linked(&self.floors_ascii(), &self.warp_links(), &self.wall_cuts())
+    }
+
+    // Los pasos entre celdas vecinas que cortan las paredes delgadas, en todos los pisos
+    pub fn wall_cuts(&self) -> Vec<(Step, Step)> {
+        self.all_floors()
+            .enumerate()
+            .flat_map(|(floor, part)| part.walls.iter().flat_map(move |wall| wall.cuts(floor)))
+            .collect()
+    }
+
+    // Los paneles que llevan a otra celda del mismo nivel, como (piso, celda) de origen y destino
+    pub fn warp_links(&self) -> Vec<(Step, Step)> {
+        let mut links = Vec::new();
+        for (floor, part) in self.all_floors().enumerate() {
+            for entity in &part.entities {
+                if let Entity::Warp { x, y, to } = entity {
+                    if to.level.is_none() {
+                        links.push(((floor, (*x, *y)), (to.floor, (to.x, to.y))));
+                    }
+                }
+            }
+        }
+        links
+    }
+
+    fn validate_floor(&self, floor: usize, part: &FloorMap, walls: &[char]) -> Result<(), MazeError> {
+        let grid = maze::parse_maze(&part.tiles.join("\n"), walls)?;
+
+        // Las entidades van en su lista, no dibujadas en las celdas
+        for (y, row) in grid.iter().enumerate() {
+            if let Some(x) = row.iter().position(|&c| c == SPAWN || c == GHOST || ItemKind::from_cell(c).is_some()) {
+                return Err(MazeError::UnknownChar { line: y + 1, column: x + 1, found: row[x] });
+            }
+        }
+
+        let is_floor = |(x, y): (usize, usize)| grid.get(y).and_then(|row| row.get(x)) == Some(&' ');
+        if self.spawn.floor == floor && !is_floor((self.spawn.x, self.spawn.y)) {
+            return Err(MazeError::MisplacedEntity { what: "El inicio".to_string(), x: self.spawn.x, y: self.spawn.y });
+        }
+        for entity in &part.entities {
+            let (x, y) = entity.cell();
+            match entity {
+                // Las luces pueden colgar de las paredes
+                Entity::Light { color, .. } => {
+                    if grid.get(y).and_then(|row| row.get(x)).is_none() {
+                        return Err(MazeError::MisplacedEntity { what: entity.describe(), x, y });
+                    }
+                    if parse_color(color).is_none() {
+                        return Err(MazeError::Format(format!("'{}' no es un color", color)));
+                    }
+                }
+                _ if !is_floor((x, y)) => return Err(MazeError::MisplacedEntity { what: entity.describe(), x, y }),
+                Entity::Item { item, .. } if ItemKind::from_cell(*item).is_none() => {
+                    return Err(MazeError::Format(format!("'{}' no es un objeto", item)));
+                }
+                _ => {}
+            }
+        }
+        for goal in &part.goals {
+            if grid.get(goal.y).and_then(|row| row.get(goal.x)).is_none() {
+                return Err(MazeError::MisplacedEntity { what: "La meta".to_string(), x: goal.x, y: goal.y });
+            }
+        }
+
+        // Las paredes delgadas deben quedar dentro del mapa y tener largo
+        let (width, height) = (grid.first().map_or(0, Vec::len) as f32, grid.len() as f32);
+        for wall in &part.walls {
+            let inside = [wall.from, wall.to].iter().all(|&[x, y]| (0.0..=width).contains(&x) && (0.0..=height).contains(&y));
+            if !inside || wall.length() <= 0.0 {
+                return Err(MazeError::Format(format!(
+                    "Piso {}: la pared delgada de {:?} a {:?} no cabe en el mapa",
+                    floor + 1, wall.from, wall.to
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+fn has_extension(path: &str, extension: &str) -> bool {
+    Path::new(path).extension().is_some_and(|ext| ext == extension)
+}
+
+// Archivos que se leen con `LevelMap::read`: el formato de celdas, los mapas de Tiled y las imágenes
+pub fn is_level_map(path: &str) -> bool {
+    ["json", "tmj", "tmx", "png"].iter().any(|ext| has_extension(path, ext))
+}
+
+// Lee y valida un nivel en cualquiera de los formatos, con la paleta que lo acompaña
+pub fn load(path: &str) -> Result<LevelMap, MazeError> {
+    if is_level_map(path) {
+        let map = LevelMap::read(path)?;
+        map.validate(&map.wall_chars(path, None))?;
+        return Ok(map);
+    }
+
+    let palette_file = Path::new(path).with_extension("palette");
+    let grid = maze::load_maze(path, &palette::wall_chars_for(&palette_file, ""))?;
+    let name = Path::new(path).file_stem().map_or(path.into(), |stem| stem.to_string_lossy());
+    let mut map = LevelMap::from_ascii(&name, &grid);
+    if palette_file.exists() {
+        map.theme = Some(palette_file.to_string_lossy().into_owned());
+    }
+    Ok(map)
+}
+
+// Convierte un nivel entre el formato ASCII (.txt) y el de celdas (.json), según las extensiones.
+// Los mapas de Tiled (.tmx, .tmj) y las imágenes (.png) se pueden convertir a cualquiera de los dos
+pub fn convert(input: &str, output: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let map = load(input)?;
+
+    if has_extension(output, "json") {
+        map.save(output)
+    } else if map.floor_count() > 1 {
+        Err("el formato ASCII no tiene pisos; convierte el nivel a .json".into())
+    } else if map.all_floors().any(|part| !part.walls.is_empty()) {
+        Err("el formato ASCII no tiene paredes delgadas; convierte el nivel a .json".into())
+    } else if map.all_floors().any(|part| part.entities.iter().any(|entity| matches!(entity, Entity::Warp { .. }))) {
+        Err("el formato ASCII no tiene paneles; convierte el nivel a .json".into())
+    } else {
+        eprintln!("Aviso: el formato ASCII no guarda el nombre, el autor, la paleta propia, el ángulo inicial, las luces ni las propiedades de las entidades");
+        save_maze_to_file(output, &map.to_ascii())?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grid(rows: &[&str]) -> Vec<Vec<char>> {
+        rows.iter().map(|row| row.chars().collect()).collect()
+    }
+
+    fn sample() -> Vec<Vec<char>> {
+        grid(&["+--+--+", "|p f k|", "+  +--+", "|  D g|", "+--+--+"])
+    }
+
+    #[test]
+    fn ascii_round_trip_keeps_every_cell() {
+        let map = LevelMap::from_ascii("prueba", &sample());
+        assert_eq!(map.to_ascii(), sample());
+    }
+
+    #[test]
+    fn entities_are_separated_from_the_tiles() {
+        let map = LevelMap::from_ascii("prueba", &sample());
+        assert_eq!(map.ground.tiles[1], "|     |");
+        assert_eq!((map.spawn.x, map.spawn.y), (1, 1));
+        assert!(map.ground.goals == [Cell { x: 5, y: 3 }]);
+        let cells: Vec<_> = map.ground.entities.iter().map(Entity::cell).collect();
+        assert_eq!(cells, [(3, 1), (5, 1)]);
+        assert!(matches!(map.ground.entities[1], Entity::Item { item: 'k', .. }));
+    }
+
+    #[test]
+    fn json_round_trip_keeps_the_level() {
+        let mut map = LevelMap::from_ascii("prueba", &sample());
+        map.author = "Edwin".to_string();
+        map.palette.insert("requires".to_string(), "k".to_string());
+        map.ground.entities.push(Entity::Light { x: 2, y: 3, radius: 2.0, color: "#FF0000".to_string() });
+
+        let json = serde_json::to_string(&map).unwrap();
+        let read: LevelMap = serde_json::from_str(&json).unwrap();
+        assert_eq!(read.name, "prueba");
+        assert_eq!(read.author, "Edwin");
+        assert_eq!(read.palette.get("requires").map(String::as_str), Some("k"));
+        assert_eq!(read.ground.entities.len(), 3);
+        assert_eq!(read.to_ascii(), sample());
+        assert!(read.validate(&[]).is_ok());
+    }
+
+    #[test]
+    fn floors_round_trip_through_json() {
+        let ground = grid(&["+---+", "|p> |", "+---+"]);
+        let upper = grid(&["+---+", "| <g|", "+---+"]);
+        let map = LevelMap::from_floors("torre", &[&ground, &upper]);
+        assert_eq!(map.floor_count(), 2);
+
+        let read: LevelMap = serde_json::from_str(&serde_json::to_string(&map).unwrap()).unwrap();
+        assert_eq!(read.floors_ascii(), [ground, upper]);
+        assert!(read.validate(&[]).is_ok());
+    }
+
+    #[test]
+    fn entities_must_stand_on_free_cells() {
+        let mut map = LevelMap::from_ascii("prueba", &sample());
+        map.ground.entities.push(Entity::Ghost { x: 0, y: 0, rate: None });
+        assert!(matches!(map.validate(&[]), Err(MazeError::MisplacedEntity { x: 0, y: 0, .. })));
+    }
+
+    #[test]
+    fn thin_walls_can_block_the_goal() {
+        let mut map = LevelMap::from_ascii("prueba", &grid(&["+----+", "|p  g|", "+----+"]));
+        assert!(map.validate(&[]).is_ok());
+        map.ground.walls.push(Segment { from: [2.0, 1.0], to: [2.0, 2.0], wall: '|' });
+        assert!(matches!(map.validate(&[]), Err(MazeError::UnreachableGoal)));
+    }
+
+    #[test]
+    fn parses_colors_with_and_without_alpha() {
+        assert_eq!(parse_color("#FFD27F"), Some(0xFFD27F));
+        assert_eq!(parse_color("#80FFD27F"), Some(0xFFD27F));
+        assert_eq!(parse_color("FFD27F"), Some(0xFFD27F));
+        assert_eq!(parse_color("#FFF"), None);
+    }
+
+    #[test]
+    fn rejects_colors_that_are_not_hex() {
+        assert_eq!(parse_color("aé12345"), None);
+        assert_eq!(parse_color("#éé1234"), None);
+        assert_eq!(parse_color("+FFFFF"), None);
+        assert_eq!(parse_color("#GGGGGG"), None);
+    }
+}
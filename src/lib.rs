@@ -0,0 +1,27 @@
+// Módulos del juego, compartidos por el juego y por `maze-tool`
+pub mod maze;
+pub mod player;
+pub mod raycasting;
+pub mod controls;
+pub mod textures;
+pub mod audio;
+pub mod sprites;
+pub mod enemy;
+pub mod palette;
+pub mod doors;
+pub mod world;
+pub mod items;
+pub mod scores;
+pub mod make_maze;
+pub mod campaign;
+pub mod save;
+pub mod game;
+pub mod replay;
+pub mod race;
+pub mod level_map;
+pub mod tiled;
+pub mod image_map;
+pub mod editor;
+pub mod analysis;
+pub mod segments;
+pub mod text;
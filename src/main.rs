@@ -1,97 +1,86 @@
-mod maze;
-mod player;
-mod raycasting;
-mod controls;
-mod textures;
-mod audio;
+use maze_proyect::{audio, campaign, controls, doors, editor, enemy, game, items, level_map, palette, player, race, raycasting, replay, save, scores, sprites, text, textures, world};
 
 use player::Player;
-use raycasting::cast_ray;
-use controls::process_events;
-use minifb::{Key, Window, WindowOptions};
+use raycasting::cast_ray_through;
+use controls::Input;
+use minifb::{Key, KeyRepeat, MouseButton, MouseMode, Window, WindowOptions};
 use nalgebra as na;
-use textures::Texture;
+use textures::{blend_color, Filter, Texture};
+use text::TextStyle;
 use once_cell::sync::Lazy;
 use std::sync::Arc;
-use rusttype::{Font, Scale};
 use std::time::{Duration, Instant};
-use audio::AudioPlayer;
+use audio::{AudioPlayer, Sounds};
+use sprites::{SpriteSheet, AnimationClip};
+use enemy::Enemy;
+use palette::CAMERA_HEIGHT;
+use doors::{DOOR, LOCKED_DOOR};
+use editor::Editor;
+use enemy::GHOST;
+use world::{Floor, Light, World};
+use maze_proyect::maze::{STAIRS_DOWN, STAIRS_UP};
+use maze_proyect::segments::Segment;
+use items::{Inventory, ItemKind};
+use scores::{HighScores, RunStats, ScoreEntry, SCORES_FILE};
+use campaign::{Campaign, Progress, CAMPAIGN_FILE, PROGRESS_FILE};
+use save::{SaveGame, AUTO_SAVE, QUICK_SAVE, SLOTS};
+use game::{GameState, TICK};
+use replay::{Replay, LAST_REPLAY};
+use race::GhostRace;
+use rayon::prelude::*;
+
+// Marca de los paneles en la vista 2D, que no es un carácter de los niveles
+const WARP: char = '*';
 
 const WIDTH: usize = 1040;
 const HEIGHT: usize = 900;
 
-static WALL1: Lazy<Arc<Texture>> = Lazy::new(|| Arc::new(Texture::new("sprites/casaSprite.png")));
-static WALL2: Lazy<Arc<Texture>> = Lazy::new(|| Arc::new(Texture::new("sprites/casaSprite2.png")));
-static FLOOR: Lazy<Arc<Texture>> = Lazy::new(|| Arc::new(Texture::new("sprites/floorSprite.png")));
-static SKY: Lazy<Arc<Texture>> = Lazy::new(|| Arc::new(Texture::new("sprites/skySprite.png")));
-static CHARACTER: Lazy<Arc<Texture>> = Lazy::new(|| Arc::new(Texture::new("sprites/ghastSprite.jpg")));
-static POKE_CENTER: Lazy<Arc<Texture>> = Lazy::new(|| Arc::new(Texture::new("sprites/centroPoke.png")));
 static INTRO_SPRITE: Lazy<Arc<Texture>> = Lazy::new(|| Arc::new(Texture::new("sprites/introSprite.png")));
 static MEDALLA_SPRITE: Lazy<Arc<Texture>> = Lazy::new(|| Arc::new(Texture::new("sprites/medallaSprite.png")));
 
-fn cell_to_texture_color(wall_type: char, _is_vertical: bool, tx: f32, ty: f32) -> u32 {
-    match wall_type {
-        '|' => WALL1.get_pixel_color((tx * WALL1.width as f32) as u32, (ty * WALL1.height as f32) as u32),
-        '-' => WALL2.get_pixel_color((tx * WALL2.width as f32) as u32, (ty * WALL2.height as f32) as u32),
-        'g' => POKE_CENTER.get_pixel_color((tx * POKE_CENTER.width as f32) as u32, (ty * POKE_CENTER.height as f32) as u32),
-        _ => WALL1.get_pixel_color((tx * WALL1.width as f32) as u32, (ty * WALL1.height as f32) as u32),
-    }
+// Hoja del fantasma: columnas = cuadros de animación (flota y ondula la cola), filas = direcciones
+// (de frente, luego en sentido antihorario; de espaldas no se le ve la cara)
+const GHOST_FRAMES: u32 = 4;
+const GHOST_DIRECTIONS: u32 = 8;
+static GHOST_SHEET: Lazy<Arc<SpriteSheet>> = Lazy::new(|| Arc::new(load_sheet("sprites/ghastSheet.png", GHOST_FRAMES, GHOST_DIRECTIONS)));
+static GHOST_FLOAT: Lazy<AnimationClip> = Lazy::new(|| {
+    AnimationClip::new((0..GHOST_FRAMES as usize).collect(), 4.0)
+        .directional(GHOST_DIRECTIONS as usize, GHOST_FRAMES as usize)
+});
+static TRAINER_SHEET: Lazy<Arc<SpriteSheet>> = Lazy::new(|| Arc::new(load_sheet("sprites/entrenadorSprite.png", 1, 1)));
+static ITEM_SPRITES: Lazy<Vec<SpriteSheet>> = Lazy::new(|| {
+    ItemKind::ALL.iter().map(|kind| load_sheet(kind.sprite_path(), 1, 1)).collect()
+});
+
+// Los sprites vienen con el juego: sin ellos no se puede seguir
+fn load_sheet(file_path: &str, columns: u32, rows: u32) -> SpriteSheet {
+    SpriteSheet::from_grid(file_path, columns, rows).unwrap_or_else(|e| panic!("No se pudo cargar la hoja de sprites {}", e))
 }
 
-fn render_sky(framebuffer: &mut Vec<u32>) {
-    for y in 0..HEIGHT / 2 {
-        for x in 0..WIDTH {
-            let tx = (x as f32 / WIDTH as f32 * SKY.width as f32) as u32;
-            let ty = (y as f32 / (HEIGHT / 2) as f32 * SKY.height as f32) as u32;
-            let color = SKY.get_pixel_color(tx, ty);
-            framebuffer[y * WIDTH + x] = color;
-        }
-    }
+fn render_text(framebuffer: &mut [u32], text: &str, x: usize, y: usize, style: TextStyle) {
+    text::draw_text(framebuffer, WIDTH, text, x as i32, y as i32, style);
 }
 
-fn render_text(framebuffer: &mut Vec<u32>, text: &str, x: usize, y: usize, scale: Scale, color: u32) {
-    let font_data = include_bytes!("../assets/PressStart2P.ttf");
-    let font = Font::try_from_bytes(font_data as &[u8]).unwrap();
-
-    let v_metrics = font.v_metrics(scale);
-
-    let glyphs: Vec<_> = font
-        .layout(text, scale, rusttype::point(x as f32, y as f32 + v_metrics.ascent))
-        .collect();
-
-    for glyph in glyphs {
-        if let Some(bb) = glyph.pixel_bounding_box() {
-            glyph.draw(|gx, gy, gv| {
-                let px = (gx as i32 + bb.min.x) as usize;
-                let py = (gy as i32 + bb.min.y) as usize;
-
-                if px < WIDTH && py < HEIGHT {
-                    let alpha = (gv * 255.0) as u32;
-                    let foreground = if alpha > 128 {
-                        color & 0xFFFFFF
-                    } else {
-                        framebuffer[py * WIDTH + px]
-                    };
-
-                    framebuffer[py * WIDTH + px] = foreground;
-                }
-            });
-        }
-    }
-}
-
-fn draw_cell(framebuffer: &mut Vec<u32>, xo: usize, yo: usize, block_size: usize, cell: char, row: usize, col: usize) {
+fn draw_cell(framebuffer: &mut [u32], xo: usize, yo: usize, block_size: usize, cell: char, row: usize, col: usize) {
     let color = match cell {
-        '+' | '-' | '|' => {
-            if (row + col) % 2 == 0 {
+        ' ' => 0x000000, // Negro para el espacio vacío
+        'p' => 0xFF0000, // Rojo para el punto de inicio
+        'g' => 0x00FF00, // Verde para el punto de meta
+        GHOST => 0x9B59B6,       // Morado para los fantasmas
+        DOOR => 0x8B4513,        // Café para las puertas
+        LOCKED_DOOR => 0xFFD700, // Dorado para las puertas con llave
+        STAIRS_UP => 0x40E0D0,   // Turquesa para las escaleras que suben
+        STAIRS_DOWN => 0x2E8B8B, // Turquesa oscuro para las que bajan
+        WARP => WARP_COLOR,      // Magenta para los paneles
+        _ if ItemKind::from_cell(cell).is_some() => 0xF1F1F1, // Blanco para los objetos
+        _ => {
+            // Cualquier otro carácter de la paleta es una pared
+            if (row + col).is_multiple_of(2) {
                 0xFF5733 // Color 1 (naranja)
             } else {
                 0x3498DB // Color 2 (azul)
             }
         }
-        'p' => 0xFF0000, // Rojo para el punto de inicio
-        'g' => 0x00FF00, // Verde para el punto de meta
-        _ => 0x000000,   // Negro para el espacio vacío
     };
 
     for y in yo..(yo + block_size).min(HEIGHT) {
@@ -101,22 +90,10 @@ fn draw_cell(framebuffer: &mut Vec<u32>, xo: usize, yo: usize, block_size: usize
     }
 }
 
-fn render_floor(framebuffer: &mut Vec<u32>) {
-    for y in (HEIGHT / 2)..HEIGHT {
-        let ty = (y - HEIGHT / 2) * FLOOR.height as usize / (HEIGHT / 2);
-
-        for x in 0..WIDTH {
-            let tx = x * FLOOR.width as usize / WIDTH;
-            let color = FLOOR.get_pixel_color(tx as u32, ty as u32);
-            framebuffer[y * WIDTH + x] = color;
-        }
-    }
-}
-
-fn render_minimap(framebuffer: &mut Vec<u32>, maze: &Vec<Vec<char>>, player: &Player, block_size: usize, enemy_positions: &Vec<na::Vector2<f32>>) {
+fn render_minimap(framebuffer: &mut [u32], world: &World, player: &Player, trainer: Option<na::Vector2<f32>>) {
     let minimap_scale = 20;
-    let minimap_width = maze[0].len() * minimap_scale;
-    let minimap_height = maze.len() * minimap_scale;
+    let floor = world.current();
+    let maze = &floor.maze;
 
     // Posición del minimapa en la pantalla (esquina superior izquierda)
     let minimap_x_offset = 10;
@@ -126,9 +103,15 @@ fn render_minimap(framebuffer: &mut Vec<u32>, maze: &Vec<Vec<char>>, player: &Pl
     for (row, line) in maze.iter().enumerate() {
         for (col, &cell) in line.iter().enumerate() {
             let color = match cell {
-                '+' | '-' | '|' => 0xFFFFFF, // Color blanco para las paredes
                 'p' => 0xFF0000, // Rojo para el punto de inicio
-                _ => 0x000000,   // Negro para el espacio vacío
+                _ if floor.doors.is_passable(col, row) => 0x000000,
+                DOOR => 0x8B4513,        // Café para las puertas cerradas
+                LOCKED_DOOR => 0xFFD700, // Dorado para las puertas con llave
+                STAIRS_UP => 0x40E0D0,   // Turquesa para las escaleras que suben
+                STAIRS_DOWN => 0x2E8B8B, // Turquesa oscuro para las que bajan
+                ' ' if floor.warp_at(col, row).is_some() => WARP_COLOR, // Magenta para los paneles
+                ' ' | 'g' => 0x000000, // Negro para el espacio vacío
+                _ => 0xFFFFFF,   // Color blanco para las paredes
             };
 
             for y in 0..minimap_scale {
@@ -143,12 +126,52 @@ fn render_minimap(framebuffer: &mut Vec<u32>, maze: &Vec<Vec<char>>, player: &Pl
         }
     }
 
+    draw_segments(framebuffer, &floor.walls, minimap_x_offset, minimap_y_offset, minimap_scale as f32, 0xFFFFFF);
+
+    // En los niveles de varios pisos, el piso que muestra el minimapa
+    if world.floors.len() > 1 {
+        let label_y = minimap_y_offset + maze.len() * minimap_scale + 6;
+        let label = format!("Piso {}/{}", world.floor + 1, world.floors.len());
+        render_text(framebuffer, &label, minimap_x_offset, label_y, TextStyle::new(14.0, 0x40E0D0));
+    }
+
+    // Dibujar los objetos en el minimapa
+    for item in &floor.items {
+        let item_x = minimap_x_offset + (item.pos.x * minimap_scale as f32) as usize;
+        let item_y = minimap_y_offset + (item.pos.y * minimap_scale as f32) as usize;
+        for y in 0..4 {
+            for x in 0..4 {
+                let pixel_x = item_x + x;
+                let pixel_y = item_y + y;
+                if pixel_x < WIDTH && pixel_y < HEIGHT {
+                    framebuffer[pixel_y * WIDTH + pixel_x] = 0xFFD700;
+                }
+            }
+        }
+    }
+
     // Dibujar al jugador en el minimapa
     let player_minimap_x = minimap_x_offset + (player.pos.x * minimap_scale as f32) as usize;
     let player_minimap_y = minimap_y_offset + (player.pos.y * minimap_scale as f32) as usize;
 
     let player_minimap_size = 8;
 
+    // El entrenador fantasma de la carrera, debajo del jugador
+    if let Some(trainer) = trainer {
+        let trainer_minimap_x = minimap_x_offset + (trainer.x * minimap_scale as f32) as usize;
+        let trainer_minimap_y = minimap_y_offset + (trainer.y * minimap_scale as f32) as usize;
+        for y in 0..player_minimap_size {
+            for x in 0..player_minimap_size {
+                let pixel_x = trainer_minimap_x + x;
+                let pixel_y = trainer_minimap_y + y;
+                if pixel_x < WIDTH && pixel_y < HEIGHT {
+                    let index = pixel_y * WIDTH + pixel_x;
+                    framebuffer[index] = blend_color(0x80C0FF, framebuffer[index], 0.6);
+                }
+            }
+        }
+    }
+
     for y in 0..player_minimap_size {
         for x in 0..player_minimap_size {
             let pixel_x = player_minimap_x + x;
@@ -160,9 +183,9 @@ fn render_minimap(framebuffer: &mut Vec<u32>, maze: &Vec<Vec<char>>, player: &Pl
     }
 
     /* Dibujar los enemigos en el minimapa
-    for enemy_pos in enemy_positions {
-        let enemy_minimap_x = minimap_x_offset + (enemy_pos.x * minimap_scale as f32) as usize;
-        let enemy_minimap_y = minimap_y_offset + (enemy_pos.y * minimap_scale as f32) as usize;
+    for enemy in &world.enemies {
+        let enemy_minimap_x = minimap_x_offset + (enemy.pos.x * minimap_scale as f32) as usize;
+        let enemy_minimap_y = minimap_y_offset + (enemy.pos.y * minimap_scale as f32) as usize;
 
         for y in 0..player_minimap_size {
             for x in 0..player_minimap_size {
@@ -176,10 +199,60 @@ fn render_minimap(framebuffer: &mut Vec<u32>, maze: &Vec<Vec<char>>, player: &Pl
     }*/
 }
 
-fn render_enemy(framebuffer: &mut Vec<u32>, player: &Player, pos: &na::Vector2<f32>, z_buffer: &mut [f32]) {
+// Tiñe un punto del mapa con el color de las luces cercanas, más fuerte cuanto más cerca
+fn apply_lights(color: u32, lights: &[Light], x: f32, y: f32) -> u32 {
+    const LIGHT_STRENGTH: f32 = 0.5;
+    lights.iter().fold(color, |color, light| {
+        let distance = (light.pos - na::Vector2::new(x, y)).norm();
+        if distance >= light.radius {
+            return color;
+        }
+        blend_color(light.color, color, (1.0 - distance / light.radius) * LIGHT_STRENGTH)
+    })
+}
+
+const WARP_COLOR: u32 = 0xFF40FF;
+
+// Las escaleras se ven en el piso como una baldosa teñida, clara si sube y oscura si baja,
+// y los paneles como una baldosa magenta
+fn floor_tint(color: u32, floor: &Floor, x: f32, y: f32) -> u32 {
+    match floor.maze.get(y as usize).and_then(|row| row.get(x as usize)) {
+        Some(&STAIRS_UP) => blend_color(0x40E0D0, color, 0.5),
+        Some(&STAIRS_DOWN) => blend_color(0x1A4F4F, color, 0.5),
+        _ if floor.warp_at(x as usize, y as usize).is_some() => blend_color(WARP_COLOR, color, 0.5),
+        _ => color,
+    }
+}
+
+// Oscurece toda la pantalla hacia negro, para el fundido de los paneles
+fn fade_to_black(framebuffer: &mut [u32], amount: f32) {
+    if amount > 0.0 {
+        framebuffer.iter_mut().for_each(|pixel| *pixel = blend_color(0x000000, *pixel, amount));
+    }
+}
+
+// Sprite plano que siempre mira a la cámara
+struct Billboard<'a> {
+    pos: na::Vector2<f32>,
+    sheet: &'a SpriteSheet,
+    frame: usize,
+    size: f32,  // Altura en pantalla relativa a la altura de la ventana a distancia 1
+    lift: f32,  // Desplazamiento vertical en proporción a la altura del sprite (positivo = hacia abajo)
+    alpha: f32,
+    shimmer: Option<f32>, // Si tiene valor, se dibuja solo una silueta ondulante en ese instante
+}
+
+const SILHOUETTE_COLOR: u32 = 0x3A2A4A;
+
+// Desplazamiento que apoya un sprite del tamaño dado sobre el piso
+fn floor_lift(size: f32) -> f32 {
+    1.0 / (4.0 * size) - 0.5
+}
+
+fn render_billboard(framebuffer: &mut [u32], player: &Player, camera: f32, billboard: &Billboard, z_buffer: &mut [f32]) {
     let sprite_dir = na::Vector2::new(
-        pos.x - player.pos.x,
-        pos.y - player.pos.y,
+        billboard.pos.x - player.pos.x,
+        billboard.pos.y - player.pos.y,
     );
 
     let sprite_d = sprite_dir.norm();
@@ -199,12 +272,16 @@ fn render_enemy(framebuffer: &mut Vec<u32>, player: &Player, pos: &na::Vector2<f
         return;
     }
 
+    let sheet = billboard.sheet;
     let screen_x = (WIDTH as f32 / 2.0) * (1.0 + sprite_angle / player.fov);
-    let sprite_height = (HEIGHT as f32 / sprite_d) * 0.4; // Ajuste de tamaño del sprite
-    let sprite_width = sprite_height;
+    let sprite_height = (HEIGHT as f32 / sprite_d) * billboard.size; // Ajuste de tamaño del sprite
+    let sprite_width = sprite_height * sheet.frame_width as f32 / sheet.frame_height as f32;
+    let lift_offset = (billboard.lift * sprite_height) as isize;
+    // Con los ojos más arriba, lo que está en el piso se ve más abajo
+    let camera_offset = ((camera - CAMERA_HEIGHT) * (HEIGHT / 2) as f32 / sprite_d) as isize;
 
     let start_x = screen_x as isize - (sprite_width as isize / 2);
-    let start_y = (HEIGHT as isize / 2) - (sprite_height as isize / 2);
+    let start_y = (HEIGHT as isize / 2) - (sprite_height as isize / 2) + lift_offset + camera_offset;
     let end_x = start_x + sprite_width as isize;
     let end_y = start_y + sprite_height as isize;
 
@@ -212,16 +289,29 @@ fn render_enemy(framebuffer: &mut Vec<u32>, player: &Player, pos: &na::Vector2<f
         for x in start_x..end_x {
             for y in start_y..end_y {
                 if x >= 0 && x < WIDTH as isize && y >= 0 && y < HEIGHT as isize {
+                    // Las siluetas ondulan desplazando cada fila de la textura
+                    let wave = match billboard.shimmer {
+                        Some(time) => ((y as f32 * 0.15 + time * 8.0).sin() * sprite_width * 0.04) as isize,
+                        None => 0,
+                    };
+                    let sx = (x - start_x + wave).clamp(0, sprite_width as isize - 1) as usize;
+                    let tx = (sx * sheet.frame_width as usize / sprite_width as usize) as u32;
+                    let ty = ((y - start_y) as usize * sheet.frame_height as usize / sprite_height as usize) as u32;
+                    let color = sheet.get_pixel_color(billboard.frame, tx, ty);
+
                     let x = x as usize;
                     let y = y as usize;
 
-                    let tx = ((x - start_x as usize) * CHARACTER.width as usize / sprite_width as usize) as u32;
-                    let ty = ((y - start_y as usize) * CHARACTER.height as usize / sprite_height as usize) as u32;
-                    let color = CHARACTER.get_pixel_color(tx, ty);
-
                     // Solo dibujar si el color no es igual al del fondo (asumimos que el fondo es blanco)
                     if color != 0xFFFFFF { // Ajusta este valor si el color de fondo es diferente
-                        framebuffer[y * WIDTH + x] = color;
+                        let index = y * WIDTH + x;
+                        framebuffer[index] = match billboard.shimmer {
+                            Some(time) => {
+                                let flicker = 0.3 + 0.1 * (time * 11.0 + y as f32 * 0.05).sin();
+                                blend_color(SILHOUETTE_COLOR, framebuffer[index], flicker)
+                            }
+                            None => blend_color(color, framebuffer[index], billboard.alpha),
+                        };
                     }
                 }
             }
@@ -230,20 +320,129 @@ fn render_enemy(framebuffer: &mut Vec<u32>, player: &Player, pos: &na::Vector2<f
     }
 }
 
-fn render_enemies(framebuffer: &mut Vec<u32>, player: &Player, z_buffer: &mut [f32]) {
-    let enemies = vec![
-        na::Vector2::new(2.0, 5.0),
-        na::Vector2::new(11.0, 3.5),
-        na::Vector2::new(5.0, 5.0),
-        na::Vector2::new(8.0, 7.0),
-    ];
+// Sin el Scope Silph los fantasmas no se pueden identificar: solo se ve una silueta
+fn enemy_billboard(player: &Player, enemy: &Enemy, revealed: bool) -> Billboard<'static> {
+    // Elegir el cuadro según la animación y el ángulo desde el que lo vemos
+    let to_player = player.pos - enemy.pos;
+    let view_angle = to_player.y.atan2(to_player.x) - enemy.facing;
+
+    Billboard {
+        pos: enemy.pos,
+        sheet: &GHOST_SHEET,
+        frame: enemy.animator.frame(&GHOST_FLOAT, view_angle),
+        size: 0.4,
+        lift: enemy.bob * 0.1, // Flotación
+        // Los fantasmas ahuyentados se ven más tenues
+        alpha: if enemy.repelled > 0.0 { enemy.alpha * 0.5 } else { enemy.alpha },
+        shimmer: if revealed { None } else { Some(enemy.animator.time) },
+    }
+}
 
-    for enemy in &enemies {
-        render_enemy(framebuffer, player, enemy, z_buffer);
+fn render_sprites(framebuffer: &mut [u32], player: &Player, world: &World, trainer: Option<na::Vector2<f32>>, z_buffer: &mut [f32]) {
+    let revealed = player.inventory.has(ItemKind::SilphScope);
+    let floor = world.current();
+    let mut billboards: Vec<Billboard> = floor.enemies.iter().map(|enemy| enemy_billboard(player, enemy, revealed)).collect();
+    for item in &floor.items {
+        billboards.push(Billboard {
+            pos: item.pos,
+            sheet: &ITEM_SPRITES[item.kind.index()],
+            frame: 0,
+            size: 0.15,
+            lift: floor_lift(0.15),
+            alpha: 1.0,
+            shimmer: None,
+        });
+    }
+    if let Some(pos) = trainer {
+        billboards.push(Billboard {
+            pos,
+            sheet: &TRAINER_SHEET,
+            frame: 0,
+            size: 0.5,
+            lift: floor_lift(0.5),
+            alpha: 0.4, // El entrenador fantasma es translúcido
+            shimmer: None,
+        });
+    }
+
+    // Dibujar del más lejano al más cercano para que la transparencia se mezcle bien
+    billboards.sort_by(|a, b| {
+        let da = (a.pos - player.pos).norm();
+        let db = (b.pos - player.pos).norm();
+        db.total_cmp(&da)
+    });
+
+    for billboard in &billboards {
+        render_billboard(framebuffer, player, world.palette.camera, billboard, z_buffer);
+    }
+}
+
+// Barra inferior con un espacio por cada tipo de objeto y la cantidad recogida
+fn render_inventory_bar(framebuffer: &mut [u32], inventory: &Inventory) {
+    let slot_width = 110;
+    let icon_size = 40;
+    let bar_height = 56;
+    let bar_width = slot_width * ItemKind::ALL.len();
+    let bar_x = (WIDTH - bar_width) / 2;
+    let bar_y = HEIGHT - bar_height - 8;
+
+    // Fondo semitransparente
+    for y in bar_y..bar_y + bar_height {
+        for x in bar_x..bar_x + bar_width {
+            framebuffer[y * WIDTH + x] = blend_color(0x000000, framebuffer[y * WIDTH + x], 0.6);
+        }
+    }
+
+    let count_style = TextStyle::new(16.0, 0xFFFFFF);
+    for (slot, &kind) in ItemKind::ALL.iter().enumerate() {
+        let sheet = &ITEM_SPRITES[kind.index()];
+        let count = inventory.count(kind);
+        let icon_x = bar_x + slot * slot_width + 8;
+        let icon_y = bar_y + (bar_height - icon_size) / 2;
+
+        // Los objetos que aún no se tienen se ven apagados
+        let alpha = if count > 0 { 1.0 } else { 0.3 };
+        for y in 0..icon_size {
+            for x in 0..icon_size {
+                let tx = (x * sheet.frame_width as usize / icon_size) as u32;
+                let ty = (y * sheet.frame_height as usize / icon_size) as u32;
+                let color = sheet.get_pixel_color(0, tx, ty);
+                if color != 0xFFFFFF {
+                    let index = (icon_y + y) * WIDTH + icon_x + x;
+                    framebuffer[index] = blend_color(color, framebuffer[index], alpha);
+                }
+            }
+        }
+
+        let count_text = format!("x{}", count);
+        render_text(framebuffer, &count_text, icon_x + icon_size + 6, icon_y + 12, count_style);
     }
 }
 
-fn render_welcome_screen(framebuffer: &mut Vec<u32>) {
+// Lista de los mejores puntajes de un laberinto, resaltando la posición indicada
+fn render_score_table(framebuffer: &mut [u32], top: &[ScoreEntry], center_x: usize, y: usize, highlight: Option<usize>) {
+    let style = TextStyle::new(14.0, 0xFFFFFF).centered();
+    render_text(framebuffer, "Mejores puntajes", center_x, y, TextStyle { color: 0xFFD700, ..style });
+
+    if top.is_empty() {
+        render_text(framebuffer, "Aún no hay récords", center_x, y + 24, style);
+        return;
+    }
+
+    for (i, entry) in top.iter().take(5).enumerate() {
+        let line = format!(
+            "{}. {:>5} pts  {}  {:>4} pasos",
+            i + 1,
+            entry.score,
+            scores::format_time(entry.time),
+            entry.steps
+        );
+        let color = if highlight == Some(i) { 0x30FF30 } else { 0xFFFFFF };
+        render_text(framebuffer, &line, center_x, y + 24 * (i + 1), TextStyle { color, ..style });
+    }
+}
+
+fn render_welcome_screen(framebuffer: &mut [u32], campaign: &Campaign, progress: &Progress, selected: usize, top: &[ScoreEntry]) {
     // Cargar la imagen y calcular la escala para mantener la relación de aspecto
     let intro_width = INTRO_SPRITE.width;
     let intro_height = INTRO_SPRITE.height;
@@ -269,25 +468,45 @@ fn render_welcome_screen(framebuffer: &mut Vec<u32>) {
     }
 
     // Cambiar ajustes para las letras
-    let large_scale = Scale::uniform(30.0);
-    let small_scale = Scale::uniform(18.0);
-    let color = 0xFFD700;
+    // Los títulos van sobre la imagen, así que llevan sombra para leerse bien
+    let large = TextStyle::new(30.0, 0xFFD700).centered().shadow(0x000000);
+    let small = TextStyle::new(18.0, 0xFFD700).centered().shadow(0x000000);
+
+    render_text(framebuffer, "Bienvenido al laberinto del", WIDTH / 2, offset_y + 20, large);
+    render_text(framebuffer, "Pueblo Lavanda", WIDTH / 2, offset_y + 180, large);
+
+    let help_text = "Elige un nivel con las flechas y presiona 'enter'\nControles: W, A, S, D\n'C': cargar partida  'E': editar nivel";
+    render_text(framebuffer, help_text, WIDTH / 2, offset_y + 330, small);
+
+    // Lista de niveles a la izquierda y récords del nivel elegido a la derecha
+    let list_y = offset_y + scaled_height + 20;
+    render_level_list(framebuffer, campaign, progress, selected, list_y);
+    render_score_table(framebuffer, top, WIDTH * 3 / 4, list_y, None);
+}
 
-    let welcome_text = "Bienvenido al laberinto del";
-    let welcome_text2 = "       Pueblo Lavanda";
-    let start_text = "Presiona 'enter' para iniciar";
-    let controls_text = "    Controles: W, A, S, D";
+const LEVEL_LIST_ROWS: usize = 5;
 
-    let text_x = WIDTH / 2 - (welcome_text.len() as f32 * large_scale.x / 2.0) as usize;
-    render_text(framebuffer, welcome_text, text_x, offset_y + 20, large_scale, color);
-    render_text(framebuffer, welcome_text2, text_x, offset_y + 180, large_scale, color);
+fn render_level_list(framebuffer: &mut [u32], campaign: &Campaign, progress: &Progress, selected: usize, y: usize) {
+    render_text(framebuffer, &campaign.name, WIDTH / 4, y, TextStyle::new(14.0, 0xFFD700).centered());
 
-    let small_text_x = WIDTH / 2 - (start_text.len() as f32 * small_scale.x / 2.0) as usize;
-    render_text(framebuffer, start_text, small_text_x, offset_y + 330, small_scale, color);
-    render_text(framebuffer, controls_text, small_text_x, offset_y + 360, small_scale, color);
+    // Desplazar la lista para que el nivel elegido siempre se vea
+    let first = (selected + 1).saturating_sub(LEVEL_LIST_ROWS);
+    for (row, (i, level)) in campaign.levels.iter().enumerate().skip(first).take(LEVEL_LIST_ROWS).enumerate() {
+        let (status, color) = if !progress.is_unlocked(campaign, i) {
+            ("bloqueado", 0x777777)
+        } else if progress.is_completed(&level.id) {
+            ("completado", 0xFFFFFF)
+        } else {
+            ("nuevo", 0xFFFFFF)
+        };
+        let marker = if i == selected { ">" } else { " " };
+        let color = if i == selected { 0x30FF30 } else { color };
+        let line = format!("{} {}. {} ({})", marker, i + 1, level.name, status);
+        render_text(framebuffer, &line, 40, y + 24 * (row + 1), TextStyle::new(14.0, color));
+    }
 }
 
-fn render_success_screen(framebuffer: &mut Vec<u32>) {
+fn render_success_screen(framebuffer: &mut [u32], stats: &RunStats, top: &[ScoreEntry], position: Option<usize>, has_next: bool) {
     // Establecer un fondo negro
     for y in 0..HEIGHT {
         for x in 0..WIDTH {
@@ -322,39 +541,44 @@ fn render_success_screen(framebuffer: &mut Vec<u32>) {
         }
     }
 
-    // Definir escalas para diferentes tamaños de texto
-    let large_scale = Scale::uniform(30.0);
-    let medium_scale = Scale::uniform(16.0);
-    let color = 0xFFD700;
+    // Textos centrados por su ancho real; los de encima de la medalla llevan sombra
+    let large = TextStyle::new(30.0, 0xFFD700).centered().shadow(0x000000);
+    let medium = TextStyle::new(16.0, 0xFFD700).centered();
 
-    let success_text = "¡Felicidades!";
-    let message_text = "Por completar el laberinto, toma esta Medalla Arcoíris";
-    let message_text2 = "¡Te la has ganado!";
-    let exit_text = "Presiona 'ESC' para salir";
-
-    // Simplificar centrado de texto calculando el ancho real del texto
-    let success_text_width = success_text.len() as f32 * large_scale.x;
-    let message_text_width = message_text.len() as f32 * medium_scale.x;
-    let message_text2_width = message_text2.len() as f32 * medium_scale.x;
-    let exit_text_width = exit_text.len() as f32 * medium_scale.x;
-
-    // Cálculo seguro de las posiciones
-    let success_text_x = (WIDTH as f32 - success_text_width) / 2.0;
-    render_text(framebuffer, success_text, success_text_x as usize, offset_y + 20, large_scale, color);
+    let exit_text = if has_next {
+        "Presiona 'enter' para el siguiente nivel o 'ESC' para salir"
+    } else {
+        "Presiona 'enter' para volver al inicio o 'ESC' para salir"
+    };
 
-    let message_text_x = (WIDTH as f32 - message_text_width) / 2.0;
-    render_text(framebuffer, message_text, message_text_x as usize, offset_y + 100, medium_scale, color);
+    render_text(framebuffer, "¡Felicidades!", WIDTH / 2, offset_y + 20, large);
+    render_text(framebuffer, "Por completar el laberinto, toma esta Medalla Arcoíris", WIDTH / 2, offset_y + 100, medium.shadow(0x000000));
+    render_text(framebuffer, "¡Te la has ganado!", WIDTH / 2, offset_y + 140, medium.shadow(0x000000));
+    render_text(framebuffer, exit_text, WIDTH / 2, HEIGHT - 40, medium);
+
+    // Resultado de la partida y tabla de récords
+    let result_text = format!(
+        "Puntaje: {}  Tiempo: {}  Pasos: {}  Fantasmas: {:.1} s",
+        stats.score(),
+        scores::format_time(stats.time),
+        stats.steps(),
+        stats.damage
+    );
+    let result_y = offset_y + scaled_height + 10;
+    render_text(framebuffer, &result_text, WIDTH / 2, result_y, medium);
 
-    let message_text2_x = (WIDTH as f32 - message_text2_width) / 2.0;
-    render_text(framebuffer, message_text2, message_text2_x as usize, offset_y + 140, medium_scale, color);
+    if position == Some(0) {
+        render_text(framebuffer, "¡Nuevo récord!", WIDTH / 2, result_y + 26, TextStyle { color: 0x30FF30, ..medium });
+    }
 
-    let exit_text_x = (WIDTH as f32 - exit_text_width) / 2.0;
-    render_text(framebuffer, exit_text, exit_text_x as usize, offset_y + 550, medium_scale, color);
+    render_score_table(framebuffer, top, WIDTH / 2, result_y + 56, position);
 }
 
-fn render2d(framebuffer: &mut Vec<u32>, maze: &Vec<Vec<char>>, block_size: usize, player: &Player) {
-    for (row, line) in maze.iter().enumerate() {
+fn render2d(framebuffer: &mut [u32], floor: &Floor, block_size: usize, player: &Player) {
+    for (row, line) in floor.maze.iter().enumerate() {
         for (col, &cell) in line.iter().enumerate() {
+            // Los paneles no están en la cuadrícula; se pintan como una celda más
+            let cell = if floor.warp_at(col, row).is_some() { WARP } else { cell };
             draw_cell(
                 framebuffer,
                 col * block_size,
@@ -367,6 +591,8 @@ fn render2d(framebuffer: &mut Vec<u32>, maze: &Vec<Vec<char>>, block_size: usize
         }
     }
 
+    draw_segments(framebuffer, &floor.walls, 0, 0, block_size as f32, 0xFFFFFF);
+
     // Dibujar al jugador en la vista 2D
     let player_x = (player.pos.x * block_size as f32) as usize;
     let player_y = (player.pos.y * block_size as f32) as usize;
@@ -379,211 +605,950 @@ fn render2d(framebuffer: &mut Vec<u32>, maze: &Vec<Vec<char>>, block_size: usize
     }
 }
 
-fn render3d(framebuffer: &mut Vec<u32>, maze: &Vec<Vec<char>>, player: &Player, block_size: f32, z_buffer: &mut [f32], enemy_positions: &Vec<na::Vector2<f32>>) {
-    render_sky(framebuffer);
-    render_floor(framebuffer);
-    
-    let num_rays = WIDTH;
-    let hh = (HEIGHT / 2) as f32;
+// Lo que la vista 3D reutiliza de un cuadro a otro. Cada columna de la pantalla es
+// independiente, así que se reparten entre los hilos; como las columnas del framebuffer no son
+// contiguas, cada hilo dibuja la suya en `columns` (una columna seguida de la otra) y al final
+// se copian al framebuffer por bloques
+struct View3d {
+    columns: Vec<u32>,
+    z_buffer: Vec<f32>, // Distancia a la pared en cada columna, que tapa a los sprites
+}
 
-    for i in 0..num_rays {
-        let current_ray = i as f32 / num_rays as f32;
-        let a = player.a - (player.fov / 2.0) + (player.fov * current_ray);
+// Filas y columnas de cada bloque que se copia de `columns` al framebuffer: lo que se lee y lo
+// que se escribe de un bloque cabe junto en la caché
+const TILE: usize = 32;
 
-        // Llamada a la función cast_ray
-        let ray_hit = cast_ray(maze, player, a);
+impl View3d {
+    fn new() -> View3d {
+        View3d { columns: vec![0; WIDTH * HEIGHT], z_buffer: vec![f32::MAX; WIDTH] }
+    }
+}
 
-        // Calcular la altura de la stake basada en la distancia
-        let stake_height = (hh / ray_hit.distance) as usize;
+fn render3d(framebuffer: &mut [u32], view: &mut View3d, world: &World, player: &Player, trainer: Option<na::Vector2<f32>>) {
+    let View3d { columns, z_buffer } = view;
+    columns.par_chunks_mut(HEIGHT).zip(z_buffer.par_iter_mut()).enumerate().for_each(|(i, (column, z))| {
+        *z = render_column(column, i, world, player);
+    });
+    framebuffer.par_chunks_mut(WIDTH * TILE).enumerate().for_each(|(block, rows)| {
+        let top = block * TILE;
+        let height = rows.len() / WIDTH;
+        for left in (0..WIDTH).step_by(TILE) {
+            for x in left..(left + TILE).min(WIDTH) {
+                let column = &columns[x * HEIGHT + top..x * HEIGHT + top + height];
+                for (y, &pixel) in column.iter().enumerate() {
+                    rows[y * WIDTH + x] = pixel;
+                }
+            }
+        }
+    });
 
-        // Calcular las posiciones superior e inferior de la stake
-        let mut stake_top = (hh - (stake_height as f32 / 2.0)) as usize;
-        let mut stake_bottom = (hh + (stake_height as f32 / 2.0)) as usize;
+    // Renderizar los enemigos y los objetos
+    render_sprites(framebuffer, player, world, trainer, z_buffer);
 
-        // Calcular la coordenada de la textura en función del punto de impacto
-        let texture_x = if ray_hit.is_vertical {
-            ray_hit.hit_y % 1.0
+    // Llamar al render_minimap
+    render_minimap(framebuffer, world, player, trainer);
+}
+
+// Dibuja la columna `i` de la vista 3D (`column` va de arriba hacia abajo) y devuelve la
+// distancia a la pared que tapa los sprites en esa columna
+fn render_column(column: &mut [u32], i: usize, world: &World, player: &Player) -> f32 {
+    let palette = &world.palette;
+    let floor = &palette.floor;
+    let current = world.current();
+
+    let num_rays = WIDTH;
+    let hh = (HEIGHT / 2) as f32;
+
+    // Altura de los ojos y de la pared más alta
+    let camera = palette.camera;
+    let max_height = palette.max_height();
+
+    let current_ray = i as f32 / num_rays as f32;
+    let a = player.a - (player.fov / 2.0) + (player.fov * current_ray);
+
+    // Llamada a la función cast_ray; los impactos van del más cercano al más lejano
+    // Una pared que no tapa nada detrás, que queda por debajo de los ojos o que tiene
+    // partes transparentes deja pasar el rayo
+    let see_through = |cell| {
+        let height = palette.height(cell);
+        height < max_height || height < camera || palette.is_transparent(cell)
+    };
+    let ray_hits = cast_ray_through(&current.maze, &current.doors, &current.walls, player, a, see_through);
+    let farthest = &ray_hits[ray_hits.len() - 1];
+
+    // Fila de la pantalla donde se ve la altura `z` de una pared a la distancia dada
+    let screen_y = |z: f32, distance: f32| hh - (z - camera) * hh / distance;
+    let floor_start = screen_y(0.0, farthest.distance).clamp(hh, HEIGHT as f32 - 1.0) as usize;
+
+    // De fondo, el cielo estirado sobre la mitad de arriba y el piso sobre la de abajo, hasta
+    // donde empieza el piso visto en perspectiva
+    let sky = &palette.ceiling;
+    let sky_x = (i as f32 / WIDTH as f32 * sky.width as f32) as u32;
+    let floor_x = (i * floor.width as usize / WIDTH) as u32;
+    for (y, pixel) in column.iter_mut().enumerate().take(floor_start) {
+        *pixel = if y < HEIGHT / 2 {
+            sky.get_pixel_color(sky_x, (y as f32 / (HEIGHT / 2) as f32 * sky.height as f32) as u32)
         } else {
-            ray_hit.hit_x % 1.0
+            floor.get_pixel_color(floor_x, ((y - HEIGHT / 2) * floor.height as usize / (HEIGHT / 2)) as u32)
         };
+    }
 
-        // Limitar los valores dentro del rango del framebuffer
-        if stake_top >= HEIGHT {
-            stake_top = HEIGHT - 1;
-        }
-        if stake_bottom >= HEIGHT {
-            stake_bottom = HEIGHT - 1;
-        }
+    // Renderizar el piso hasta la pared más lejana; las paredes bajas se dibujan encima
+    for (y, pixel) in column.iter_mut().enumerate().skip(floor_start) {
+        // Con la cámara a la altura de siempre, el piso se ve igual que antes
+        let current_distance = hh * (camera / CAMERA_HEIGHT) / (y as f32 - hh);
 
-        // Dibujar la stake directamente en el framebuffer
-        for y in stake_top..stake_bottom {
-            let texture_y = (y as f32 - stake_top as f32) / (stake_bottom as f32 - stake_top as f32);
-            let color = cell_to_texture_color(ray_hit.wall_type, ray_hit.is_vertical, texture_x, texture_y);
-            framebuffer[y * WIDTH + i] = color;
-        }
+        let weight = current_distance / farthest.distance;
 
-        // Renderizar el piso
-        for y in stake_bottom..HEIGHT {
-            let current_distance = hh / (y as f32 - hh);
+        let floor_x = weight * farthest.hit_x + (1.0 - weight) * player.pos.x;
+        let floor_y = weight * farthest.hit_y + (1.0 - weight) * player.pos.y;
 
-            let weight = current_distance / ray_hit.distance;
+        let texture_x = (floor_x * floor.width as f32) as u32 % floor.width;
+        let texture_y = (floor_y * floor.height as f32) as u32 % floor.height;
 
-            let floor_x = weight * ray_hit.hit_x + (1.0 - weight) * player.pos.x;
-            let floor_y = weight * ray_hit.hit_y + (1.0 - weight) * player.pos.y;
+        let color = floor_tint(floor.get_pixel_color(texture_x, texture_y), current, floor_x, floor_y);
+        *pixel = apply_lights(color, &current.lights, floor_x, floor_y);
+    }
 
-            let texture_x = (floor_x * FLOOR.width as f32) as u32 % FLOOR.width;
-            let texture_y = (floor_y * FLOOR.height as f32) as u32 % FLOOR.height;
+    // Dibujar las stakes de atrás hacia adelante: las partes transparentes de una pared
+    // se mezclan con lo que ya se dibujó detrás
+    for ray_hit in ray_hits.iter().rev() {
+        let height = palette.height(ray_hit.wall_type);
+        let top = screen_y(height, ray_hit.distance);
+        let bottom = screen_y(0.0, ray_hit.distance);
 
-            let color = FLOOR.get_pixel_color(texture_x, texture_y);
-            framebuffer[y * WIDTH + i] = color;
+        // Limitar los valores dentro del rango del framebuffer
+        let stake_top = top.clamp(0.0, HEIGHT as f32 - 1.0) as usize;
+        let stake_bottom = bottom.clamp(0.0, HEIGHT as f32 - 1.0) as usize;
+
+        // Una unidad de altura ocupa (bottom - top) / height píxeles de la pantalla: con eso
+        // se elige una vez el tamaño de la textura para toda la stake
+        let texture = palette.tile(ray_hit.wall_type).face(ray_hit.face);
+        let mip = texture.mip(texture.height as f32 * height / (bottom - top));
+        let texture_column = mip.column(ray_hit.texture_x);
+
+        // La textura se repite en cada unidad de altura, empezando desde arriba como
+        // en una pared normal: una cerca muestra la mitad de abajo
+        for (y, pixel) in column.iter_mut().enumerate().take(stake_bottom).skip(stake_top) {
+            let z = height * (bottom - y as f32) / (bottom - top);
+            let texture_y = (1.0 - z).rem_euclid(1.0);
+            let (color, alpha) = match palette.filter {
+                Filter::Nearest => texture_column.pixel(texture_y),
+                Filter::Bilinear => mip.sample(ray_hit.texture_x, texture_y, Filter::Bilinear),
+            };
+            if alpha == 0 {
+                continue;
+            }
+            let color = apply_lights(color, &current.lights, ray_hit.hit_x, ray_hit.hit_y);
+            *pixel = if alpha == u8::MAX { color } else { blend_color(color, *pixel, alpha as f32 / 255.0) };
         }
-
-        z_buffer[i] = ray_hit.distance;
     }
-    // Renderizar los enemigos
-    render_enemies(framebuffer, player, z_buffer);
 
-    // Llamar al render_minimap
-    render_minimap(framebuffer, maze, player, block_size as usize, enemy_positions);
+    // Los sprites se esconden detrás de las paredes que tapan la vista, no de las cercas
+    // ni de las rejas
+    let blocking = ray_hits
+        .iter()
+        .find(|hit| palette.height(hit.wall_type) >= camera && !palette.is_transparent(hit.wall_type))
+        .unwrap_or(farthest);
+    blocking.distance
 }
 
-fn main() {
-    let mut window = Window::new(
-        "Maze",
-        WIDTH,
-        HEIGHT,
-        WindowOptions::default(),
-    )
-    .unwrap_or_else(|e| {
-        panic!("{}", e);
-    });
-
-    let mut framebuffer: Vec<u32> = vec![0; WIDTH * HEIGHT];
-    let block_size = 80;
+// Pantalla de inicio: elegir un nivel desbloqueado o cargar una partida. Devuelve None al salir
+fn select_level(window: &mut Window, framebuffer: &mut [u32], campaign: &Campaign, progress: &Progress, high_scores: &HighScores, mut selected: usize, race: &mut bool) -> Option<(usize, GameState)> {
+    let mut error = String::new();
 
-    // Renderizar la pantalla de bienvenida
-    render_welcome_screen(&mut framebuffer);
-    window.update_with_buffer(&framebuffer, WIDTH, HEIGHT).unwrap();
+    while window.is_open() && !window.is_key_pressed(Key::Escape, KeyRepeat::No) {
+        if window.is_key_pressed(Key::Up, KeyRepeat::Yes) && selected > 0 {
+            selected -= 1;
+            error.clear();
+        }
+        if window.is_key_pressed(Key::Down, KeyRepeat::Yes) && progress.is_unlocked(campaign, selected + 1) && selected + 1 < campaign.levels.len() {
+            selected += 1;
+            error.clear();
+        }
+        if window.is_key_pressed(Key::Enter, KeyRepeat::No) {
+            // Un nivel con errores no se juega: se explica el problema en pantalla
+            match GameState::new(&campaign.levels[selected]) {
+                Ok(state) => return Some((selected, state)),
+                Err(e) => error = e.to_string(),
+            }
+        }
+        if window.is_key_pressed(Key::C, KeyRepeat::No) {
+            if let Some(loaded) = load_menu(window, framebuffer, campaign) {
+                return Some(loaded);
+            }
+        }
+        if window.is_key_pressed(Key::G, KeyRepeat::No) {
+            *race = !*race;
+        }
+        if window.is_key_pressed(Key::E, KeyRepeat::No) {
+            let level = &campaign.levels[selected];
+            match &level.file {
+                Some(file) => edit_level(window, framebuffer, file, level.theme.clone()),
+                None => error = "Los niveles generados no se pueden editar".to_string(),
+            }
+        }
 
-    // Esperar a que el usuario pulse Enter
-    while window.is_open() && !window.is_key_down(Key::Enter) {
-        window.update();
+        let top = high_scores.top(&campaign.levels[selected].key());
+        render_welcome_screen(framebuffer, campaign, progress, selected, top);
+        let race_text = format!("Carrera contra tu mejor partida (G): {}", if *race { "activada" } else { "desactivada" });
+        render_text(framebuffer, &race_text, 40, HEIGHT - 30, TextStyle::new(14.0, if *race { 0x30FF30 } else { 0x777777 }));
+        render_text(framebuffer, &error, 40, HEIGHT - 55, TextStyle::new(14.0, 0xFF5050));
+        window.update_with_buffer(framebuffer, WIDTH, HEIGHT).unwrap();
         std::thread::sleep(Duration::from_millis(16));
     }
+    None
+}
 
-    // Crear el reproductor de música de fondo
-    let background_music = AudioPlayer::new("assets/Musica de Pueblo Lavanda.mp3").expect("Failed to initialize background music");
+// Menú de carga: lista las ranuras de guardado. Devuelve None al volver a la pantalla de inicio
+fn load_menu(window: &mut Window, framebuffer: &mut [u32], campaign: &Campaign) -> Option<(usize, GameState)> {
+    let saves: Vec<_> = SLOTS.iter().map(|&(_, path)| SaveGame::load(path)).collect();
+    let mut selected = 0;
+    let mut error = String::new();
 
-    background_music.set_volume(0.2);
+    while window.is_open() && !window.is_key_pressed(Key::Escape, KeyRepeat::No) {
+        if window.is_key_pressed(Key::Up, KeyRepeat::No) && selected > 0 {
+            selected -= 1;
+        }
+        if window.is_key_pressed(Key::Down, KeyRepeat::No) && selected + 1 < SLOTS.len() {
+            selected += 1;
+        }
+        if window.is_key_pressed(Key::Enter, KeyRepeat::No) {
+            match saves[selected].as_ref().map_err(ToString::to_string).and_then(|save| save.restore(campaign).map_err(|e| e.to_string())) {
+                Ok(loaded) => return Some(loaded),
+                Err(e) => error = e,
+            }
+        }
+
+        framebuffer.iter_mut().for_each(|pixel| *pixel = 0);
+        let style = TextStyle::new(16.0, 0xFFD700);
+        render_text(framebuffer, "Cargar partida", 40, 60, TextStyle { size: 30.0, ..style });
+
+        for (i, ((name, _), save)) in SLOTS.iter().zip(&saves).enumerate() {
+            let detail = match save {
+                Ok(save) => save.summary(campaign),
+                Err(e) => e.to_string(),
+            };
+            let marker = if i == selected { ">" } else { " " };
+            let color = match (i == selected, save.is_ok()) {
+                (true, _) => 0x30FF30,
+                (false, true) => 0xFFFFFF,
+                (false, false) => 0x777777,
+            };
+            render_text(framebuffer, &format!("{} {}: {}", marker, name, detail), 40, 140 + 36 * i, TextStyle { color, ..style });
+        }
+
+        render_text(framebuffer, &error, 40, 160 + 36 * SLOTS.len(), TextStyle { color: 0xFF5050, ..style }.wrap((WIDTH - 80) as f32));
+        render_text(framebuffer, "Presiona 'enter' para cargar o 'ESC' para volver", 40, HEIGHT - 60, style);
+        window.update_with_buffer(framebuffer, WIDTH, HEIGHT).unwrap();
+        std::thread::sleep(Duration::from_millis(16));
+    }
+    None
+}
 
-    background_music.play();
+// Cómo se juega un nivel
+#[derive(Clone, Copy)]
+enum Mode<'a> {
+    Normal,
+    Race,                // Con el entrenador fantasma de la mejor partida
+    Playback(&'a Replay), // Las teclas salen de una grabación en lugar del teclado
+}
 
-    // Crear el reproductor de efectos de sonido para los pasos
-    let steps_sound = AudioPlayer::new("assets/Efecto de Pasos.mp3").expect("Failed to initialize steps sound");
+// Cómo terminó un nivel
+enum LevelEnd {
+    Completed(RunStats),
+    Load(usize, Box<GameState>), // Se cargó una partida guardada o un panel llevó a otro nivel
+    Quit,
+}
 
-    let enemy_positions = vec![
-        na::Vector2::new(2.0, 5.0),
-        na::Vector2::new(11.0, 3.5),
-        na::Vector2::new(5.0, 5.0),
-        na::Vector2::new(8.0, 7.0),
-    ];
+// Juega un nivel hasta llegar a la meta, cargar una partida o salir del juego
+fn play_level(window: &mut Window, framebuffer: &mut [u32], campaign: &Campaign, index: usize, mut state: GameState, sounds: &Sounds, mode: Mode) -> LevelEnd {
+    let block_size = 80;
 
-    let maze = maze::load_maze("maze.txt");
+    let level = &campaign.levels[index];
+    let mut message = level.name.clone();
+    let mut message_time = Instant::now();
 
-    let mut player = Player {
-        pos: na::Vector2::new(1.5, 1.5),
-        a: std::f32::consts::FRAC_PI_3,
-        fov: std::f32::consts::FRAC_PI_3,
+    // Solo se graba una partida que empieza desde el inicio del nivel
+    let tape = match mode {
+        Mode::Playback(replay) => Some(replay.inputs()),
+        _ => None,
+    };
+    let mut recording = (tape.is_none() && state.ticks == 0).then(|| Replay::new(level));
+
+    // En la carrera, el entrenador fantasma repite la mejor partida del nivel
+    let best_path = replay::best_path(&level.id);
+    let mut race = match mode {
+        Mode::Race if !std::path::Path::new(&best_path).exists() => {
+            message = "Aún no hay una mejor partida para este nivel".to_string();
+            None
+        }
+        Mode::Race => match Replay::load(&best_path).map_err(|e| e.to_string()).and_then(|best| GhostRace::new(campaign, &best)) {
+            Ok(race) => Some(race),
+            Err(e) => {
+                message = format!("Sin carrera: {}", e);
+                None
+            }
+        },
+        _ => None,
     };
+    let mut input = Input::default();
+    let mut accumulator = 0.0;
 
-    let mut mode = "3D";
+    let mut view = "3D";
+    let mut view3d = View3d::new();
 
     let mut last_time = Instant::now();
+    let mut last_frame = Instant::now();
     let mut frame_count = 0;
     let mut fps_text = String::new();
 
-    // Definir la posición del CentroPokemon (meta)
-    let goal_area = vec![
-        na::Vector2::new(11.0, 7.0),
-        na::Vector2::new(12.0, 7.0),
-        na::Vector2::new(11.0, 8.0),
-        na::Vector2::new(12.0, 8.0),
-    ];
-    let mut game_completed = false;
+    sounds.music.play();
 
     while window.is_open() && !window.is_key_down(Key::Escape) {
         let frame_start_time = Instant::now();
 
-        // Solo procesar eventos y actualizar si el juego no ha sido completado
-        if !game_completed {
-            process_events(&window, &mut player, &maze, block_size, &steps_sound);
+        // Guardado rápido con F5 y carga rápida con F9
+        if tape.is_none() && window.is_key_pressed(Key::F5, KeyRepeat::No) {
+            message = match SaveGame::capture(level, &state).save(QUICK_SAVE) {
+                Ok(()) => "Partida guardada".to_string(),
+                Err(e) => format!("No se pudo guardar: {}", e),
+            };
+            message_time = frame_start_time;
+        }
+        if tape.is_none() && window.is_key_pressed(Key::F9, KeyRepeat::No) {
+            match SaveGame::load(QUICK_SAVE).and_then(|save| save.restore(campaign)) {
+                Ok((index, loaded)) => return LevelEnd::Load(index, Box::new(loaded)),
+                Err(e) => {
+                    message = e.to_string();
+                    message_time = frame_start_time;
+                }
+            }
+        }
+
+        // Correr los ticks que caben en el tiempo transcurrido (con tope, por si la ventana se congela)
+        accumulator += frame_start_time.duration_since(last_frame).as_secs_f32().min(0.25);
+        last_frame = frame_start_time;
+        input.read(window);
+        let mut moved = false;
+        while accumulator >= TICK {
+            accumulator -= TICK;
+            let tick_input = match &tape {
+                Some(tape) => match tape.get(state.ticks as usize) {
+                    Some(&recorded) => recorded,
+                    None => return LevelEnd::Quit,
+                },
+                None => input,
+            };
+            input.clear_actions();
+            if let Some(replay) = &mut recording {
+                replay.push(tick_input);
+            }
 
-            framebuffer.iter_mut().for_each(|pixel| *pixel = 0);
-            let mut z_buffer: Vec<f32> = vec![std::f32::MAX; WIDTH];
+            let tick = state.tick(&tick_input);
+            moved |= tick.moved;
+            if let Some(race) = &mut race {
+                race.update(state.world.floor, &state.player.pos, state.ticks);
+            }
+            if let Some(text) = tick.message {
+                message = text;
+                message_time = frame_start_time;
+            }
+            // Un panel hacia otro nivel termina este; la repetición solo llega hasta aquí
+            if let Some(to) = tick.warp {
+                if tape.is_some() {
+                    return LevelEnd::Quit;
+                }
+                match state.enter_level(campaign, &to) {
+                    Ok(index) => {
+                        if let Some(replay) = recording {
+                            if let Err(e) = replay.save(LAST_REPLAY) {
+                                eprintln!("No se pudo guardar la repetición: {}", e);
+                            }
+                        }
+                        return LevelEnd::Load(index, Box::new(state));
+                    }
+                    Err(e) => {
+                        message = e;
+                        message_time = frame_start_time;
+                    }
+                }
+            }
+            if tick.finished {
+                sounds.music.pause();
+                sounds.steps.pause();
+                if let Some(mut replay) = recording {
+                    replay.time = Some(state.stats.time);
+                    if let Err(e) = replay.save(LAST_REPLAY).and_then(|_| replay.save_if_best()) {
+                        eprintln!("No se pudo guardar la repetición: {}", e);
+                    }
+                }
+                println!("¡Meta alcanzada!");
+                return LevelEnd::Completed(state.stats);
+            }
+        }
 
-            if mode == "2D" {
-                render2d(&mut framebuffer, &maze, block_size, &player);
-            } else {
-                render3d(&mut framebuffer, &maze, &player, block_size as f32, &mut z_buffer, &enemy_positions);
-            }
-
-            // Calcular FPS
-            frame_count += 1;
-            let current_time = Instant::now();
-            let elapsed = current_time.duration_since(last_time);
-
-            if elapsed >= std::time::Duration::from_secs(1) {
-                let fps = frame_count as f64 / elapsed.as_secs_f64();
-                fps_text = format!("FPS: {:.0}", fps);
-                last_time = current_time;
-                frame_count = 0;
-            }
-
-            // Dibujar FPS
-            let scale = Scale::uniform(24.0);
-            let text_width = 200;
-            let fps_x = WIDTH.saturating_sub(text_width) - 10;
-            let fps_y = 10;
-            render_text(&mut framebuffer, &fps_text, fps_x, fps_y, scale, 0x000000);
-
-            // Verificar si el jugador ha alcanzado la meta
-            for goal_position in &goal_area {
-                if (player.pos - *goal_position).norm() < 0.5 {
-                    background_music.pause();
-                    steps_sound.pause();
-                    game_completed = true;
-                    println!("¡Meta alcanzada!");
-                    break;
+        // Reproducir o pausar el sonido de los pasos dependiendo si el jugador se mueve o no
+        if moved {
+            sounds.steps.play();
+        } else {
+            sounds.steps.pause();
+        }
+
+        framebuffer.iter_mut().for_each(|pixel| *pixel = 0);
+
+        // El entrenador solo se ve si está en el mismo piso
+        let trainer = race.as_ref()
+            .map(|race| race.trainer_pos(state.ticks))
+            .filter(|&(floor, _)| floor == state.world.floor)
+            .map(|(_, pos)| pos);
+        if view == "2D" {
+            render2d(framebuffer, state.world.current(), block_size, &state.player);
+        } else {
+            render3d(framebuffer, &mut view3d, &state.world, &state.player, trainer);
+        }
+        fade_to_black(framebuffer, state.fade());
+
+        // Calcular FPS
+        frame_count += 1;
+        let current_time = Instant::now();
+        let elapsed = current_time.duration_since(last_time);
+
+        if elapsed >= std::time::Duration::from_secs(1) {
+            let fps = frame_count as f64 / elapsed.as_secs_f64();
+            fps_text = format!("FPS: {:.0}", fps);
+            last_time = current_time;
+            frame_count = 0;
+        }
+
+        // Dibujar FPS, alineado a la derecha según su ancho
+        let fps_style = TextStyle::new(24.0, 0x000000);
+        let fps_x = WIDTH.saturating_sub(fps_style.measure(&fps_text).0 as usize + 10);
+        render_text(framebuffer, &fps_text, fps_x, 10, fps_style);
+
+        // Dibujar el tiempo, los pasos, el inventario y el último mensaje
+        let mut stats_text = format!("{}  Pasos: {}", scores::format_time(state.stats.time), state.stats.steps());
+        if state.world.floors.len() > 1 {
+            stats_text += &format!("  Piso {}", state.world.floor + 1);
+        }
+        render_text(framebuffer, &stats_text, WIDTH - 220, 50, TextStyle::new(12.0, 0xFFFFFF));
+        render_inventory_bar(framebuffer, &state.player.inventory);
+        if frame_start_time.duration_since(message_time) < Duration::from_secs(2) {
+            let style = TextStyle::new(16.0, 0xFFD700).centered().shadow(0x000000).wrap((WIDTH - 80) as f32);
+            render_text(framebuffer, &message, WIDTH / 2, HEIGHT - 100, style);
+        }
+        if tape.is_some() {
+            render_text(framebuffer, "REPETICIÓN", 20, 20, TextStyle::new(20.0, 0xFF5050).shadow(0x000000));
+        }
+
+        // Mejor tiempo y diferencia con el entrenador en la última celda que ambos pisaron
+        if let Some(race) = &race {
+            let best_text = format!("Mejor: {}", scores::format_time(race.best_time));
+            render_text(framebuffer, &best_text, WIDTH - 220, 92, TextStyle::new(12.0, 0x80C0FF));
+            if let Some(split) = race.split {
+                let color = if split <= 0.0 { 0x30FF30 } else { 0xFF5050 };
+                render_text(framebuffer, &format!("{:+.1} s", split), WIDTH - 220, 110, TextStyle::new(16.0, color));
+            }
+        }
+
+        window.update_with_buffer(framebuffer, WIDTH, HEIGHT).unwrap();
+
+        if window.is_key_down(Key::M) {
+            view = if view == "2D" { "3D" } else { "2D" };
+        }
+
+        let frame_end_time = Instant::now();
+        let frame_duration_actual = frame_end_time.duration_since(frame_start_time);
+        if frame_duration_actual < std::time::Duration::from_millis(16) {
+            let sleep_duration = std::time::Duration::from_millis(16) - frame_duration_actual;
+            if sleep_duration > std::time::Duration::from_millis(0) {
+                std::thread::sleep(sleep_duration);
+            }
+        }
+    }
+
+    // Al salir a mitad del nivel se guarda la partida para continuarla después,
+    // y lo grabado hasta ahora, para poder reproducir lo que pasó
+    if tape.is_none() {
+        if let Err(e) = SaveGame::capture(level, &state).save(AUTO_SAVE) {
+            eprintln!("No se pudo guardar la partida: {}", e);
+        }
+    }
+    if let Some(replay) = recording {
+        if let Err(e) = replay.save(LAST_REPLAY) {
+            eprintln!("No se pudo guardar la repetición: {}", e);
+        }
+    }
+    LevelEnd::Quit
+}
+
+const EDITOR_HEIGHT: usize = HEIGHT - 100; // Lo que queda abajo es para la barra de estado
+
+// Editor de niveles sobre la vista 2D: el mouse pinta las celdas y TAB alterna una vista
+// previa en 3D que se puede recorrer. Solo se guarda un laberinto que se puede jugar
+fn edit_level(window: &mut Window, framebuffer: &mut [u32], path: &str, theme: Option<String>) {
+    let mut editor = match Editor::open(path, theme) {
+        Ok(editor) => editor,
+        Err(e) => return show_error(window, framebuffer, path, &e.to_string()),
+    };
+    let mut message = format!("Editando {}", path);
+    let mut message_time = Instant::now();
+    let mut preview: Option<GameState> = None;
+    let mut input = Input::default();
+    let mut confirm_exit = false;
+    let mut view3d = View3d::new();
+
+    while window.is_open() {
+        let now = Instant::now();
+        let ctrl = window.is_key_down(Key::LeftCtrl) || window.is_key_down(Key::RightCtrl);
+
+        // ESC sale de la vista previa, y del editor si no hay cambios sin guardar
+        if window.is_key_pressed(Key::Escape, KeyRepeat::No) && preview.take().is_none() {
+            if !editor.dirty || confirm_exit {
+                // Consumir la tecla para que la pantalla de inicio no la vea también
+                window.update();
+                return;
+            }
+            confirm_exit = true;
+            message = "Hay cambios sin guardar: ESC otra vez para salir".to_string();
+            message_time = now;
+        }
+        if window.is_key_pressed(Key::Tab, KeyRepeat::No) {
+            preview = match preview {
+                Some(_) => None,
+                None => match editor.world() {
+                    Ok(world) => Some(GameState::from_world(world)),
+                    Err(e) => {
+                        message = e.to_string();
+                        message_time = now;
+                        None
+                    }
+                },
+            };
+        }
+
+        framebuffer.iter_mut().for_each(|pixel| *pixel = 0);
+
+        if let Some(state) = &mut preview {
+            // Un tick por cuadro: la vista previa no se graba, así que no necesita pasos fijos
+            input.read(window);
+            let tick = state.tick(&input);
+            input.clear_actions();
+            if let Some(text) = tick.message {
+                message = text;
+                message_time = now;
+            }
+
+            if let Some(to) = tick.warp {
+                message = format!("El panel lleva al nivel {}", to.level.unwrap_or_default());
+                message_time = now;
+            }
+
+            render3d(framebuffer, &mut view3d, &state.world, &state.player, None);
+            fade_to_black(framebuffer, state.fade());
+            render_text(framebuffer, "VISTA PREVIA (TAB para volver)", 20, HEIGHT - 40, TextStyle::new(16.0, 0xFFD700).shadow(0x000000));
+            if tick.finished {
+                preview = None;
+                message = "¡Meta alcanzada!".to_string();
+                message_time = now;
+            }
+        } else {
+            if window.is_key_pressed(Key::Right, KeyRepeat::Yes) {
+                editor.next_brush(1);
+            }
+            if window.is_key_pressed(Key::Left, KeyRepeat::Yes) {
+                editor.next_brush(-1);
+            }
+            if ctrl && window.is_key_pressed(Key::Z, KeyRepeat::Yes) && !editor.undo() {
+                message = "No hay nada que deshacer".to_string();
+                message_time = now;
+            }
+            if ctrl && window.is_key_pressed(Key::Y, KeyRepeat::Yes) && !editor.redo() {
+                message = "No hay nada que rehacer".to_string();
+                message_time = now;
+            }
+            // Pisos: AvPág y RePág para moverse, Ctrl+N agrega uno arriba y Ctrl+Supr quita el actual
+            if window.is_key_pressed(Key::PageUp, KeyRepeat::No) && !editor.change_floor(1) {
+                message = "Es el último piso (Ctrl+N agrega otro)".to_string();
+                message_time = now;
+            }
+            if window.is_key_pressed(Key::PageDown, KeyRepeat::No) && !editor.change_floor(-1) {
+                message = "Es la planta baja".to_string();
+                message_time = now;
+            }
+            if ctrl && window.is_key_pressed(Key::N, KeyRepeat::No) {
+                editor.add_floor();
+                message = format!("Piso {} agregado", editor.floor + 1);
+                message_time = now;
+            }
+            if ctrl && window.is_key_pressed(Key::Delete, KeyRepeat::No) {
+                message = if editor.remove_floor() { "Piso quitado" } else { "El nivel necesita al menos un piso" }.to_string();
+                message_time = now;
+            }
+            if ctrl && window.is_key_pressed(Key::S, KeyRepeat::No) {
+                message = match editor.save() {
+                    Ok(output) => format!("Guardado en {}", output),
+                    Err(e) => format!("No se pudo guardar: {}", e),
+                };
+                message_time = now;
+                confirm_exit = false;
+            }
+
+            // Clic izquierdo pinta con el pincel y el derecho borra
+            let rows = editor.grid().len();
+            let cols = editor.grid().first().map_or(1, |row| row.len());
+            let block_size = (WIDTH / cols).min(EDITOR_HEIGHT / rows).max(1);
+            let hovered = window
+                .get_mouse_pos(MouseMode::Discard)
+                .map(|(x, y)| (x as usize / block_size, y as usize / block_size))
+                .filter(|&(x, y)| x < cols && y < rows);
+            match (hovered, window.get_mouse_down(MouseButton::Left), window.get_mouse_down(MouseButton::Right)) {
+                (Some((x, y)), true, _) => editor.paint(x, y, editor.brush()),
+                (Some((x, y)), false, true) => editor.paint(x, y, ' '),
+                _ => editor.end_stroke(),
+            }
+
+            for (row, line) in editor.grid().iter().enumerate() {
+                for (col, &cell) in line.iter().enumerate() {
+                    draw_cell(framebuffer, col * block_size, row * block_size, block_size, cell, row, col);
+                }
+            }
+            draw_segments(framebuffer, editor.walls(), 0, 0, block_size as f32, 0xFFFFFF);
+            if let Some((x, y)) = hovered {
+                draw_outline(framebuffer, x * block_size, y * block_size, block_size, 0xFFFFFF);
+            }
+
+            // Barra de estado: pincel, validación y atajos
+            let bar_y = EDITOR_HEIGHT + 10;
+            draw_cell(framebuffer, 20, bar_y, 20, editor.brush(), 0, 0);
+            draw_outline(framebuffer, 20, bar_y, 20, 0xFFFFFF);
+            let title = format!("{}{}  Piso {}/{}", editor.path, if editor.dirty { " *" } else { "" }, editor.floor + 1, editor.floor_count());
+            render_text(framebuffer, &format!("Pincel: {}", editor::brush_name(editor.brush())), 50, bar_y + 4, TextStyle::new(14.0, 0xFFFFFF));
+            render_text(framebuffer, &title, WIDTH / 2, bar_y + 4, TextStyle::new(14.0, 0xAAAAAA));
+            match editor.validate() {
+                Ok(()) => render_text(framebuffer, "Laberinto válido", 20, bar_y + 32, TextStyle::new(12.0, 0x30FF30)),
+                Err(e) => render_text(framebuffer, &e.to_string(), 20, bar_y + 32, TextStyle::new(12.0, 0xFF5050)),
+            }
+            let help = "Flechas: pincel  Clic der.: borrar  RePág/AvPág: piso  Ctrl+N/Supr  Ctrl+Z/Y  Ctrl+S  TAB: 3D";
+            render_text(framebuffer, help, 20, bar_y + 56, TextStyle::new(11.0, 0x777777));
+        }
+
+        if now.duration_since(message_time) < Duration::from_secs(2) {
+            render_text(framebuffer, &message, WIDTH / 2, EDITOR_HEIGHT - 30, TextStyle::new(16.0, 0xFFD700).centered().shadow(0x000000));
+        }
+
+        window.update_with_buffer(framebuffer, WIDTH, HEIGHT).unwrap();
+        std::thread::sleep(Duration::from_millis(16));
+    }
+}
+
+// Dibuja las paredes delgadas como líneas de dos píxeles, con `scale` píxeles por celda
+fn draw_segments(framebuffer: &mut [u32], walls: &[Segment], xo: usize, yo: usize, scale: f32, color: u32) {
+    for wall in walls {
+        let points = (wall.length() * scale).ceil() as usize + 1;
+        for i in 0..=points {
+            let point = wall.start() + (wall.end() - wall.start()) * (i as f32 / points as f32);
+            let (px, py) = (xo + (point.x * scale) as usize, yo + (point.y * scale) as usize);
+            for (x, y) in [(px, py), (px + 1, py), (px, py + 1), (px + 1, py + 1)] {
+                if x < WIDTH && y < HEIGHT {
+                    framebuffer[y * WIDTH + x] = color;
                 }
             }
+        }
+    }
+}
+
+fn draw_outline(framebuffer: &mut [u32], xo: usize, yo: usize, size: usize, color: u32) {
+    for i in 0..size {
+        for (x, y) in [(xo + i, yo), (xo + i, yo + size - 1), (xo, yo + i), (xo + size - 1, yo + i)] {
+            if x < WIDTH && y < HEIGHT {
+                framebuffer[y * WIDTH + x] = color;
+            }
+        }
+    }
+}
+
+// Muestra por qué no se pudo cargar un nivel y espera a que el jugador lo lea
+fn show_error(window: &mut Window, framebuffer: &mut [u32], level_name: &str, error: &str) {
+    framebuffer.iter_mut().for_each(|pixel| *pixel = 0);
+    render_text(framebuffer, &format!("No se pudo cargar {}", level_name), 40, 60, TextStyle::new(30.0, 0xFFD700));
+    render_text(framebuffer, error, 40, 140, TextStyle::new(16.0, 0xFF5050).wrap((WIDTH - 80) as f32));
+    render_text(framebuffer, "Presiona 'enter' para continuar", 40, HEIGHT - 60, TextStyle::new(16.0, 0xFFD700));
+    window.update_with_buffer(framebuffer, WIDTH, HEIGHT).unwrap();
+
+    while window.is_open() && !window.is_key_pressed(Key::Enter, KeyRepeat::No) && !window.is_key_pressed(Key::Escape, KeyRepeat::No) {
+        window.update();
+        std::thread::sleep(Duration::from_millis(16));
+    }
+}
+
+// Muestra la pantalla de éxito. Devuelve true si el jugador quiere continuar
+fn show_success(window: &mut Window, framebuffer: &mut [u32], stats: &RunStats, top: &[ScoreEntry], position: Option<usize>, has_next: bool) -> bool {
+    render_success_screen(framebuffer, stats, top, position, has_next);
+    window.update_with_buffer(framebuffer, WIDTH, HEIGHT).unwrap();
+
+    // Esperar a que el jugador presione 'enter' para continuar o 'ESC' para salir
+    while window.is_open() && !window.is_key_down(Key::Escape) {
+        if window.is_key_pressed(Key::Enter, KeyRepeat::No) {
+            return true;
+        }
+        window.update();
+        std::thread::sleep(Duration::from_millis(16));
+    }
+    false
+}
+
+// Reproduce una grabación sin ventana y compara el resultado con el grabado
+fn verify_replay(campaign: &Campaign, replay: &Replay) -> Result<(), String> {
+    let (state, finished) = replay.simulate(campaign)?;
+    let result = if finished { Some(state.stats.time) } else { None };
+
+    println!("Nivel: {}", replay.level);
+    println!("Ticks: {}  Tiempo: {}  Pasos: {}", state.ticks, scores::format_time(state.stats.time), state.stats.steps());
+    println!("Posición final: ({:.3}, {:.3})", state.player.pos.x, state.player.pos.y);
+
+    match (result, replay.time) {
+        (Some(time), Some(recorded)) if time == recorded => {
+            println!("Meta alcanzada en {}: coincide con la grabación", scores::format_time(time));
+            Ok(())
+        }
+        (None, None) => {
+            println!("La grabación termina sin llegar a la meta, igual que al grabarla");
+            Ok(())
+        }
+        (result, recorded) => Err(format!(
+            "el resultado no coincide con la grabación (obtenido {:?}, grabado {:?})",
+            result, recorded
+        )),
+    }
+}
+
+// Mide cuánto tarda render3d en cada nivel de la campaña, con un solo hilo y con todos, girando
+// al jugador en su lugar. También revisa que las dos versiones dibujen exactamente lo mismo
+fn bench_render(campaign: &Campaign, frames: usize) -> Result<(), String> {
+    let threads = rayon::current_num_threads();
+    let single = rayon::ThreadPoolBuilder::new().num_threads(1).build().map_err(|e| e.to_string())?;
+    println!("{} cuadros de {}x{} por nivel, 1 hilo contra {}", frames, WIDTH, HEIGHT, threads);
+
+    for level in &campaign.levels {
+        let mut state = GameState::new(level).map_err(|e| format!("{}: {}", level.id, e))?;
+        let start_angle = state.player.a;
+        let mut view3d = View3d::new();
+        let mut render = |framebuffer: &mut [u32]| {
+            let started = Instant::now();
+            for frame in 0..frames {
+                state.player.a = start_angle + frame as f32 * std::f32::consts::TAU / frames as f32;
+                render3d(framebuffer, &mut view3d, &state.world, &state.player, None);
+            }
+            started.elapsed().as_secs_f64() * 1000.0 / frames as f64
+        };
+
+        let mut sequential = vec![0; WIDTH * HEIGHT];
+        let mut parallel = vec![0; WIDTH * HEIGHT];
+        let single_ms = single.install(|| render(&mut sequential));
+        let parallel_ms = render(&mut parallel);
+        if sequential != parallel {
+            return Err(format!("{}: la imagen cambia según el número de hilos", level.id));
+        }
+        println!(
+            "{:<12} 1 hilo: {:>6.2} ms/cuadro  {} hilos: {:>6.2} ms/cuadro  ({:.2}x)",
+            level.id, single_ms, threads, parallel_ms, single_ms / parallel_ms
+        );
+    }
+    Ok(())
+}
+
+// Milisegundos por pasada leyendo un píxel de textura por cada píxel de la pantalla
+fn time_reads(passes: usize, read: impl Fn(f32, f32) -> u32) -> f64 {
+    let started = Instant::now();
+    let mut checksum = 0u32;
+    for _ in 0..passes {
+        for x in 0..WIDTH {
+            let u = x as f32 / WIDTH as f32;
+            for y in 0..HEIGHT {
+                checksum = checksum.wrapping_add(read(u, y as f32 / HEIGHT as f32));
+            }
+        }
+    }
+    std::hint::black_box(checksum);
+    started.elapsed().as_secs_f64() * 1000.0 / passes as f64
+}
+
+// Compara la lectura de las texturas con la forma anterior de guardarlas (un Vec por columna
+// y dos módulos por píxel), recorriendo la imagen por columnas como al dibujar las paredes
+fn bench_textures(paths: &[&str], passes: usize) -> Result<(), String> {
+    println!("Lectura de texturas, {} pasadas de {}x{} píxeles", passes, WIDTH, HEIGHT);
+    for path in paths {
+        let texture = Texture::load(path).map_err(|e| format!("{}: {}", path, e))?;
+        let nested: Vec<Vec<u32>> = (0..texture.width)
+            .map(|x| (0..texture.height).map(|y| texture.get_pixel_color(x, y)).collect())
+            .collect();
+        let (width, height) = (texture.width as f32, texture.height as f32);
+
+        let before = time_reads(passes, |u, v| {
+            let (x, y) = ((u * width) as usize, (v * height) as usize);
+            nested[x % texture.width as usize][y % texture.height as usize]
+        });
+        let (full, far) = (texture.mip(1.0), texture.mip(8.0));
+        let nearest = time_reads(passes, |u, v| full.column(u).pixel(v).0);
+        let mipmap = time_reads(passes, |u, v| far.column(u).pixel(v).0);
+        let bilinear = time_reads(passes, |u, v| full.sample(u, v, Filter::Bilinear).0);
+        println!(
+            "{:<28} antes: {:>6.2} ms  ahora: {:>6.2} ms ({:.2}x)  mipmap: {:>6.2} ms  bilineal: {:>6.2} ms",
+            format!("{} ({}x{})", path, texture.width, texture.height), before, nearest, before / nearest, mipmap, bilinear
+        );
+    }
+    Ok(())
+}
+
+fn main() {
+    // --replay archivo [--headless]: reproducir una grabación en la ventana o solo simularla
+    // --convert entrada salida: convertir un nivel entre los formatos .txt y .json
+    // --edit archivo: editar un nivel (o crearlo)
+    // --bench [cuadros]: medir la vista 3D sin ventana, con uno y con varios hilos, y las texturas
+    let args: Vec<String> = std::env::args().collect();
+    if let Some(i) = args.iter().position(|arg| arg == "--bench") {
+        let frames = args.get(i + 1).and_then(|frames| frames.parse().ok()).unwrap_or(120);
+        let textures = ["sprites/casaSprite.png", "sprites/rejaSprite.png"];
+        if let Err(e) = bench_render(&Campaign::load(CAMPAIGN_FILE), frames).and_then(|_| bench_textures(&textures, frames / 10 + 1)) {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+    if let Some(i) = args.iter().position(|arg| arg == "--convert") {
+        let (Some(input), Some(output)) = (args.get(i + 1), args.get(i + 2)) else {
+            eprintln!("Uso: --convert entrada salida");
+            std::process::exit(1);
+        };
+        if let Err(e) = level_map::convert(input, output) {
+            eprintln!("No se pudo convertir {}: {}", input, e);
+            std::process::exit(1);
+        }
+        println!("{} -> {}", input, output);
+        return;
+    }
+
+    let replay_file = args.iter().position(|arg| arg == "--replay").and_then(|i| args.get(i + 1));
+    let edit_file = args.iter().position(|arg| arg == "--edit").and_then(|i| args.get(i + 1));
+    let headless = args.iter().any(|arg| arg == "--headless");
 
-            window.update_with_buffer(&framebuffer, WIDTH, HEIGHT).unwrap();
+    let campaign = Campaign::load(CAMPAIGN_FILE);
+    let replay = match replay_file.map(|path| Replay::load(path)) {
+        Some(Ok(replay)) => Some(replay),
+        Some(Err(e)) => {
+            eprintln!("No se pudo leer la repetición: {}", e);
+            std::process::exit(1);
+        }
+        None => None,
+    };
+
+    if let (Some(replay), true) = (&replay, headless) {
+        if let Err(e) = verify_replay(&campaign, replay) {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
 
-            if window.is_key_down(Key::M) {
-                mode = if mode == "2D" { "3D" } else { "2D" };
+    let mut window = Window::new(
+        "Maze",
+        WIDTH,
+        HEIGHT,
+        WindowOptions::default(),
+    )
+    .unwrap_or_else(|e| {
+        panic!("{}", e);
+    });
+
+    let mut framebuffer: Vec<u32> = vec![0; WIDTH * HEIGHT];
+
+    // --edit archivo: abrir el editor directamente; si el archivo no existe, se crea al guardar
+    if let Some(path) = edit_file {
+        return edit_level(&mut window, &mut framebuffer, path, None);
+    }
+
+    // Crear el reproductor de música de fondo y el de efectos de sonido para los pasos
+    let sounds = Sounds {
+        music: AudioPlayer::new("assets/Musica de Pueblo Lavanda.mp3").expect("Failed to initialize background music"),
+        steps: AudioPlayer::new("assets/Efecto de Pasos.mp3").expect("Failed to initialize steps sound"),
+    };
+
+    sounds.music.set_volume(0.2);
+
+    // Ver una repetición en la ventana, sin tocar el progreso ni los récords
+    if let Some(replay) = &replay {
+        let index = match replay.level_index(&campaign) {
+            Ok(index) => index,
+            Err(e) => {
+                eprintln!("No se pudo reproducir: {}", e);
+                std::process::exit(1);
             }
+        };
+        let state = match GameState::new(&campaign.levels[index]) {
+            Ok(state) => state,
+            Err(e) => return show_error(&mut window, &mut framebuffer, &campaign.levels[index].name, &e.to_string()),
+        };
+        if let LevelEnd::Completed(stats) = play_level(&mut window, &mut framebuffer, &campaign, index, state, &sounds, Mode::Playback(replay)) {
+            show_success(&mut window, &mut framebuffer, &stats, &[], None, false);
+        }
+        return;
+    }
 
-            let frame_end_time = Instant::now();
-            let frame_duration_actual = frame_end_time.duration_since(frame_start_time);
-            if frame_duration_actual < std::time::Duration::from_millis(16) {
-                let sleep_duration = std::time::Duration::from_millis(16) - frame_duration_actual;
-                if sleep_duration > std::time::Duration::from_millis(0) {
-                    std::thread::sleep(sleep_duration);
+    // El progreso y los récords se guardan entre partidas
+    let mut progress = Progress::load(PROGRESS_FILE);
+    let mut high_scores = HighScores::load(SCORES_FILE);
+
+    // Empezar en el primer nivel que falta por completar
+    let mut title_selected = (0..campaign.levels.len())
+        .find(|&i| !progress.is_completed(&campaign.levels[i].id))
+        .unwrap_or(0);
+
+    let mut race = false;
+    while let Some((mut selected, mut state)) = select_level(&mut window, &mut framebuffer, &campaign, &progress, &high_scores, title_selected, &mut race) {
+        let mode = if race { Mode::Race } else { Mode::Normal };
+        // Jugar los niveles en orden hasta terminar la campaña o volver al inicio
+        loop {
+            let stats = match play_level(&mut window, &mut framebuffer, &campaign, selected, state, &sounds, mode) {
+                LevelEnd::Completed(stats) => stats,
+                LevelEnd::Load(index, loaded) => {
+                    selected = index;
+                    state = *loaded;
+                    continue;
                 }
+                LevelEnd::Quit => return,
+            };
+
+            let level = &campaign.levels[selected];
+            progress.complete(&level.id);
+            if let Err(e) = progress.save(PROGRESS_FILE) {
+                eprintln!("No se pudo guardar el progreso: {}", e);
             }
-        } else {
-            // Si el juego se ha completado, renderizar la pantalla de éxito
-            println!("Renderizando pantalla de éxito...");
-            render_success_screen(&mut framebuffer);
-            window.update_with_buffer(&framebuffer, WIDTH, HEIGHT).unwrap();
-            println!("Esperando que el jugador presione 'ESC'...");
-
-            // Esperar a que el jugador presione 'ESC' para salir
-            while window.is_open() && !window.is_key_down(Key::Escape) {
-                window.update();
-                std::thread::sleep(Duration::from_millis(16));
+
+            // Guardar el resultado en la tabla de récords
+            let key = level.key();
+            let position = high_scores.record(&key, ScoreEntry::from_run(&stats));
+            if let Err(e) = high_scores.save(SCORES_FILE) {
+                eprintln!("No se pudieron guardar los récords: {}", e);
             }
-            break;  // Salir del bucle principal después de mostrar la pantalla de éxito
+
+            let has_next = selected + 1 < campaign.levels.len();
+            if !show_success(&mut window, &mut framebuffer, &stats, high_scores.top(&key), position, has_next) {
+                return;
+            }
+            title_selected = selected;
+            if !has_next {
+                break;
+            }
+            selected += 1;
+            let next = &campaign.levels[selected];
+            state = match GameState::new(next) {
+                Ok(state) => state,
+                Err(e) => {
+                    show_error(&mut window, &mut framebuffer, &next.name, &e.to_string());
+                    break;
+                }
+            };
         }
     }
 }
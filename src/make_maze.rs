@@ -1,61 +1,72 @@
 use std::fs::File;
 use std::io::{self, Write};
+use rand::rngs::StdRng;
 use rand::seq::SliceRandom;
-use rand::Rng;
+use rand::{Rng, SeedableRng};
+use std::str::FromStr;
+use crate::analysis::{self, Difficulty};
+use crate::maze::{GOAL, SPAWN, STAIRS_DOWN, STAIRS_UP};
+use crate::segments::Segment;
 
-pub fn make_maze(w: usize, h: usize) -> Vec<Vec<char>> {
-    let mut vis = vec![vec![0; w]; h];
-    let mut ver = vec![vec!['|'; w]; h];
-    let mut hor = vec![vec!['+'; w + 1]; h + 1];
+// Forma de recorrer la cuadrícula al derribar paredes. Todas dan laberintos perfectos
+// (un solo camino entre dos celdas), pero con distinto carácter
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Algorithm {
+    Backtracker, // Pasillos largos y sinuosos
+    Prim,        // Muchas ramas cortas
+    Kruskal,     // Ramas repartidas de forma pareja
+}
 
-    for y in 0..h {
-        ver[y].push('|');
-    }
+impl FromStr for Algorithm {
+    type Err = String;
 
-    for x in 0..=w {
-        hor[h].push('+');
+    fn from_str(name: &str) -> Result<Algorithm, String> {
+        match name {
+            "backtracker" => Ok(Algorithm::Backtracker),
+            "prim" => Ok(Algorithm::Prim),
+            "kruskal" => Ok(Algorithm::Kruskal),
+            _ => Err(format!("algoritmo desconocido '{}' (backtracker, prim o kruskal)", name)),
+        }
     }
+}
+
+type Passage = ((usize, usize), (usize, usize));
 
-    let mut rng = rand::thread_rng();
-    let mut d = vec![(0, 0); 4];
+// Genera un laberinto perfecto de `w` x `h` celdas con el formato de maze.txt:
+// cada celda ocupa tres caracteres ("+--" arriba, "|  " a la izquierda).
+// La misma semilla produce siempre el mismo laberinto.
+pub fn make_maze(w: usize, h: usize, seed: u64) -> Vec<Vec<char>> {
+    make_maze_with(Algorithm::Backtracker, w, h, seed)
+}
 
-    fn walk(x: usize, y: usize, vis: &mut Vec<Vec<u8>>, hor: &mut Vec<Vec<char>>, ver: &mut Vec<Vec<char>>, rng: &mut impl Rng, d: &mut Vec<(usize, usize)>) {
-        vis[y][x] = 1;
+pub fn make_maze_with(algorithm: Algorithm, w: usize, h: usize, seed: u64) -> Vec<Vec<char>> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut ver = vec![vec!["|  "; w]; h];
+    let mut hor = vec![vec!["+--"; w]; h + 1];
 
-        d[0] = (x.wrapping_sub(1), y);
-        d[1] = (x, y + 1);
-        d[2] = (x + 1, y);
-        d[3] = (x, y.wrapping_sub(1));
-        d.shuffle(rng);
+    let passages = match algorithm {
+        Algorithm::Backtracker => backtracker(w, h, &mut rng),
+        Algorithm::Prim => prim(w, h, &mut rng),
+        Algorithm::Kruskal => kruskal(w, h, &mut rng),
+    };
 
-        for i in 0..d.len() {
-            let (xx, yy) = d[i];
-            if yy < vis.len() && xx < vis[0].len() && vis[yy][xx] != 0 { continue; }
-            if xx == x && yy < hor.len() {
-                hor[std::cmp::max(y, yy)][x] = ' ';
-            }
-            if yy == y && xx < ver[y].len() {
-                ver[y][std::cmp::max(x, xx)] = ' ';
-            }
-            if yy < vis.len() && xx < vis[0].len() {
-                walk(xx, yy, vis, hor, ver, rng, d);
-            }
+    // Derribar la pared entre cada par de celdas unidas
+    for ((x, y), (xx, yy)) in passages {
+        if xx == x {
+            hor[std::cmp::max(y, yy)][x] = "+  ";
+        } else {
+            ver[y][std::cmp::max(x, xx)] = "   ";
         }
     }
 
-    walk(rng.gen_range(0..w), rng.gen_range(0..h), &mut vis, &mut hor, &mut ver, &mut rng, &mut d);
-
     // Convertimos las líneas a un array 2D de caracteres
     let mut maze = Vec::new();
 
     for (a, b) in hor.iter().zip(ver.iter()) {
-        let mut line_a = a.clone();
-        let mut line_b = b.clone();
-        line_a.push('\n');
-        line_b.push('\n');
-        maze.push(line_a);
-        maze.push(line_b);
+        maze.push(format!("{}+", a.concat()).chars().collect());
+        maze.push(format!("{}|", b.concat()).chars().collect());
     }
+    maze.push(format!("{}+", hor[h].concat()).chars().collect::<Vec<char>>());
 
     // Colocar 'p' y 'g' en las posiciones adecuadas
     let last_row_index = maze.len() - 2;
@@ -66,14 +77,327 @@ pub fn make_maze(w: usize, h: usize) -> Vec<Vec<char>> {
     maze
 }
 
-pub fn save_maze_to_file(filename: &str, maze: &Vec<Vec<char>>) -> io::Result<()> {
+// Prueba semillas consecutivas desde `seed` hasta dar con un laberinto de la dificultad pedida;
+// si ninguna cae en el rango, se queda con la más cercana. Devuelve la semilla usada y si el
+// laberinto quedó dentro del rango, para que quien lo pidió decida si avisar
+pub fn make_maze_for(difficulty: Difficulty, algorithm: Algorithm, w: usize, h: usize, seed: u64) -> (u64, Vec<Vec<char>>, bool) {
+    const ATTEMPTS: u64 = 200;
+    let mut closest: Option<(f32, u64, Vec<Vec<char>>)> = None;
+
+    for attempt in 0..ATTEMPTS {
+        let candidate = seed.wrapping_add(attempt);
+        let maze = make_maze_with(algorithm, w, h, candidate);
+        let miss = difficulty.miss(analysis::stats(&maze).difficulty().unwrap_or(0.0));
+        if miss == 0.0 {
+            return (candidate, maze, true);
+        }
+        if closest.as_ref().is_none_or(|(closest_miss, _, _)| miss < *closest_miss) {
+            closest = Some((miss, candidate, maze));
+        }
+    }
+
+    let (_, seed, maze) = closest.unwrap();
+    (seed, maze, false)
+}
+
+// Distancia entre las semillas de dos pisos, para que la búsqueda por dificultad no las repita
+const FLOOR_SEED_STEP: u64 = 1000;
+
+// Genera un nivel de varios pisos del mismo tamaño, de abajo hacia arriba. El inicio está en la
+// planta baja y cada piso tiene su escalera de subida en la habitación más lejana a donde se
+// llega, hasta la meta en el último. Como cada piso es un laberinto perfecto, todo queda conectado.
+// Con una dificultad, se busca para cada piso por separado; también devuelve si todos los pisos
+// quedaron dentro del rango
+pub fn make_floors(algorithm: Algorithm, difficulty: Option<Difficulty>, w: usize, h: usize, floors: usize, seed: u64) -> (Vec<Vec<Vec<char>>>, bool) {
+    let mut levels = Vec::with_capacity(floors);
+    let mut arrival = (1, 1);
+    let mut matched = true;
+
+    for floor in 0..floors {
+        let floor_seed = seed.wrapping_add(floor as u64 * FLOOR_SEED_STEP);
+        let mut maze = match difficulty {
+            Some(difficulty) => {
+                let (_, maze, found) = make_maze_for(difficulty, algorithm, w, h, floor_seed);
+                matched &= found;
+                maze
+            }
+            None => make_maze_with(algorithm, w, h, floor_seed),
+        };
+        for cell in maze.iter_mut().flatten().filter(|cell| **cell == SPAWN || **cell == GOAL) {
+            *cell = ' ';
+        }
+        maze[arrival.1][arrival.0] = if floor == 0 { SPAWN } else { STAIRS_DOWN };
+
+        // La habitación más lejana, por su casilla izquierda (usize::MAX, donde no se llega, da la vuelta a 0)
+        let distance = analysis::distances(&maze, arrival);
+        let farthest = (0..h)
+            .flat_map(|j| (0..w).map(move |i| (3 * i + 1, 2 * j + 1)))
+            .max_by_key(|&(x, y)| distance[y][x].wrapping_add(1))
+            .unwrap_or(arrival);
+
+        if floor + 1 == floors {
+            // La meta va en la casilla derecha, como en make_maze
+            maze[farthest.1][farthest.0 + 1] = GOAL;
+        } else {
+            maze[farthest.1][farthest.0] = STAIRS_UP;
+            arrival = farthest;
+        }
+        levels.push(maze);
+    }
+
+    (levels, matched)
+}
+
+// Convierte un laberinto de make_maze a paredes delgadas: cada celda del laberinto pasa a ser
+// una sola celda del mapa y las paredes entre celdas, segmentos sobre sus bordes. Solo el
+// borde exterior queda como bloques. El inicio, la meta y las escaleras conservan su celda
+pub fn thin_walls(maze: &[Vec<char>]) -> (Vec<Vec<char>>, Vec<Segment>) {
+    let w = (maze[0].len() - 1) / 3;
+    let h = (maze.len() - 1) / 2;
+
+    let mut grid = vec![vec![' '; w + 2]; h + 2];
+    for (y, row) in grid.iter_mut().enumerate() {
+        for (x, cell) in row.iter_mut().enumerate() {
+            let top_or_bottom = y == 0 || y == h + 1;
+            let side = x == 0 || x == w + 1;
+            *cell = match (top_or_bottom, side) {
+                (true, true) => '+',
+                (true, false) => '-',
+                (false, true) => '|',
+                (false, false) => {
+                    // Lo que haya en cualquiera de los dos caracteres de la celda
+                    let (i, j) = (x - 1, y - 1);
+                    let chars = [maze[2 * j + 1][3 * i + 1], maze[2 * j + 1][3 * i + 2]];
+                    chars.into_iter().find(|&c| c != ' ').unwrap_or(' ')
+                }
+            };
+        }
+    }
+
+    // Las paredes seguidas sobre la misma línea se juntan en un solo segmento
+    let mut walls = Vec::new();
+    let mut push_run = |run: &mut Option<(f32, f32)>, line: f32, vertical: bool, wall: char| {
+        if let Some((start, end)) = run.take() {
+            let (from, to) = if vertical { ([line, start], [line, end]) } else { ([start, line], [end, line]) };
+            walls.push(Segment { from, to, wall });
+        }
+    };
+    for i in 1..w {
+        let mut run = None;
+        for j in 0..h {
+            if maze[2 * j + 1][3 * i] == ' ' {
+                push_run(&mut run, (i + 1) as f32, true, '|');
+            } else {
+                let start = run.map_or(j + 1, |(start, _)| start as usize) as f32;
+                run = Some((start, (j + 2) as f32));
+            }
+        }
+        push_run(&mut run, (i + 1) as f32, true, '|');
+    }
+    for j in 1..h {
+        let mut run = None;
+        for i in 0..w {
+            if maze[2 * j][3 * i + 1] == ' ' {
+                push_run(&mut run, (j + 1) as f32, false, '-');
+            } else {
+                let start = run.map_or(i + 1, |(start, _)| start as usize) as f32;
+                run = Some((start, (i + 2) as f32));
+            }
+        }
+        push_run(&mut run, (j + 1) as f32, false, '-');
+    }
+
+    (grid, walls)
+}
+
+pub fn save_maze_to_file(filename: &str, maze: &[Vec<char>]) -> io::Result<()> {
     let mut file = File::create(filename)?;
 
     for line in maze {
-        for &ch in line {
-            write!(file, "{}", ch)?;
-        }
+        let line: String = line.iter().collect();
+        writeln!(file, "{}", line)?;
     }
 
     Ok(())
 }
+
+// Celdas vecinas dentro de la cuadrícula
+fn neighbors(x: usize, y: usize, w: usize, h: usize) -> Vec<(usize, usize)> {
+    let mut d = vec![];
+    if x > 0 { d.push((x - 1, y)); }
+    if y + 1 < h { d.push((x, y + 1)); }
+    if x + 1 < w { d.push((x + 1, y)); }
+    if y > 0 { d.push((x, y - 1)); }
+    d
+}
+
+// Recorrido en profundidad con una pila explícita para no desbordar en laberintos grandes
+fn backtracker(w: usize, h: usize, rng: &mut StdRng) -> Vec<Passage> {
+    let mut vis = vec![vec![false; w]; h];
+    let mut passages = Vec::new();
+
+    let start = (rng.gen_range(0..w), rng.gen_range(0..h));
+    vis[start.1][start.0] = true;
+    let mut stack = vec![start];
+
+    while let Some(&(x, y)) = stack.last() {
+        let mut d = neighbors(x, y, w, h);
+        d.retain(|&(xx, yy)| !vis[yy][xx]);
+
+        let Some(&(xx, yy)) = d.choose(rng) else {
+            stack.pop();
+            continue;
+        };
+
+        passages.push(((x, y), (xx, yy)));
+        vis[yy][xx] = true;
+        stack.push((xx, yy));
+    }
+
+    passages
+}
+
+// Crece desde una celda tomando al azar cualquier pared de la frontera
+fn prim(w: usize, h: usize, rng: &mut StdRng) -> Vec<Passage> {
+    let mut vis = vec![vec![false; w]; h];
+    let mut passages = Vec::new();
+
+    let start = (rng.gen_range(0..w), rng.gen_range(0..h));
+    vis[start.1][start.0] = true;
+    let mut frontier: Vec<Passage> = neighbors(start.0, start.1, w, h).into_iter().map(|next| (start, next)).collect();
+
+    while !frontier.is_empty() {
+        let (from, (x, y)) = frontier.swap_remove(rng.gen_range(0..frontier.len()));
+        if vis[y][x] {
+            continue;
+        }
+        vis[y][x] = true;
+        passages.push((from, (x, y)));
+        frontier.extend(neighbors(x, y, w, h).into_iter().filter(|&(xx, yy)| !vis[yy][xx]).map(|next| ((x, y), next)));
+    }
+
+    passages
+}
+
+// Derriba las paredes en orden aleatorio si separan zonas todavía no conectadas
+fn kruskal(w: usize, h: usize, rng: &mut StdRng) -> Vec<Passage> {
+    let mut walls: Vec<Passage> = Vec::new();
+    for y in 0..h {
+        for x in 0..w {
+            if x + 1 < w { walls.push(((x, y), (x + 1, y))); }
+            if y + 1 < h { walls.push(((x, y), (x, y + 1))); }
+        }
+    }
+    walls.shuffle(rng);
+
+    // Conjuntos disjuntos: cada celda apunta hacia el representante de su zona
+    let mut parent: Vec<usize> = (0..w * h).collect();
+    fn find(parent: &mut [usize], mut i: usize) -> usize {
+        while parent[i] != i {
+            parent[i] = parent[parent[i]];
+            i = parent[i];
+        }
+        i
+    }
+
+    walls
+        .into_iter()
+        .filter(|&((x, y), (xx, yy))| {
+            let (a, b) = (find(&mut parent, y * w + x), find(&mut parent, yy * w + xx));
+            parent[a] = b;
+            a != b
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::Step;
+
+    const ALGORITHMS: [Algorithm; 3] = [Algorithm::Backtracker, Algorithm::Prim, Algorithm::Kruskal];
+
+    // Paredes derribadas entre habitaciones vecinas
+    fn passages(maze: &[Vec<char>], w: usize, h: usize) -> usize {
+        let across = (0..h).flat_map(|j| (1..w).map(move |i| (3 * i, 2 * j + 1)));
+        let down = (1..h).flat_map(|j| (0..w).map(move |i| (3 * i + 1, 2 * j)));
+        across.chain(down).filter(|&(x, y)| maze[y][x] == ' ').count()
+    }
+
+    #[test]
+    fn the_same_seed_gives_the_same_maze() {
+        for algorithm in ALGORITHMS {
+            assert_eq!(make_maze_with(algorithm, 8, 6, 42), make_maze_with(algorithm, 8, 6, 42));
+            assert_ne!(make_maze_with(algorithm, 8, 6, 42), make_maze_with(algorithm, 8, 6, 43));
+        }
+    }
+
+    #[test]
+    fn generates_perfect_mazes() {
+        let (w, h) = (7, 5);
+        for algorithm in ALGORITHMS {
+            let maze = make_maze_with(algorithm, w, h, 7);
+            assert_eq!(maze.len(), 2 * h + 1);
+            assert!(maze.iter().all(|row| row.len() == 3 * w + 1));
+            assert_eq!(maze[1][1], SPAWN);
+            assert_eq!(maze[2 * h - 1][3 * w - 1], GOAL);
+
+            // Conexo y con una pared menos que habitaciones: un árbol
+            let stats = analysis::stats(&maze);
+            assert!(stats.rooms);
+            assert_eq!(stats.open_cells, w * h);
+            assert_eq!(passages(&maze, w, h), w * h - 1);
+            let distance = analysis::distances(&maze, (1, 1));
+            assert!((0..h).all(|j| (0..w).all(|i| distance[2 * j + 1][3 * i + 1] != usize::MAX)));
+            assert!(stats.solution.is_some());
+        }
+    }
+
+    #[test]
+    fn searches_seeds_for_a_difficulty() {
+        let (seed, maze, matched) = make_maze_for(Difficulty::Easy, Algorithm::Backtracker, 6, 6, 1);
+        assert!(matched);
+        assert_eq!(analysis::stats(&maze).difficulty().map(Difficulty::of), Some(Difficulty::Easy));
+        assert_eq!(maze, make_maze_with(Algorithm::Backtracker, 6, 6, seed));
+        assert_eq!(make_maze_for(Difficulty::Easy, Algorithm::Backtracker, 6, 6, 1).0, seed);
+    }
+
+    #[test]
+    fn reports_when_no_seed_has_the_difficulty() {
+        // Con una sola habitación el inicio y la meta están juntos: nunca sale difícil
+        let (_, maze, matched) = make_maze_for(Difficulty::Hard, Algorithm::Prim, 1, 1, 5);
+        assert!(!matched);
+        assert_eq!(maze.len(), 3);
+        assert!(!make_floors(Algorithm::Prim, Some(Difficulty::Hard), 1, 1, 2, 5).1);
+    }
+
+    #[test]
+    fn floors_chain_up_to_the_goal() {
+        let (floors, matched) = make_floors(Algorithm::Prim, None, 5, 4, 3, 9);
+        assert!(matched);
+        assert_eq!(floors.len(), 3);
+        let count = |maze: &Vec<Vec<char>>, c: char| maze.iter().flatten().filter(|&&cell| cell == c).count();
+        assert_eq!(floors.iter().map(|maze| count(maze, SPAWN)).sum::<usize>(), 1);
+        assert_eq!(count(&floors[2], GOAL), 1);
+        assert_eq!(count(&floors[0], STAIRS_UP), 1);
+        assert_eq!(count(&floors[1], STAIRS_DOWN), 1);
+        assert!(analysis::solve_floors(&floors).is_some());
+    }
+
+    #[test]
+    fn thin_walls_keep_the_maze_solvable() {
+        let (w, h) = (6, 4);
+        let maze = make_maze_with(Algorithm::Kruskal, w, h, 3);
+        let (grid, walls) = thin_walls(&maze);
+        assert_eq!(grid.len(), h + 2);
+        assert!(grid.iter().all(|row| row.len() == w + 2));
+        assert_eq!(grid[1][1], SPAWN);
+        assert_eq!(grid[h][w], GOAL);
+
+        let cuts: Vec<(Step, Step)> = walls.iter().flat_map(|wall| wall.cuts(0)).collect();
+        let path = analysis::solve_linked(&[&grid], &[], &cuts).unwrap();
+        assert_eq!(analysis::stats_linked(&grid, 0, &[], &cuts).solution.unwrap().length, path.len() - 1);
+        // Sin las paredes, el mapa es una sala abierta y el camino es más corto
+        assert!(analysis::solve(&grid).unwrap().len() <= path.len());
+    }
+}
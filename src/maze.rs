@@ -1,12 +1,248 @@
-use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::fmt;
+use std::fs;
+use std::io::ErrorKind;
+use crate::analysis::{self, Step};
+use crate::doors::{DOOR, LOCKED_DOOR};
+use crate::enemy::GHOST;
+use crate::items::ItemKind;
 
-pub fn load_maze(filename: &str) -> Vec<Vec<char>> {
-    let file = File::open(filename).unwrap();
-    let reader = BufReader::new(file);
+pub const SPAWN: char = 'p';
+pub const GOAL: char = 'g';
+// Escaleras entre pisos: la que sube tiene encima, en la misma celda, la que baja
+pub const STAIRS_UP: char = '>';
+pub const STAIRS_DOWN: char = '<';
+const WALLS: [char; 3] = ['+', '-', '|'];
 
-    reader
-        .lines()
-        .map(|line| line.unwrap().chars().collect())
-        .collect()
+// Problemas que impiden jugar un laberinto. Las líneas y columnas empiezan en 1
+#[derive(Debug)]
+pub enum MazeError {
+    NotFound(String),
+    Io(String, std::io::Error),
+    Empty,
+    RaggedRow { line: usize, expected: usize, found: usize },
+    UnknownChar { line: usize, column: usize, found: char },
+    MissingSpawn,
+    DuplicateSpawn { line: usize, column: usize },
+    MissingGoal,
+    OpenBoundary { line: usize, column: usize },
+    UnreachableGoal,
+    Format(String),
+    MisplacedEntity { what: String, x: usize, y: usize },
+    UnpairedStairs { floor: usize, x: usize, y: usize },
+    Texture(String, String),
+}
+
+impl fmt::Display for MazeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MazeError::NotFound(path) => write!(f, "No se encontró el laberinto {}", path),
+            MazeError::Io(path, e) => write!(f, "No se pudo leer el laberinto {}: {}", path, e),
+            MazeError::Empty => write!(f, "El laberinto está vacío"),
+            MazeError::RaggedRow { line, expected, found } => {
+                write!(f, "Línea {}: tiene {} caracteres y se esperaban {}", line, found, expected)
+            }
+            MazeError::UnknownChar { line, column, found } => {
+                write!(f, "Línea {}, columna {}: carácter desconocido '{}'", line, column, found)
+            }
+            MazeError::MissingSpawn => write!(f, "Falta el inicio del jugador ('{}')", SPAWN),
+            MazeError::DuplicateSpawn { line, column } => {
+                write!(f, "Línea {}, columna {}: el inicio del jugador ('{}') está repetido", line, column, SPAWN)
+            }
+            MazeError::MissingGoal => write!(f, "Falta la meta ('{}')", GOAL),
+            MazeError::OpenBoundary { line, column } => {
+                write!(f, "Línea {}, columna {}: el borde del laberinto tiene una abertura", line, column)
+            }
+            MazeError::UnreachableGoal => write!(f, "No hay camino desde el inicio hasta la meta"),
+            MazeError::Format(e) => write!(f, "El nivel no tiene un formato válido: {}", e),
+            MazeError::MisplacedEntity { what, x, y } => {
+                write!(f, "{} en ({}, {}) no está sobre una celda libre del mapa", what, x, y)
+            }
+            MazeError::UnpairedStairs { floor, x, y } => {
+                write!(f, "Piso {}: la escalera en ({}, {}) no lleva a otra escalera en el piso de al lado", floor + 1, x, y)
+            }
+            MazeError::Texture(path, e) => write!(f, "No se pudo cargar la textura {}: {}", path, e),
+        }
+    }
+}
+
+impl std::error::Error for MazeError {}
+
+// Celdas por las que se puede caminar (las puertas cuentan, aunque estén cerradas)
+pub fn is_walkable(cell: char) -> bool {
+    cell == ' ' || cell == SPAWN || cell == GHOST || cell == DOOR || cell == LOCKED_DOOR || is_stairs(cell) || ItemKind::from_cell(cell).is_some()
+}
+
+pub fn is_stairs(cell: char) -> bool {
+    cell == STAIRS_UP || cell == STAIRS_DOWN
+}
+
+// Lee un archivo de nivel, distinguiendo si falta o no se puede leer
+pub fn read_file(filename: &str) -> Result<String, MazeError> {
+    fs::read_to_string(filename).map_err(|e| match e.kind() {
+        ErrorKind::NotFound => MazeError::NotFound(filename.to_string()),
+        _ => MazeError::Io(filename.to_string(), e),
+    })
+}
+
+// Lee y valida un laberinto. `walls` son caracteres de pared extra, como los de la paleta
+pub fn load_maze(filename: &str, walls: &[char]) -> Result<Vec<Vec<char>>, MazeError> {
+    let contents = read_file(filename)?;
+
+    let maze = parse_maze(&contents, walls)?;
+    validate_maze(&maze)?;
+    Ok(maze)
+}
+
+// Convierte el texto en la cuadrícula, revisando solo la forma y los caracteres
+pub fn parse_maze(contents: &str, walls: &[char]) -> Result<Vec<Vec<char>>, MazeError> {
+    // Las líneas vacías al final no cuentan
+    let lines: Vec<&str> = contents.trim_end_matches(['\n', '\r']).lines().collect();
+    if lines.iter().all(|line| line.trim().is_empty()) {
+        return Err(MazeError::Empty);
+    }
+
+    let width = lines[0].chars().count();
+    let mut maze = Vec::with_capacity(lines.len());
+    for (y, line) in lines.iter().enumerate() {
+        let row: Vec<char> = line.chars().collect();
+        if row.len() != width {
+            return Err(MazeError::RaggedRow { line: y + 1, expected: width, found: row.len() });
+        }
+        if let Some(x) = row.iter().position(|&c| !is_walkable(c) && c != GOAL && !WALLS.contains(&c) && !walls.contains(&c)) {
+            return Err(MazeError::UnknownChar { line: y + 1, column: x + 1, found: row[x] });
+        }
+        maze.push(row);
+    }
+
+    Ok(maze)
+}
+
+// Revisa que el laberinto se pueda jugar: un inicio, una meta alcanzable y el borde cerrado
+pub fn validate_maze(maze: &[Vec<char>]) -> Result<(), MazeError> {
+    validate_floors(&[maze])
+}
+
+// Las mismas reglas para un nivel de varios pisos, de abajo hacia arriba: un solo inicio en
+// todo el nivel, la meta en cualquier piso y cada escalera con su par en el piso de al lado
+pub fn validate_floors<M: AsRef<[Vec<char>]>>(floors: &[M]) -> Result<(), MazeError> {
+    validate_linked(floors, &[], &[])
+}
+
+// Con los saltos de los paneles, que también cuentan para llegar a la meta, y los pasos que
+// cortan las paredes delgadas
+pub fn validate_linked<M: AsRef<[Vec<char>]>>(floors: &[M], links: &[(Step, Step)], cuts: &[(Step, Step)]) -> Result<(), MazeError> {
+    let cell_at = |floor: usize, x: usize, y: usize| floors.get(floor).and_then(|maze| maze.as_ref().get(y)).and_then(|row| row.get(x)).copied();
+
+    let mut spawn = None;
+    let mut goals = Vec::new();
+    for (floor, maze) in floors.iter().enumerate() {
+        let maze = maze.as_ref();
+        for (y, row) in maze.iter().enumerate() {
+            for (x, &cell) in row.iter().enumerate() {
+                if cell == SPAWN {
+                    if spawn.is_some() {
+                        return Err(MazeError::DuplicateSpawn { line: y + 1, column: x + 1 });
+                    }
+                    spawn = Some((x, y));
+                } else if cell == GOAL {
+                    goals.push((x, y));
+                }
+
+                let on_border = y == 0 || x == 0 || y + 1 == maze.len() || x + 1 == row.len();
+                if on_border && is_walkable(cell) {
+                    return Err(MazeError::OpenBoundary { line: y + 1, column: x + 1 });
+                }
+
+                let paired = match cell {
+                    STAIRS_UP => cell_at(floor + 1, x, y) == Some(STAIRS_DOWN),
+                    STAIRS_DOWN => floor > 0 && cell_at(floor - 1, x, y) == Some(STAIRS_UP),
+                    _ => true,
+                };
+                if !paired {
+                    return Err(MazeError::UnpairedStairs { floor, x, y });
+                }
+            }
+        }
+    }
+    spawn.ok_or(MazeError::MissingSpawn)?;
+    if goals.is_empty() {
+        return Err(MazeError::MissingGoal);
+    }
+
+    // La meta se alcanza tocando una de sus esquinas, incluso en diagonal
+    match analysis::solve_linked(floors, links, cuts) {
+        Some(_) => Ok(()),
+        None => Err(MazeError::UnreachableGoal),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAZE: &str = "+--+--+\n|p    |\n+  +--+\n|    g|\n+--+--+\n";
+
+    fn parse(contents: &str) -> Vec<Vec<char>> {
+        parse_maze(contents, &[]).unwrap()
+    }
+
+    #[test]
+    fn parses_and_validates_a_playable_maze() {
+        let maze = parse(MAZE);
+        assert_eq!(maze.len(), 5);
+        assert_eq!(maze[1][1], SPAWN);
+        assert!(validate_maze(&maze).is_ok());
+    }
+
+    #[test]
+    fn rejects_empty_files() {
+        assert!(matches!(parse_maze("", &[]), Err(MazeError::Empty)));
+        assert!(matches!(parse_maze("\n  \n", &[]), Err(MazeError::Empty)));
+    }
+
+    #[test]
+    fn reports_the_line_of_a_ragged_row() {
+        let result = parse_maze("+--+\n|p|\n+--+", &[]);
+        assert!(matches!(result, Err(MazeError::RaggedRow { line: 2, expected: 4, found: 3 })));
+    }
+
+    #[test]
+    fn unknown_characters_are_allowed_only_as_palette_walls() {
+        let contents = "+--+\n|pg#\n+--+";
+        assert!(matches!(parse_maze(contents, &[]), Err(MazeError::UnknownChar { line: 2, column: 4, found: '#' })));
+        assert!(parse_maze(contents, &['#']).is_ok());
+    }
+
+    #[test]
+    fn requires_exactly_one_spawn_and_a_goal() {
+        assert!(matches!(validate_maze(&parse(&MAZE.replace('p', " "))), Err(MazeError::MissingSpawn)));
+        assert!(matches!(validate_maze(&parse(&MAZE.replace('g', " "))), Err(MazeError::MissingGoal)));
+        let twice = MAZE.replace("|    g|", "|p   g|");
+        assert!(matches!(validate_maze(&parse(&twice)), Err(MazeError::DuplicateSpawn { line: 4, column: 2 })));
+    }
+
+    #[test]
+    fn rejects_openings_in_the_border() {
+        let open = MAZE.replace("|    g|", "     g|");
+        assert!(matches!(validate_maze(&parse(&open)), Err(MazeError::OpenBoundary { line: 4, column: 1 })));
+    }
+
+    #[test]
+    fn the_goal_must_be_reachable() {
+        let closed = MAZE.replace("+  +--+", "+--+--+");
+        assert!(matches!(validate_maze(&parse(&closed)), Err(MazeError::UnreachableGoal)));
+    }
+
+    #[test]
+    fn stairs_need_a_pair_on_the_next_floor() {
+        let ground = parse("+---+\n|p> |\n+---+");
+        let upper = parse("+---+\n| <g|\n+---+");
+        assert!(validate_floors(&[&ground, &upper]).is_ok());
+        assert!(matches!(validate_floors(&[&ground]), Err(MazeError::UnpairedStairs { floor: 0, x: 2, y: 1 })));
+    }
+
+    #[test]
+    fn a_missing_file_is_reported_as_not_found() {
+        assert!(matches!(load_maze("no-existe.txt", &[]), Err(MazeError::NotFound(path)) if path == "no-existe.txt"));
+    }
 }
@@ -0,0 +1,308 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use crate::items::ItemKind;
+use crate::maze::MazeError;
+use crate::raycasting::Face;
+use crate::textures::{Filter, Texture};
+
+const DEFAULT_WALL: &str = "sprites/casaSprite.png";
+const DEFAULT_FLOOR: &str = "sprites/floorSprite.png";
+const DEFAULT_CEILING: &str = "sprites/skySprite.png";
+
+// Equivale a la asignación original de Pueblo Lavanda
+const BUILTIN: &str = "| = sprites/casaSprite.png\n\
+                       - = sprites/casaSprite2.png\n\
+                       g = sprites/centroPoke.png\n";
+
+// Texturas de un tipo de celda: una por cara, o la misma para todas
+pub struct TileTextures {
+    faces: [Arc<Texture>; 4], // Norte, sur, este, oeste
+}
+
+impl TileTextures {
+    fn uniform(texture: Arc<Texture>) -> TileTextures {
+        TileTextures {
+            faces: [texture.clone(), texture.clone(), texture.clone(), texture],
+        }
+    }
+
+    pub fn face(&self, face: Face) -> &Texture {
+        &self.faces[face_index(face)]
+    }
+
+    pub fn is_transparent(&self) -> bool {
+        self.faces.iter().any(|texture| texture.is_transparent())
+    }
+}
+
+// Relación entre los caracteres del mapa y sus texturas, junto con el piso y el cielo.
+// Se lee de un archivo `.palette` con el mismo nombre que el laberinto:
+//
+//   floor = sprites/floorSprite.png
+//   ceiling = sprites/skySprite.png
+//   | = sprites/casaSprite.png
+//   t.north = sprites/lapida.png
+//
+// Las celdas sin entrada usan la textura `default`. La clave `requires` lista los
+// caracteres de los objetos necesarios para entrar a la meta (`requires = s k`).
+// `t.height = 0.4` cambia la altura de una pared (1 por defecto: una cerca es más baja y
+// una torre, más alta) y `camera = 0.5` la altura de los ojos del jugador. Con
+// `filter = bilinear` las paredes se ven suavizadas en lugar de pixeladas.
+pub struct Palette {
+    pub floor: Arc<Texture>,
+    pub ceiling: Arc<Texture>,
+    pub goal_requires: Vec<ItemKind>,
+    pub camera: f32,
+    pub filter: Filter,
+    default: TileTextures,
+    tiles: HashMap<char, TileTextures>,
+    heights: HashMap<char, f32>,
+    walls: Vec<char>,
+}
+
+pub const WALL_HEIGHT: f32 = 1.0;
+pub const CAMERA_HEIGHT: f32 = 0.5;
+
+impl Palette {
+    // Busca la paleta junto al archivo del laberinto (maze.txt -> maze.palette)
+    pub fn load_for(maze_file: &str) -> Result<Palette, MazeError> {
+        Palette::load(Path::new(maze_file).with_extension("palette"))
+    }
+
+    pub fn load(palette_file: impl AsRef<Path>) -> Result<Palette, MazeError> {
+        Palette::load_with(palette_file, "")
+    }
+
+    // Lee la paleta y le agrega entradas con la misma sintaxis, que tienen prioridad
+    pub fn load_with(palette_file: impl AsRef<Path>, extra: &str) -> Result<Palette, MazeError> {
+        Palette::parse(&read_contents(palette_file.as_ref(), extra))
+    }
+
+    // La paleta original de Pueblo Lavanda
+    pub fn builtin() -> Result<Palette, MazeError> {
+        Palette::parse(BUILTIN)
+    }
+
+    pub fn parse(contents: &str) -> Result<Palette, MazeError> {
+        let mut cache = TextureCache::default();
+        let mut floor = None;
+        let mut ceiling = None;
+        let mut default = None;
+        let mut goal_requires = Vec::new();
+        let mut camera = CAMERA_HEIGHT;
+        let mut filter = Filter::default();
+        let mut heights = HashMap::new();
+        let mut faces: HashMap<char, [Option<Arc<Texture>>; 4]> = HashMap::new();
+
+        for (number, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let Some((key, path)) = line.split_once('=') else {
+                eprintln!("Paleta, línea {}: se esperaba 'clave = textura'", number + 1);
+                continue;
+            };
+            let key = key.trim();
+
+            if key == "requires" {
+                for cell in path.chars().filter(|c| !c.is_whitespace()) {
+                    match ItemKind::from_cell(cell) {
+                        Some(kind) => goal_requires.push(kind),
+                        None => eprintln!("Paleta, línea {}: '{}' no es un objeto", number + 1, cell),
+                    }
+                }
+                continue;
+            }
+
+            if key == "filter" {
+                match path.trim() {
+                    "nearest" => filter = Filter::Nearest,
+                    "bilinear" => filter = Filter::Bilinear,
+                    other => eprintln!("Paleta, línea {}: filtro desconocido '{}' (nearest o bilinear)", number + 1, other),
+                }
+                continue;
+            }
+
+            // Las alturas son números, no texturas
+            if key == "camera" || key.ends_with(".height") {
+                let height = match path.trim().parse::<f32>() {
+                    Ok(height) if height > 0.0 => height,
+                    _ => {
+                        eprintln!("Paleta, línea {}: '{}' no es una altura válida", number + 1, path.trim());
+                        continue;
+                    }
+                };
+                match key.strip_suffix(".height").map(|cell| cell.chars().collect::<Vec<char>>()) {
+                    None => camera = height,
+                    Some(cell) if cell.len() == 1 => {
+                        heights.insert(cell[0], height);
+                    }
+                    Some(_) => eprintln!("Paleta, línea {}: clave desconocida '{}'", number + 1, key),
+                }
+                continue;
+            }
+
+            let Some(texture) = cache.get(path.trim()) else {
+                continue;
+            };
+
+            match key {
+                "floor" => floor = Some(texture),
+                "ceiling" => ceiling = Some(texture),
+                "default" => default = Some(texture),
+                _ => {
+                    let mut chars = key.chars();
+                    let Some(cell) = chars.next() else {
+                        eprintln!("Paleta, línea {}: falta el carácter de la celda", number + 1);
+                        continue;
+                    };
+                    let rest: String = chars.collect();
+                    let entry = faces.entry(cell).or_default();
+
+                    match rest.as_str() {
+                        "" => *entry = [Some(texture.clone()), Some(texture.clone()), Some(texture.clone()), Some(texture)],
+                        ".north" => entry[0] = Some(texture),
+                        ".south" => entry[1] = Some(texture),
+                        ".east" => entry[2] = Some(texture),
+                        ".west" => entry[3] = Some(texture),
+                        _ => eprintln!("Paleta, línea {}: clave desconocida '{}'", number + 1, key),
+                    }
+                }
+            }
+        }
+
+        let default = match default {
+            Some(default) => default,
+            None => cache.get_or_builtin(DEFAULT_WALL)?,
+        };
+        let tiles = faces
+            .into_iter()
+            .map(|(cell, [n, s, e, w])| {
+                let pick = |t: Option<Arc<Texture>>| t.unwrap_or_else(|| default.clone());
+                (cell, TileTextures { faces: [pick(n), pick(s), pick(e), pick(w)] })
+            })
+            .collect();
+
+        Ok(Palette {
+            floor: match floor {
+                Some(floor) => floor,
+                None => cache.get_or_builtin(DEFAULT_FLOOR)?,
+            },
+            ceiling: match ceiling {
+                Some(ceiling) => ceiling,
+                None => cache.get_or_builtin(DEFAULT_CEILING)?,
+            },
+            goal_requires,
+            camera,
+            filter,
+            default: TileTextures::uniform(default),
+            tiles,
+            heights,
+            walls: wall_chars_in(contents),
+        })
+    }
+
+    // Caracteres con textura o altura propia; el mapa puede usarlos como paredes
+    pub fn wall_chars(&self) -> Vec<char> {
+        self.walls.clone()
+    }
+
+    // Las paredes con píxeles transparentes (rejas, ventanas) dejan ver lo que hay detrás
+    pub fn is_transparent(&self, cell: char) -> bool {
+        self.tile(cell).is_transparent()
+    }
+
+    pub fn height(&self, cell: char) -> f32 {
+        self.heights.get(&cell).copied().unwrap_or(WALL_HEIGHT)
+    }
+
+    // La pared más alta del nivel; detrás de ella no se ve nada
+    pub fn max_height(&self) -> f32 {
+        self.heights.values().copied().fold(WALL_HEIGHT, f32::max)
+    }
+
+    pub fn tile(&self, cell: char) -> &TileTextures {
+        self.tiles.get(&cell).unwrap_or(&self.default)
+    }
+}
+
+// El archivo de la paleta con las entradas extra al final, o la paleta por defecto si no existe
+fn read_contents(palette_file: &Path, extra: &str) -> String {
+    let contents = fs::read_to_string(palette_file).unwrap_or_else(|_| {
+        eprintln!("No se encontró {}, usando la paleta por defecto.", palette_file.display());
+        BUILTIN.to_string()
+    });
+    format!("{}\n{}", contents, extra)
+}
+
+// Los caracteres de pared de una paleta, sin cargar sus imágenes: alcanza con las claves. Es lo
+// único que necesitan las herramientas que validan niveles sin abrir la ventana
+pub fn wall_chars_for(palette_file: impl AsRef<Path>, extra: &str) -> Vec<char> {
+    wall_chars_in(&read_contents(palette_file.as_ref(), extra))
+}
+
+fn wall_chars_in(contents: &str) -> Vec<char> {
+    let mut chars: Vec<char> = contents
+        .lines()
+        .filter_map(|line| line.split_once('='))
+        .map(|(key, _)| key.trim())
+        .filter(|key| !key.starts_with('#'))
+        .filter_map(|key| {
+            let mut chars = key.chars();
+            let cell = chars.next()?;
+            let rest = chars.as_str();
+            (rest.is_empty() || [".north", ".south", ".east", ".west", ".height"].contains(&rest)).then_some(cell)
+        })
+        .collect();
+    chars.sort_unstable();
+    chars.dedup();
+    chars
+}
+
+fn face_index(face: Face) -> usize {
+    match face {
+        Face::North => 0,
+        Face::South => 1,
+        Face::East => 2,
+        Face::West => 3,
+    }
+}
+
+// Evita cargar dos veces la misma imagen cuando varias celdas la comparten
+#[derive(Default)]
+struct TextureCache {
+    loaded: HashMap<String, Arc<Texture>>,
+}
+
+impl TextureCache {
+    fn get(&mut self, path: &str) -> Option<Arc<Texture>> {
+        if let Some(texture) = self.loaded.get(path) {
+            return Some(texture.clone());
+        }
+
+        match Texture::load(path) {
+            Ok(texture) => {
+                let texture = Arc::new(texture);
+                self.loaded.insert(path.to_string(), texture.clone());
+                Some(texture)
+            }
+            Err(e) => {
+                eprintln!("No se pudo cargar la textura {}: {}", path, e);
+                None
+            }
+        }
+    }
+
+    // Las texturas por defecto forman parte del juego; si faltan (por ejemplo, al correr desde
+    // otra carpeta) la paleta no se puede armar
+    fn get_or_builtin(&mut self, path: &str) -> Result<Arc<Texture>, MazeError> {
+        if let Some(texture) = self.get(path) {
+            return Ok(texture);
+        }
+        Texture::load(path).map(Arc::new).map_err(|e| MazeError::Texture(path.to_string(), e.to_string()))
+    }
+}
@@ -1,7 +1,10 @@
 use nalgebra::Vector2;
+use crate::items::Inventory;
 
 pub struct Player {
     pub pos: Vector2<f32>,
     pub a: f32, // angle of view
     pub fov: f32, // field of view
+    pub inventory: Inventory, // objetos recogidos
 }
+pub const SPAWN_ANGLE: f32 = std::f32::consts::FRAC_PI_3; // Hacia donde mira el jugador al empezar
@@ -0,0 +1,67 @@
+use std::collections::{HashMap, HashSet};
+use nalgebra::Vector2;
+use crate::campaign::Campaign;
+use crate::game::{GameState, TICK};
+use crate::replay::Replay;
+
+// Carrera contra la mejor partida grabada: el "entrenador fantasma" repite
+// el recorrido tick a tick y se compara el tiempo al pasar por cada celda
+pub struct GhostRace {
+    path: Vec<(usize, Vector2<f32>)>,   // Piso y posición del entrenador en cada tick
+    first_visit: HashMap<Visit, u64>,   // Tick en que el entrenador pisó cada celda por primera vez
+    visited: HashSet<Visit>,            // Celdas que ya pisó el jugador en esta partida
+    pub best_time: f32,
+    pub split: Option<f32>, // Segundos de diferencia en la última celda compartida (negativo = adelante)
+}
+
+impl GhostRace {
+    // Simula la grabación completa para conocer el recorrido del entrenador
+    pub fn new(campaign: &Campaign, replay: &Replay) -> Result<GhostRace, String> {
+        let index = replay.level_index(campaign)?;
+        let mut state = GameState::new(&campaign.levels[index]).map_err(|e| e.to_string())?;
+        let mut path = vec![(state.world.floor, state.player.pos)];
+        let mut first_visit = HashMap::new();
+        first_visit.insert(cell(state.world.floor, &state.player.pos), 0);
+
+        for input in replay.inputs() {
+            let finished = state.tick(&input).finished;
+            path.push((state.world.floor, state.player.pos));
+            first_visit.entry(cell(state.world.floor, &state.player.pos)).or_insert(state.ticks);
+            if finished {
+                break;
+            }
+        }
+
+        Ok(GhostRace {
+            path,
+            first_visit,
+            visited: HashSet::new(),
+            best_time: replay.time.unwrap_or(f32::MAX),
+            split: None,
+        })
+    }
+
+    // Al terminar su recorrido el entrenador se queda en la meta
+    pub fn trainer_pos(&self, ticks: u64) -> (usize, Vector2<f32>) {
+        let index = (ticks as usize).min(self.path.len() - 1);
+        self.path[index]
+    }
+
+    // Actualiza la diferencia de tiempo cuando el jugador pisa una celda nueva
+    pub fn update(&mut self, floor: usize, player_pos: &Vector2<f32>, ticks: u64) {
+        let current = cell(floor, player_pos);
+        if !self.visited.insert(current) {
+            return;
+        }
+        if let Some(&trainer_ticks) = self.first_visit.get(&current) {
+            self.split = Some((ticks as f32 - trainer_ticks as f32) * TICK);
+        }
+    }
+}
+
+// Piso y celda
+type Visit = (usize, usize, usize);
+
+fn cell(floor: usize, pos: &Vector2<f32>) -> Visit {
+    (floor, pos.x as usize, pos.y as usize)
+}
@@ -1,34 +1,47 @@
 use crate::player::Player;
+use crate::doors::Doors;
+use crate::maze::is_stairs;
+use crate::segments::Segment;
+use nalgebra::Vector2;
+
+// Cara de la celda que golpea el rayo
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Face {
+    North,
+    South,
+    East,
+    West,
+}
 
 pub struct RayHit {
     pub distance: f32,      // Distancia desde el jugador hasta la pared
     pub hit_x: f32,         // Coordenada x del punto de impacto
     pub hit_y: f32,         // Coordenada y del punto de impacto
     pub wall_type: char,    // Símbolo de la pared golpeada ('-' o '|')
-    pub is_vertical: bool,  // Indica si la pared golpeada es vertical
-}
-
-pub fn cast_rays(
-    framebuffer: &mut Vec<u32>,
-    maze: &Vec<Vec<char>>,
-    player: &Player,
-    block_size: usize,
-    screen_width: usize,
-) {
-    let num_rays = screen_width;
-    let delta_angle = player.fov / num_rays as f32;
-
-    for i in 0..num_rays {
-        let ray_angle = player.a - (player.fov / 2.0) + (i as f32) * delta_angle;
-        cast_ray(maze, player, ray_angle);
-    }
+    pub face: Face,         // Cara de la celda por la que entró el rayo
+    pub texture_x: f32,     // Coordenada horizontal de la textura en el punto de impacto (0.0 a 1.0)
 }
 
 pub fn cast_ray(
-    maze: &Vec<Vec<char>>,
+    maze: &[Vec<char>],
+    doors: &Doors,
     player: &Player,
     angle: f32
 ) -> RayHit {
+    cast_ray_through(maze, doors, &[], player, angle, |_| false).swap_remove(0)
+}
+
+// Como cast_ray, pero también choca con las paredes delgadas y sigue de largo por las que
+// `see_through` deja ver (las bajas, detrás de las que se asoman otras más altas). Devuelve
+// los impactos del más cercano al más lejano; el último es la pared que detuvo el rayo
+pub fn cast_ray_through(
+    maze: &[Vec<char>],
+    doors: &Doors,
+    walls: &[Segment],
+    player: &Player,
+    angle: f32,
+    see_through: impl Fn(char) -> bool,
+) -> Vec<RayHit> {
     let sin_a = angle.sin();
     let cos_a = angle.cos();
 
@@ -56,14 +69,19 @@ pub fn cast_ray(
         (y - map_y as f32) * delta_dist_y
     };
 
-    let mut hit = false;
+    // Un rayo que avanza hacia +x entra a la celda por su cara oeste, etc.
+    let face = |is_vertical: bool| match (is_vertical, step_x > 0, step_y > 0) {
+        (true, true, _) => Face::West,
+        (true, false, _) => Face::East,
+        (false, _, true) => Face::North,
+        (false, _, false) => Face::South,
+    };
+
+    let mut hits = Vec::new();
     let mut wall_type = ' ';
-    let mut is_vertical = false;
-    let mut distance = 0.0;
-    let mut hit_x = 0.0;
-    let mut hit_y = 0.0;
+    let mut is_vertical;
 
-    while !hit {
+    loop {
         if side_dist_x < side_dist_y {
             side_dist_x += delta_dist_x;
             map_x += step_x;
@@ -79,25 +97,79 @@ pub fn cast_ray(
         }
 
         wall_type = maze[map_y as usize][map_x as usize];
-        if wall_type != ' ' {
-            hit = true;
-            if is_vertical {
-                distance = (map_x as f32 - x + (1.0 - step_x as f32) / 2.0) / cos_a;
-                hit_x = map_x as f32;
-                hit_y = y + distance * sin_a;
+
+        // Las puertas están empotradas a mitad de la celda y se deslizan hacia un lado
+        if let Some(door) = doors.get(map_x as usize, map_y as usize) {
+            let (t, along) = if door.horizontal {
+                let t = (map_y as f32 + 0.5 - y) / sin_a;
+                (t, x + t * cos_a - map_x as f32)
             } else {
-                distance = (map_y as f32 - y + (1.0 - step_y as f32) / 2.0) / sin_a;
-                hit_x = x + distance * cos_a;
-                hit_y = map_y as f32;
+                let t = (map_x as f32 + 0.5 - x) / cos_a;
+                (t, y + t * sin_a - map_y as f32)
+            };
+
+            // El rayo pasa por la parte abierta o sale de la celda antes de llegar a la hoja
+            if !t.is_finite() || !(0.0..1.0).contains(&along) || along < door.open {
+                continue;
             }
+
+            hits.push(RayHit {
+                distance: t,
+                hit_x: x + t * cos_a,
+                hit_y: y + t * sin_a,
+                wall_type,
+                face: face(!door.horizontal),
+                texture_x: along - door.open,
+            });
+        } else if wall_type != ' ' && !is_stairs(wall_type) {
+            // Las escaleras están en el piso y no tapan la vista
+            let hit = if is_vertical {
+                let distance = (map_x as f32 - x + (1.0 - step_x as f32) / 2.0) / cos_a;
+                let hit_y = y + distance * sin_a;
+                RayHit { distance, hit_x: map_x as f32, hit_y, wall_type, face: face(true), texture_x: hit_y % 1.0 }
+            } else {
+                let distance = (map_y as f32 - y + (1.0 - step_y as f32) / 2.0) / sin_a;
+                let hit_x = x + distance * cos_a;
+                RayHit { distance, hit_x, hit_y: map_y as f32, wall_type, face: face(false), texture_x: hit_x % 1.0 }
+            };
+            hits.push(hit);
+        } else {
+            continue;
+        }
+
+        if !see_through(wall_type) {
+            break;
+        }
+    }
+
+    // Las paredes delgadas se cruzan con el rayo en cualquier punto, no celda por celda
+    let dir = Vector2::new(cos_a, sin_a);
+    for wall in walls {
+        let Some((distance, along)) = wall.intersect(player.pos, dir) else {
+            continue;
+        };
+        // Una pared acostada se golpea por su cara norte o sur, como las de las celdas
+        let edge = wall.end() - wall.start();
+        let horizontal = edge.x.abs() >= edge.y.abs();
+        hits.push(RayHit {
+            distance,
+            hit_x: x + distance * cos_a,
+            hit_y: y + distance * sin_a,
+            wall_type: wall.wall,
+            face: face(!horizontal),
+            texture_x: (along * wall.length()) % 1.0,
+        });
+    }
+    if !walls.is_empty() {
+        hits.sort_by(|a, b| a.distance.total_cmp(&b.distance));
+        if let Some(stop) = hits.iter().position(|hit| !see_through(hit.wall_type)) {
+            hits.truncate(stop + 1);
         }
     }
 
-    RayHit {
-        distance,
-        hit_x,
-        hit_y,
-        wall_type,
-        is_vertical,
+    // Si el rayo sale del mapa sin chocar, se devuelve un impacto vacío como antes
+    if hits.is_empty() {
+        hits.push(RayHit { distance: 0.0, hit_x: 0.0, hit_y: 0.0, wall_type, face: face(is_vertical), texture_x: 0.0 });
     }
-}
\ No newline at end of file
+    hits
+}
@@ -0,0 +1,197 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+use crate::campaign::{Campaign, LevelEntry};
+use crate::controls::Input;
+use crate::game::{GameState, TICK_RATE};
+
+pub const LAST_REPLAY: &str = "replays/last.json";
+
+// Ruta de la mejor partida grabada de un nivel
+pub fn best_path(level_id: &str) -> String {
+    format!("replays/{}-best.json", level_id)
+}
+
+// Se incrementa cada vez que cambia el formato o la simulación deja de ser compatible
+const REPLAY_VERSION: u32 = 2;
+
+// Las teclas de cada tick de una partida, desde el inicio del nivel
+#[derive(Serialize, Deserialize)]
+pub struct Replay {
+    version: u32,
+    pub level: String,      // Id del nivel en la campaña
+    level_key: String,      // Hash del archivo o semilla, para detectar si el nivel cambió
+    seed: Option<u64>,      // Semilla del generador, si el nivel es generado
+    tick_rate: u32,
+    pub time: Option<f32>,  // Tiempo final, si llegó a la meta
+    inputs: Vec<(u8, u32)>, // Teclas por tick comprimidas: (bits de Input, ticks seguidos)
+}
+
+impl Replay {
+    pub fn new(level: &LevelEntry) -> Replay {
+        Replay {
+            version: REPLAY_VERSION,
+            level: level.id.clone(),
+            level_key: level.key(),
+            seed: level.generate.as_ref().map(|spec| spec.seed),
+            tick_rate: TICK_RATE,
+            time: None,
+            inputs: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, input: Input) {
+        let bits = input.bits();
+        match self.inputs.last_mut() {
+            Some((last, count)) if *last == bits => *count += 1,
+            _ => self.inputs.push((bits, 1)),
+        }
+    }
+
+    pub fn inputs(&self) -> Vec<Input> {
+        self.inputs.iter()
+            .flat_map(|&(bits, count)| std::iter::repeat_n(Input::from_bits(bits), count as usize))
+            .collect()
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(dir) = Path::new(path).parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, serde_json::to_string(self)?)?;
+        Ok(())
+    }
+
+    // La guarda como la mejor del nivel si llegó a la meta más rápido que la anterior
+    pub fn save_if_best(&self) -> Result<bool, Box<dyn std::error::Error>> {
+        let Some(time) = self.time else {
+            return Ok(false);
+        };
+        let path = best_path(&self.level);
+        let previous = Replay::load(&path).ok()
+            .filter(|best| best.level_key == self.level_key)
+            .and_then(|best| best.time);
+        if previous.is_some_and(|best| best <= time) {
+            return Ok(false);
+        }
+        self.save(&path)?;
+        Ok(true)
+    }
+
+    pub fn load(path: &str) -> Result<Replay, Box<dyn std::error::Error>> {
+        let replay: Replay = serde_json::from_str(&fs::read_to_string(path)?)?;
+        if replay.version != REPLAY_VERSION || replay.tick_rate != TICK_RATE {
+            return Err(format!(
+                "la repetición es de otra versión ({}, {} ticks/s; se esperaba {}, {} ticks/s)",
+                replay.version, replay.tick_rate, REPLAY_VERSION, TICK_RATE
+            ).into());
+        }
+        Ok(replay)
+    }
+
+    // Posición del nivel en la campaña, comprobando que sea el mismo que se grabó
+    pub fn level_index(&self, campaign: &Campaign) -> Result<usize, String> {
+        let index = campaign.levels.iter()
+            .position(|level| level.id == self.level)
+            .ok_or_else(|| format!("el nivel {} no está en la campaña", self.level))?;
+        let level = &campaign.levels[index];
+        let seed = level.generate.as_ref().map(|spec| spec.seed);
+        if level.key() != self.level_key || seed != self.seed {
+            return Err(format!("el nivel {} cambió desde que se grabó", self.level));
+        }
+        Ok(index)
+    }
+
+    // Reproduce la partida sin ventana. Devuelve el estado final y si llegó a la meta
+    pub fn simulate(&self, campaign: &Campaign) -> Result<(GameState, bool), String> {
+        let index = self.level_index(campaign)?;
+        let mut state = GameState::new(&campaign.levels[index]).map_err(|e| e.to_string())?;
+        for input in self.inputs() {
+            if state.tick(&input).finished {
+                return Ok((state, true));
+            }
+        }
+        Ok((state, false))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn campaign(seed: u64) -> Campaign {
+        let json = format!(r#"{{"name": "Prueba", "levels": [{{"id": "uno", "name": "Uno", "generate": {{"width": 4, "height": 3, "seed": {}}}}}]}}"#, seed);
+        serde_json::from_str(&json).unwrap()
+    }
+
+    fn input(bits: u8) -> Input {
+        Input::from_bits(bits)
+    }
+
+    fn temp_path(name: &str) -> String {
+        std::env::temp_dir().join(format!("maze-{}-{}", std::process::id(), name)).to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn compresses_repeated_inputs() {
+        let mut replay = Replay::new(&campaign(1).levels[0]);
+        let ticks = [1, 1, 1, 0, 0, 9, 1, 1];
+        for bits in ticks {
+            replay.push(input(bits));
+        }
+        assert_eq!(replay.inputs, vec![(1, 3), (0, 2), (9, 1), (1, 2)]);
+        let bits: Vec<u8> = replay.inputs().into_iter().map(Input::bits).collect();
+        assert_eq!(bits, ticks);
+    }
+
+    #[test]
+    fn input_bits_round_trip() {
+        for bits in 0..64 {
+            assert_eq!(input(bits).bits(), bits);
+        }
+    }
+
+    #[test]
+    fn saves_and_loads_a_replay() {
+        let mut replay = Replay::new(&campaign(5).levels[0]);
+        replay.push(input(1));
+        replay.time = Some(12.5);
+        let path = temp_path("replay.json");
+        replay.save(&path).unwrap();
+        let loaded = Replay::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.level, "uno");
+        assert_eq!(loaded.time, Some(12.5));
+        assert_eq!(loaded.inputs, replay.inputs);
+        assert_eq!(loaded.level_index(&campaign(5)), Ok(0));
+        // La misma id con otra semilla ya es otro nivel
+        assert!(loaded.level_index(&campaign(6)).is_err());
+    }
+
+    #[test]
+    fn rejects_replays_from_another_version() {
+        let mut replay = Replay::new(&campaign(1).levels[0]);
+        replay.version = REPLAY_VERSION - 1;
+        let path = temp_path("old-replay.json");
+        replay.save(&path).unwrap();
+        let result = Replay::load(&path);
+        fs::remove_file(&path).unwrap();
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn simulation_is_deterministic() {
+        let campaign = campaign(3);
+        let mut replay = Replay::new(&campaign.levels[0]);
+        for tick in 0..120 {
+            replay.push(input(if tick % 40 < 30 { 1 } else { 8 }));
+        }
+        let (first, first_won) = replay.simulate(&campaign).unwrap();
+        let (second, second_won) = replay.simulate(&campaign).unwrap();
+        assert_eq!(first.ticks, 120);
+        assert_eq!(first_won, second_won);
+        assert_eq!((first.player.pos.x, first.player.pos.y), (second.player.pos.x, second.player.pos.y));
+        assert_eq!(first.player.a, second.player.a);
+    }
+}
@@ -0,0 +1,295 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+use nalgebra::Vector2;
+use crate::campaign::{Campaign, LevelEntry};
+use crate::enemy::Enemy;
+use crate::game::{GameState, TICK};
+use crate::items::{Item, ItemKind};
+use crate::maze::MazeError;
+use crate::scores::{self, RunStats};
+
+pub const QUICK_SAVE: &str = "saves/quicksave.json";
+pub const AUTO_SAVE: &str = "saves/autosave.json";
+// Ranuras que aparecen en el menú de carga
+pub const SLOTS: [(&str, &str); 2] = [("Guardado rápido", QUICK_SAVE), ("Al salir", AUTO_SAVE)];
+
+// Se incrementa cada vez que cambia el formato; las partidas de otra versión no se cargan
+const SAVE_VERSION: u32 = 3;
+
+#[derive(Debug)]
+pub enum SaveError {
+    Missing,
+    Corrupt(String),
+    Outdated(u32),
+    UnknownLevel(String),
+    LevelChanged(String),
+    InvalidLevel(MazeError),
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SaveError::Missing => write!(f, "No hay partida guardada"),
+            SaveError::Corrupt(e) => write!(f, "La partida está dañada: {}", e),
+            SaveError::Outdated(version) => write!(f, "La partida es de otra versión ({}, se esperaba {})", version, SAVE_VERSION),
+            SaveError::UnknownLevel(id) => write!(f, "El nivel {} ya no está en la campaña", id),
+            SaveError::LevelChanged(id) => write!(f, "El nivel {} cambió desde que se guardó", id),
+            SaveError::InvalidLevel(e) => write!(f, "El nivel no se pudo cargar: {}", e),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct EnemySave {
+    pub pos: [f32; 2],
+    pub home: [f32; 2],
+    pub facing: f32,
+    pub repelled: f32,
+    pub time: f32,
+    pub rate: f32,
+    pub phase: f32,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct DoorSave {
+    pub x: usize,
+    pub y: usize,
+    pub locked: bool,
+    pub open: f32,
+    pub opening: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ItemSave {
+    item: char, // Carácter del objeto en el mapa
+    x: f32,
+    y: f32,
+}
+
+// Lo que cambia en cada piso mientras se juega
+#[derive(Serialize, Deserialize)]
+struct FloorSave {
+    enemies: Vec<EnemySave>,
+    items: Vec<ItemSave>, // Objetos que siguen en el piso
+    doors: Vec<DoorSave>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct PlayerSave {
+    x: f32,
+    y: f32,
+    floor: usize,
+    angle: f32,
+    inventory: Vec<(char, u32)>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct StatsSave {
+    time: f32,
+    distance: f32,
+    items: u32,
+    damage: f32,
+}
+
+// Solo la versión, para rechazar formatos viejos antes de leer el resto
+#[derive(Deserialize)]
+struct SaveHeader {
+    version: u32,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SaveGame {
+    version: u32,
+    level: String,     // Id del nivel en la campaña
+    level_key: String, // Hash del archivo o semilla, para detectar si el nivel cambió
+    player: PlayerSave,
+    stats: StatsSave,
+    floors: Vec<FloorSave>,
+}
+
+impl SaveGame {
+    pub fn capture(level: &LevelEntry, state: &GameState) -> SaveGame {
+        let GameState { world, player, stats, .. } = state;
+        SaveGame {
+            version: SAVE_VERSION,
+            level: level.id.clone(),
+            level_key: level.key(),
+            player: PlayerSave {
+                x: player.pos.x,
+                y: player.pos.y,
+                floor: world.floor,
+                angle: player.a,
+                inventory: ItemKind::ALL.iter()
+                    .map(|&kind| (kind.cell(), player.inventory.count(kind)))
+                    .filter(|&(_, count)| count > 0)
+                    .collect(),
+            },
+            stats: StatsSave {
+                time: stats.time,
+                distance: stats.distance,
+                items: stats.items,
+                damage: stats.damage,
+            },
+            floors: world.floors.iter()
+                .map(|floor| FloorSave {
+                    enemies: floor.enemies.iter().map(Enemy::snapshot).collect(),
+                    items: floor.items.iter()
+                        .map(|item| ItemSave { item: item.kind.cell(), x: item.pos.x, y: item.pos.y })
+                        .collect(),
+                    doors: floor.doors.snapshot(),
+                })
+                .collect(),
+        }
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(dir) = Path::new(path).parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    pub fn load(path: &str) -> Result<SaveGame, SaveError> {
+        let contents = fs::read_to_string(path).map_err(|e| match e.kind() {
+            ErrorKind::NotFound => SaveError::Missing,
+            _ => SaveError::Corrupt(e.to_string()),
+        })?;
+
+        let header: SaveHeader = serde_json::from_str(&contents).map_err(|e| SaveError::Corrupt(e.to_string()))?;
+        if header.version != SAVE_VERSION {
+            return Err(SaveError::Outdated(header.version));
+        }
+        serde_json::from_str(&contents).map_err(|e| SaveError::Corrupt(e.to_string()))
+    }
+
+    // Descripción corta para el menú de carga
+    pub fn summary(&self, campaign: &Campaign) -> String {
+        let name = campaign.levels.iter()
+            .find(|level| level.id == self.level)
+            .map_or(self.level.as_str(), |level| level.name.as_str());
+        format!("{}  {}  Objetos: {}", name, scores::format_time(self.stats.time), self.stats.items)
+    }
+
+    // Reconstruye el nivel y le aplica el estado guardado; devuelve también su posición en la campaña
+    pub fn restore(&self, campaign: &Campaign) -> Result<(usize, GameState), SaveError> {
+        let index = campaign.levels.iter()
+            .position(|level| level.id == self.level)
+            .ok_or_else(|| SaveError::UnknownLevel(self.level.clone()))?;
+        let level = &campaign.levels[index];
+        if level.key() != self.level_key {
+            return Err(SaveError::LevelChanged(self.level.clone()));
+        }
+
+        let mut state = GameState::new(level).map_err(SaveError::InvalidLevel)?;
+        let GameState { world, player, stats, ticks, .. } = &mut state;
+
+        if self.floors.len() != world.floors.len() || self.player.floor >= world.floors.len() {
+            return Err(SaveError::LevelChanged(self.level.clone()));
+        }
+        for (floor, save) in world.floors.iter_mut().zip(&self.floors) {
+            if !floor.doors.restore(&save.doors) {
+                return Err(SaveError::LevelChanged(self.level.clone()));
+            }
+            floor.enemies = save.enemies.iter().map(Enemy::restore).collect();
+            floor.items = save.items.iter()
+                .map(|save| match ItemKind::from_cell(save.item) {
+                    Some(kind) => Ok(Item { kind, pos: Vector2::new(save.x, save.y) }),
+                    None => Err(SaveError::Corrupt(format!("objeto desconocido '{}'", save.item))),
+                })
+                .collect::<Result<_, _>>()?;
+        }
+
+        world.floor = self.player.floor;
+        player.pos = Vector2::new(self.player.x, self.player.y);
+        player.a = self.player.angle;
+        for &(cell, count) in &self.player.inventory {
+            let kind = ItemKind::from_cell(cell)
+                .ok_or_else(|| SaveError::Corrupt(format!("objeto desconocido '{}'", cell)))?;
+            player.inventory.set(kind, count);
+        }
+
+        *stats = RunStats {
+            time: self.stats.time,
+            distance: self.stats.distance,
+            items: self.stats.items,
+            damage: self.stats.damage,
+        };
+        *ticks = (self.stats.time / TICK).round() as u64;
+
+        Ok((index, state))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn campaign(id: &str, seed: u64) -> Campaign {
+        let json = format!(r#"{{"name": "Prueba", "levels": [{{"id": "{}", "name": "Uno", "generate": {{"width": 4, "height": 3, "seed": {}}}}}]}}"#, id, seed);
+        serde_json::from_str(&json).unwrap()
+    }
+
+    fn temp_path(name: &str) -> String {
+        std::env::temp_dir().join(format!("maze-{}-{}", std::process::id(), name)).to_string_lossy().into_owned()
+    }
+
+    // Guarda el contenido en un archivo temporal y lo carga
+    fn load_contents(name: &str, contents: &str) -> Result<SaveGame, SaveError> {
+        let path = temp_path(name);
+        fs::write(&path, contents).unwrap();
+        let result = SaveGame::load(&path);
+        fs::remove_file(&path).unwrap();
+        result
+    }
+
+    #[test]
+    fn saves_and_restores_the_player() {
+        let campaign = campaign("uno", 2);
+        let level = &campaign.levels[0];
+        let mut state = GameState::new(level).unwrap();
+        state.player.a = 1.25;
+        state.stats.time = 3.0;
+        state.stats.items = 2;
+
+        let path = temp_path("save.json");
+        SaveGame::capture(level, &state).save(&path).unwrap();
+        let loaded = SaveGame::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let (index, restored) = loaded.restore(&campaign).unwrap();
+        assert_eq!(index, 0);
+        assert_eq!(restored.player.pos, state.player.pos);
+        assert_eq!(restored.player.a, 1.25);
+        assert_eq!(restored.stats.items, 2);
+        assert_eq!(restored.ticks, (3.0 / TICK).round() as u64);
+        assert_eq!(loaded.summary(&campaign), format!("Uno  {}  Objetos: 2", scores::format_time(3.0)));
+    }
+
+    #[test]
+    fn rejects_saves_from_another_version() {
+        assert!(matches!(load_contents("v1.json", r#"{"version": 1}"#), Err(SaveError::Outdated(1))));
+        // La versión 2 guardaba la salud y no el contacto con fantasmas
+        assert!(matches!(load_contents("v2.json", r#"{"version": 2}"#), Err(SaveError::Outdated(2))));
+    }
+
+    #[test]
+    fn tells_missing_from_corrupt_saves() {
+        assert!(matches!(SaveGame::load(&temp_path("nothing.json")), Err(SaveError::Missing)));
+        assert!(matches!(load_contents("garbage.json", "no es json"), Err(SaveError::Corrupt(_))));
+        let incomplete = format!(r#"{{"version": {}}}"#, SAVE_VERSION);
+        assert!(matches!(load_contents("incomplete.json", &incomplete), Err(SaveError::Corrupt(_))));
+    }
+
+    #[test]
+    fn rejects_saves_of_another_level() {
+        let original = campaign("uno", 2);
+        let state = GameState::new(&original.levels[0]).unwrap();
+        let save = SaveGame::capture(&original.levels[0], &state);
+        assert!(matches!(save.restore(&campaign("otro", 2)), Err(SaveError::UnknownLevel(id)) if id == "uno"));
+        assert!(matches!(save.restore(&campaign("uno", 3)), Err(SaveError::LevelChanged(id)) if id == "uno"));
+    }
+}
@@ -0,0 +1,138 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub const SCORES_FILE: &str = "scores.json";
+const MAX_ENTRIES: usize = 10;   // Récords que se guardan por laberinto
+const STEP_LENGTH: f32 = 0.5;    // Distancia que cuenta como un paso
+
+// Estadísticas de la partida en curso
+#[derive(Default)]
+pub struct RunStats {
+    pub time: f32,      // Segundos desde que empezó el nivel
+    pub distance: f32,  // Distancia recorrida
+    pub items: u32,     // Objetos recogidos
+    pub damage: f32,    // Segundos que los fantasmas tocaron al jugador
+}
+
+impl RunStats {
+    pub fn steps(&self) -> u32 {
+        (self.distance / STEP_LENGTH) as u32
+    }
+
+    // Más rápido, con más objetos y menos contacto con fantasmas es mejor
+    pub fn score(&self) -> u32 {
+        let time_bonus = (5000.0 - self.time * 20.0).max(0.0);
+        let item_bonus = self.items as f32 * 250.0;
+        let damage_penalty = self.damage * 100.0;
+        (time_bonus + item_bonus - damage_penalty).max(0.0) as u32
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ScoreEntry {
+    pub score: u32,
+    pub time: f32,
+    pub steps: u32,
+    pub items: u32,
+    #[serde(default)] // Los récords anteriores no lo tenían
+    pub damage: f32,
+    pub date: u64, // Segundos desde 1970, para desempatar y mostrar el récord más reciente
+}
+
+impl ScoreEntry {
+    pub fn from_run(stats: &RunStats) -> ScoreEntry {
+        let date = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+
+        ScoreEntry {
+            score: stats.score(),
+            time: stats.time,
+            steps: stats.steps(),
+            items: stats.items,
+            damage: stats.damage,
+            date,
+        }
+    }
+}
+
+// Tabla de récords por laberinto, identificado por el hash de su contenido o por su semilla
+#[derive(Serialize, Deserialize, Default)]
+pub struct HighScores {
+    tables: HashMap<String, Vec<ScoreEntry>>,
+}
+
+impl HighScores {
+    // Un archivo que falta o está dañado equivale a no tener récords
+    pub fn load(path: &str) -> HighScores {
+        match fs::read_to_string(path) {
+            Ok(contents) => serde_json::from_str(&contents).unwrap_or_else(|e| {
+                eprintln!("No se pudieron leer los récords de {}: {}", path, e);
+                HighScores::default()
+            }),
+            Err(_) => HighScores::default(),
+        }
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    pub fn top(&self, maze_key: &str) -> &[ScoreEntry] {
+        self.tables.get(maze_key).map(Vec::as_slice).unwrap_or(&[])
+    }
+
+    // Agrega el resultado y devuelve su posición en la tabla (0 = mejor), si entró
+    pub fn record(&mut self, maze_key: &str, entry: ScoreEntry) -> Option<usize> {
+        let table = self.tables.entry(maze_key.to_string()).or_default();
+        let position = table.iter().position(|e| entry.score > e.score).unwrap_or(table.len());
+        if position >= MAX_ENTRIES {
+            return None;
+        }
+
+        table.insert(position, entry);
+        table.truncate(MAX_ENTRIES);
+        Some(position)
+    }
+}
+
+// Identificador estable de un laberinto a partir de su contenido (FNV-1a de 64 bits)
+pub fn maze_key(contents: &str) -> String {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in contents.bytes() {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    format!("{:016x}", hash)
+}
+
+// Formato mm:ss.d para los tiempos
+pub fn format_time(seconds: f32) -> String {
+    let minutes = (seconds / 60.0) as u32;
+    let rest = seconds - minutes as f32 * 60.0;
+    format!("{:02}:{:04.1}", minutes, rest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ghost_contact_lowers_the_score() {
+        let clean = RunStats { time: 10.0, distance: 0.0, items: 2, damage: 0.0 };
+        let touched = RunStats { damage: 3.0, ..clean };
+        assert_eq!(clean.score(), 4800 + 500);
+        assert_eq!(touched.score(), 4800 + 500 - 300);
+        assert_eq!(RunStats { damage: 100.0, ..clean }.score(), 0);
+    }
+
+    #[test]
+    fn reads_records_saved_without_damage() {
+        let entry: ScoreEntry = serde_json::from_str(r#"{"score": 10, "time": 1.0, "steps": 2, "items": 0, "date": 0}"#).unwrap();
+        assert_eq!(entry.damage, 0.0);
+    }
+}
@@ -0,0 +1,165 @@
+use nalgebra::Vector2;
+use serde::{Deserialize, Serialize};
+use crate::analysis::{Pos, Step};
+
+// Pared delgada entre dos puntos del mapa, en unidades de celda: sobre el borde de una celda
+// ("from": [2, 1], "to": [2, 3]) o en diagonal. No ocupa celdas, así que a los dos lados
+// se puede caminar. `wall` es el carácter de la paleta que le da textura y altura
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct Segment {
+    pub from: [f32; 2],
+    pub to: [f32; 2],
+    #[serde(default = "default_segment_wall")]
+    pub wall: char,
+}
+
+pub fn default_segment_wall() -> char {
+    '|'
+}
+
+// Qué tan cerca de una pared delgada puede quedar el jugador
+const WALL_MARGIN: f32 = 0.05;
+
+fn cross(a: Vector2<f32>, b: Vector2<f32>) -> f32 {
+    a.x * b.y - a.y * b.x
+}
+
+fn center((x, y): Pos) -> Vector2<f32> {
+    Vector2::new(x as f32 + 0.5, y as f32 + 0.5)
+}
+
+impl Segment {
+    pub fn start(&self) -> Vector2<f32> {
+        Vector2::new(self.from[0], self.from[1])
+    }
+
+    pub fn end(&self) -> Vector2<f32> {
+        Vector2::new(self.to[0], self.to[1])
+    }
+
+    pub fn length(&self) -> f32 {
+        (self.end() - self.start()).norm()
+    }
+
+    // Dónde corta el rayo a la pared: la distancia a lo largo del rayo (en unidades de `dir`)
+    // y la posición sobre la pared, de 0.0 en `from` a 1.0 en `to`
+    pub fn intersect(&self, origin: Vector2<f32>, dir: Vector2<f32>) -> Option<(f32, f32)> {
+        let edge = self.end() - self.start();
+        let denom = cross(dir, edge);
+        if denom.abs() < f32::EPSILON {
+            return None; // Paralelos
+        }
+        let offset = self.start() - origin;
+        let t = cross(offset, edge) / denom;
+        let along = cross(offset, dir) / denom;
+        (t > 0.0 && (0.0..=1.0).contains(&along)).then_some((t, along))
+    }
+
+    // Distancia más corta desde un punto hasta la pared
+    pub fn distance_to(&self, point: Vector2<f32>) -> f32 {
+        let edge = self.end() - self.start();
+        let along = ((point - self.start()).dot(&edge) / edge.norm_squared()).clamp(0.0, 1.0);
+        (self.start() + edge * along - point).norm()
+    }
+
+    // Si la pared corta la línea entre dos puntos
+    pub fn crosses(&self, from: Vector2<f32>, to: Vector2<f32>) -> bool {
+        self.intersect(from, to - from).is_some_and(|(t, _)| t <= 1.0)
+    }
+
+    // Si la pared impide moverse de `from` a `to`: la cruza, o se acerca demasiado a ella
+    // (alejarse siempre se puede, para no quedar atrapado junto a la pared)
+    pub fn blocks(&self, from: Vector2<f32>, to: Vector2<f32>) -> bool {
+        let distance = self.distance_to(to);
+        self.crosses(from, to) || (distance < WALL_MARGIN && distance < self.distance_to(from))
+    }
+
+    // Si la pared separa dos celdas vecinas, cortando la línea entre sus centros
+    pub fn separates(&self, a: Pos, b: Pos) -> bool {
+        self.crosses(center(a), center(b))
+    }
+
+    // Pares de celdas vecinas del piso que la pared separa, para los recorridos del laberinto
+    pub fn cuts(&self, floor: usize) -> Vec<(Step, Step)> {
+        let (x0, x1) = (self.from[0].min(self.to[0]), self.from[0].max(self.to[0]));
+        let (y0, y1) = (self.from[1].min(self.to[1]), self.from[1].max(self.to[1]));
+        let mut cuts = Vec::new();
+        for y in (y0 - 1.0).max(0.0) as usize..=y1.max(0.0) as usize {
+            for x in (x0 - 1.0).max(0.0) as usize..=x1.max(0.0) as usize {
+                for next in [(x + 1, y), (x, y + 1)] {
+                    if self.separates((x, y), next) {
+                        cuts.push(((floor, (x, y)), (floor, next)));
+                    }
+                }
+            }
+        }
+        cuts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn segment(from: [f32; 2], to: [f32; 2]) -> Segment {
+        Segment { from, to, wall: '|' }
+    }
+
+    fn point(x: f32, y: f32) -> Vector2<f32> {
+        Vector2::new(x, y)
+    }
+
+    #[test]
+    fn intersects_a_ray_in_front() {
+        let wall = segment([2.0, 0.0], [2.0, 2.0]);
+        assert_eq!(wall.intersect(point(0.0, 1.0), point(1.0, 0.0)), Some((2.0, 0.5)));
+        assert_eq!(wall.intersect(point(0.0, 1.0), point(0.5, 0.0)), Some((4.0, 0.5)));
+        // Detrás del origen o al costado de la pared no hay corte
+        assert_eq!(wall.intersect(point(3.0, 1.0), point(1.0, 0.0)), None);
+        assert_eq!(wall.intersect(point(0.0, 3.0), point(1.0, 0.0)), None);
+    }
+
+    #[test]
+    fn parallel_and_collinear_rays_miss() {
+        let wall = segment([2.0, 0.0], [2.0, 2.0]);
+        assert_eq!(wall.intersect(point(1.0, 0.0), point(0.0, 1.0)), None);
+        assert_eq!(wall.intersect(point(2.0, -1.0), point(0.0, 1.0)), None);
+    }
+
+    #[test]
+    fn hits_the_endpoints() {
+        let wall = segment([2.0, 0.0], [2.0, 2.0]);
+        assert_eq!(wall.intersect(point(0.0, 0.0), point(1.0, 0.0)), Some((2.0, 0.0)));
+        assert_eq!(wall.intersect(point(0.0, 2.0), point(1.0, 0.0)), Some((2.0, 1.0)));
+        assert_eq!(wall.distance_to(point(2.0, 3.0)), 1.0);
+        assert_eq!(wall.distance_to(point(1.5, 1.0)), 0.5);
+    }
+
+    #[test]
+    fn blocks_crossing_and_approaching_but_not_leaving() {
+        let wall = segment([2.0, 0.0], [2.0, 2.0]);
+        assert!(!wall.crosses(point(0.5, 1.0), point(1.5, 1.0)));
+        assert!(wall.crosses(point(1.5, 1.0), point(2.5, 1.0)));
+        assert!(wall.blocks(point(1.5, 1.0), point(2.5, 1.0)));
+        assert!(wall.blocks(point(1.9, 1.0), point(1.97, 1.0)));
+        assert!(!wall.blocks(point(1.97, 1.0), point(1.9, 1.0)));
+        assert!(!wall.blocks(point(1.0, 1.0), point(1.5, 1.0)));
+    }
+
+    #[test]
+    fn cuts_the_cells_on_both_sides_of_an_edge() {
+        let wall = segment([2.0, 2.0], [2.0, 0.0]);
+        assert!(wall.separates((1, 0), (2, 0)));
+        assert!(!wall.separates((1, 2), (2, 2)));
+        assert_eq!(wall.cuts(3), vec![((3, (1, 0)), (3, (2, 0))), ((3, (1, 1)), (3, (2, 1)))]);
+    }
+
+    #[test]
+    fn cuts_the_cells_a_diagonal_goes_through() {
+        // Con pendiente 1/2 no pasa por ningún centro: separa la celda (1, 0) de sus vecinas
+        // de la izquierda y de abajo
+        let wall = segment([0.0, 0.0], [2.0, 1.0]);
+        assert_eq!(wall.cuts(0), vec![((0, (0, 0)), (0, (1, 0))), ((0, (1, 0)), (0, (1, 1)))]);
+        assert_eq!(wall.length(), 5.0f32.sqrt());
+    }
+}
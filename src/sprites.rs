@@ -0,0 +1,156 @@
+use std::f32::consts::PI;
+use crate::textures::Texture;
+
+// Hoja de sprites: una sola imagen con varios cuadros del mismo tamaño
+pub struct SpriteSheet {
+    texture: Texture,
+    pub frame_width: u32,
+    pub frame_height: u32,
+    frames: Vec<(u32, u32)>, // Esquina superior izquierda de cada cuadro dentro de la imagen
+}
+
+impl SpriteSheet {
+    // Divide la imagen en una cuadrícula de `columns` x `rows`, numerando los cuadros por filas.
+    // Falla si la imagen no se puede leer o es más chica que la cuadrícula
+    pub fn from_grid(file_path: &str, columns: u32, rows: u32) -> Result<SpriteSheet, String> {
+        if columns == 0 || rows == 0 {
+            return Err(format!("{}: la hoja necesita al menos una columna y una fila", file_path));
+        }
+        let texture = Texture::load(file_path).map_err(|e| format!("{}: {}", file_path, e))?;
+        let frame_width = texture.width / columns;
+        let frame_height = texture.height / rows;
+
+        let mut frames = Vec::new();
+        for row in 0..rows {
+            for col in 0..columns {
+                frames.push((col * frame_width, row * frame_height));
+            }
+        }
+
+        SpriteSheet::from_frames(texture, frame_width, frame_height, frames).map_err(|e| format!("{}: {}", file_path, e))
+    }
+
+    // Cuadros en cualquier lugar de la imagen, dados por su esquina superior izquierda, para
+    // hojas que no son una cuadrícula pareja. Los que se salen de la imagen se descartan
+    pub fn from_frames(texture: Texture, frame_width: u32, frame_height: u32, frames: Vec<(u32, u32)>) -> Result<SpriteSheet, String> {
+        if frame_width == 0 || frame_height == 0 {
+            return Err(format!(
+                "los cuadros medirían {}x{} píxeles en una imagen de {}x{}",
+                frame_width, frame_height, texture.width, texture.height
+            ));
+        }
+        let frames: Vec<(u32, u32)> = frames
+            .into_iter()
+            .filter(|&(x, y)| x + frame_width <= texture.width && y + frame_height <= texture.height)
+            .collect();
+        let frames = if frames.is_empty() { vec![(0, 0)] } else { frames };
+        Ok(SpriteSheet { texture, frame_width, frame_height, frames })
+    }
+
+    // Color del píxel (x, y) relativo al cuadro indicado
+    pub fn get_pixel_color(&self, frame: usize, x: u32, y: u32) -> u32 {
+        let (fx, fy) = self.frames[frame % self.frames.len()];
+        self.texture.get_pixel_color(fx + x % self.frame_width, fy + y % self.frame_height)
+    }
+}
+
+// Secuencia de cuadros de una hoja que se reproduce a una velocidad fija
+pub struct AnimationClip {
+    pub frames: Vec<usize>, // Cuadros de la dirección 0 (de frente a la cámara)
+    pub fps: f32,
+    pub directions: usize,       // 1 para sprites sin orientación, 8 para sprites con ángulos
+    pub direction_stride: usize, // Distancia en cuadros entre una dirección y la siguiente
+}
+
+impl AnimationClip {
+    pub fn new(frames: Vec<usize>, fps: f32) -> AnimationClip {
+        AnimationClip {
+            frames,
+            fps,
+            directions: 1,
+            direction_stride: 0,
+        }
+    }
+
+    // Cada dirección ocupa `stride` cuadros en la hoja (p. ej. una fila por dirección)
+    pub fn directional(mut self, directions: usize, stride: usize) -> AnimationClip {
+        self.directions = directions.max(1);
+        self.direction_stride = stride;
+        self
+    }
+
+    // Cuadro de la hoja para un tiempo de reproducción y un ángulo relativo de visión.
+    // `view_angle` es el ángulo entre hacia dónde mira la entidad y la dirección hacia la cámara:
+    // 0 significa que la entidad mira a la cámara.
+    pub fn frame_at(&self, time: f32, view_angle: f32) -> usize {
+        if self.frames.is_empty() {
+            return 0;
+        }
+
+        let index = (time.max(0.0) * self.fps) as usize % self.frames.len();
+        self.frames[index] + direction_index(view_angle, self.directions) * self.direction_stride
+    }
+}
+
+// Elige entre `directions` sectores iguales el más cercano al ángulo dado
+pub fn direction_index(view_angle: f32, directions: usize) -> usize {
+    if directions <= 1 {
+        return 0;
+    }
+
+    let sector = 2.0 * PI / directions as f32;
+    let angle = view_angle.rem_euclid(2.0 * PI);
+    ((angle / sector).round() as usize) % directions
+}
+
+// Estado de reproducción propio de cada entidad
+pub struct Animator {
+    pub time: f32,
+    pub rate: f32, // Multiplicador de velocidad (1.0 = velocidad del clip)
+}
+
+impl Animator {
+    pub fn new(rate: f32) -> Animator {
+        Animator { time: 0.0, rate }
+    }
+
+    pub fn update(&mut self, dt: f32) {
+        self.time += dt * self.rate;
+    }
+
+    pub fn frame(&self, clip: &AnimationClip, view_angle: f32) -> usize {
+        clip.frame_at(self.time, view_angle)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_a_sheet_into_a_grid() {
+        let sheet = SpriteSheet::from_grid("sprites/ghastSheet.png", 4, 8).unwrap();
+        assert_eq!((sheet.frame_width, sheet.frame_height), (128, 128));
+        assert_eq!(sheet.frames.len(), 32);
+        assert_eq!(sheet.frames[5], (128, 128));
+    }
+
+    #[test]
+    fn rejects_empty_grids_and_frames() {
+        assert!(SpriteSheet::from_grid("sprites/ghastSheet.png", 0, 8).is_err());
+        assert!(SpriteSheet::from_grid("sprites/ghastSheet.png", 4, 0).is_err());
+        // 64 píxeles no alcanzan para 100 columnas
+        assert!(SpriteSheet::from_grid("sprites/entrenadorSprite.png", 100, 1).is_err());
+        assert!(SpriteSheet::from_grid("sprites/no-existe.png", 1, 1).is_err());
+
+        let texture = Texture::load("sprites/entrenadorSprite.png").unwrap();
+        assert!(SpriteSheet::from_frames(texture, 0, 16, vec![(0, 0)]).is_err());
+    }
+
+    #[test]
+    fn drops_frames_outside_the_image() {
+        let texture = Texture::load("sprites/entrenadorSprite.png").unwrap();
+        let sheet = SpriteSheet::from_frames(texture, 32, 32, vec![(0, 0), (32, 64), (48, 0)]).unwrap();
+        assert_eq!(sheet.frames, vec![(0, 0), (32, 64)]);
+    }
+}
@@ -0,0 +1,263 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use once_cell::sync::Lazy;
+use rusttype::{point, Font, Scale};
+use crate::textures::blend_color;
+
+// La fuente del juego se lee una sola vez
+static FONT: Lazy<Font<'static>> = Lazy::new(|| {
+    Font::try_from_bytes(include_bytes!("../assets/PressStart2P.ttf")).expect("La fuente PressStart2P no es válida")
+});
+
+// Letras ya dibujadas, por carácter y tamaño (los bits del f32): el texto del HUD se repite
+// en cada cuadro
+type GlyphCache = HashMap<(char, u32), Arc<Glyph>>;
+static GLYPHS: Lazy<Mutex<GlyphCache>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+// Espacio entre líneas, relativo a la altura de la fuente
+const LINE_SPACING: f32 = 1.5;
+
+// Una letra rasterizada: cuánto cubre cada píxel (0 a 255) y dónde va respecto del punto
+// de partida sobre la línea base
+struct Glyph {
+    left: i32,
+    top: i32,
+    width: usize,
+    coverage: Vec<u8>,
+    advance: f32,
+}
+
+fn glyph(c: char, size: f32) -> Arc<Glyph> {
+    let mut cache = GLYPHS.lock().unwrap();
+    cache
+        .entry((c, size.to_bits()))
+        .or_insert_with(|| {
+            let glyph = FONT.glyph(c).scaled(Scale::uniform(size));
+            let advance = glyph.h_metrics().advance_width;
+            let glyph = glyph.positioned(point(0.0, 0.0));
+            let Some(bb) = glyph.pixel_bounding_box() else {
+                return Arc::new(Glyph { left: 0, top: 0, width: 0, coverage: Vec::new(), advance });
+            };
+
+            let width = bb.width() as usize;
+            let mut coverage = vec![0; width * bb.height() as usize];
+            glyph.draw(|x, y, v| coverage[y as usize * width + x as usize] = (v * 255.0).round() as u8);
+            Arc::new(Glyph { left: bb.min.x, top: bb.min.y, width, coverage, advance })
+        })
+        .clone()
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Align {
+    Left,
+    Center, // `x` es el centro de cada línea
+}
+
+// Tamaño, color y forma de un texto:
+//
+//   TextStyle::new(16.0, 0xFFD700).centered().shadow(0x000000).wrap(600.0)
+#[derive(Clone, Copy, Debug)]
+pub struct TextStyle {
+    pub size: f32,
+    pub color: u32,
+    pub align: Align,
+    pub shadow: Option<u32>,
+    pub max_width: Option<f32>, // Partir las líneas más anchas en varias, por palabras
+}
+
+impl TextStyle {
+    pub fn new(size: f32, color: u32) -> TextStyle {
+        TextStyle { size, color, align: Align::Left, shadow: None, max_width: None }
+    }
+
+    pub fn centered(mut self) -> TextStyle {
+        self.align = Align::Center;
+        self
+    }
+
+    // Una copia del texto en este color, corrida hacia abajo y a la derecha
+    pub fn shadow(mut self, color: u32) -> TextStyle {
+        self.shadow = Some(color);
+        self
+    }
+
+    pub fn wrap(mut self, max_width: f32) -> TextStyle {
+        self.max_width = Some(max_width);
+        self
+    }
+
+    // Distancia entre la parte de arriba de una línea y la de la siguiente
+    pub fn line_height(&self) -> f32 {
+        let v_metrics = FONT.v_metrics(Scale::uniform(self.size));
+        (v_metrics.ascent - v_metrics.descent + v_metrics.line_gap) * LINE_SPACING
+    }
+
+    // Las líneas que se dibujan, ya partidas
+    pub fn lines(&self, text: &str) -> Vec<String> {
+        match self.max_width {
+            Some(max_width) => wrap_text(text, self.size, max_width),
+            None => text.lines().map(str::to_string).collect(),
+        }
+    }
+
+    // Ancho y alto del texto en píxeles, como se dibuja con este estilo
+    pub fn measure(&self, text: &str) -> (f32, f32) {
+        let lines = self.lines(text);
+        let width = lines.iter().map(|line| text_width(line, self.size)).fold(0.0, f32::max);
+        (width, lines.len() as f32 * self.line_height())
+    }
+}
+
+// Ancho de una línea en píxeles, sumando el avance de cada carácter (no de cada byte)
+pub fn text_width(line: &str, size: f32) -> f32 {
+    let scale = Scale::uniform(size);
+    let mut previous = None;
+    let mut width = 0.0;
+    for c in line.chars() {
+        let id = FONT.glyph(c).id();
+        if let Some(previous) = previous {
+            width += FONT.pair_kerning(scale, previous, id);
+        }
+        width += glyph(c, size).advance;
+        previous = Some(id);
+    }
+    width
+}
+
+// Parte el texto en líneas que no pasen de `max_width`, cortando entre palabras. Los saltos
+// de línea del texto se respetan, y una palabra que no cabe sola se corta donde llegue
+pub fn wrap_text(text: &str, size: f32, max_width: f32) -> Vec<String> {
+    let mut lines = Vec::new();
+    for paragraph in text.lines() {
+        let mut line = String::new();
+        for word in paragraph.split_whitespace() {
+            let candidate = if line.is_empty() { word.to_string() } else { format!("{} {}", line, word) };
+            if text_width(&candidate, size) <= max_width {
+                line = candidate;
+                continue;
+            }
+            if !line.is_empty() {
+                lines.push(std::mem::take(&mut line));
+            }
+            for c in word.chars() {
+                line.push(c);
+                if text_width(&line, size) > max_width && line.chars().count() > 1 {
+                    line.pop();
+                    lines.push(std::mem::replace(&mut line, c.to_string()));
+                }
+            }
+        }
+        lines.push(line);
+    }
+    lines
+}
+
+// Dibuja el texto con la esquina de arriba a la izquierda en (x, y), o centrado en `x`,
+// mezclando los bordes de las letras con el fondo. Devuelve el alto que ocupó
+pub fn draw_text(framebuffer: &mut [u32], width: usize, text: &str, x: i32, y: i32, style: TextStyle) -> usize {
+    let ascent = FONT.v_metrics(Scale::uniform(style.size)).ascent;
+    let line_height = style.line_height();
+    let lines = style.lines(text);
+
+    for (i, line) in lines.iter().enumerate() {
+        let left = match style.align {
+            Align::Left => x as f32,
+            Align::Center => x as f32 - text_width(line, style.size) / 2.0,
+        };
+        let baseline = y as f32 + i as f32 * line_height + ascent;
+
+        if let Some(shadow) = style.shadow {
+            let offset = (style.size / 10.0).round().max(1.0);
+            draw_line(framebuffer, width, line, left + offset, baseline + offset, style.size, shadow);
+        }
+        draw_line(framebuffer, width, line, left, baseline, style.size, style.color);
+    }
+    (lines.len() as f32 * line_height).round() as usize
+}
+
+fn draw_line(framebuffer: &mut [u32], width: usize, line: &str, x: f32, baseline: f32, size: f32, color: u32) {
+    let height = framebuffer.len() / width;
+    let scale = Scale::uniform(size);
+    let mut pen = x;
+    let mut previous = None;
+
+    for c in line.chars() {
+        let id = FONT.glyph(c).id();
+        if let Some(previous) = previous {
+            pen += FONT.pair_kerning(scale, previous, id);
+        }
+        previous = Some(id);
+
+        let glyph = glyph(c, size);
+        let (gx, gy) = (pen.round() as i32 + glyph.left, baseline.round() as i32 + glyph.top);
+        for (row, coverage) in glyph.coverage.chunks(glyph.width.max(1)).enumerate() {
+            let py = gy + row as i32;
+            if py < 0 || py as usize >= height {
+                continue;
+            }
+            for (col, &alpha) in coverage.iter().enumerate() {
+                let px = gx + col as i32;
+                if alpha == 0 || px < 0 || px as usize >= width {
+                    continue;
+                }
+                let pixel = &mut framebuffer[py as usize * width + px as usize];
+                *pixel = if alpha == u8::MAX { color } else { blend_color(color, *pixel, alpha as f32 / 255.0) };
+            }
+        }
+        pen += glyph.advance;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIZE: f32 = 16.0;
+
+    // La fuente es de ancho fijo: todos los caracteres avanzan lo mismo
+    fn chars(count: f32) -> f32 {
+        text_width("a", SIZE) * count
+    }
+
+    fn wrap(text: &str, max_chars: f32) -> Vec<String> {
+        wrap_text(text, SIZE, chars(max_chars))
+    }
+
+    #[test]
+    fn measures_by_char_not_by_byte() {
+        assert_eq!(text_width("", SIZE), 0.0);
+        assert!(chars(1.0) > 0.0);
+        assert_eq!(text_width("ñandú", SIZE), text_width("nandu", SIZE));
+        assert_eq!(text_width("ñandú", SIZE), chars(5.0));
+    }
+
+    #[test]
+    fn keeps_line_breaks() {
+        assert_eq!(wrap("uno\ndos", 100.0), vec!["uno", "dos"]);
+        assert_eq!(wrap("uno\n\ndos", 100.0), vec!["uno", "", "dos"]);
+    }
+
+    #[test]
+    fn breaks_between_words() {
+        assert_eq!(wrap("uno dos tres", 7.5), vec!["uno dos", "tres"]);
+        assert_eq!(wrap("uno   dos", 3.5), vec!["uno", "dos"]);
+    }
+
+    #[test]
+    fn splits_words_longer_than_a_line() {
+        assert_eq!(wrap("abcdefghij", 4.5), vec!["abcd", "efgh", "ij"]);
+        assert_eq!(wrap("ab abcdefgh x", 4.5), vec!["ab", "abcd", "efgh", "x"]);
+        assert_eq!(wrap("ñññññ", 2.5), vec!["ññ", "ññ", "ñ"]);
+        // Aunque no quepa ni un carácter, cada línea lleva al menos uno
+        assert_eq!(wrap("abc", 0.5), vec!["a", "b", "c"]);
+    }
+
+    #[test]
+    fn measures_wrapped_text() {
+        let style = TextStyle::new(SIZE, 0xFFFFFF).wrap(chars(4.5));
+        let (width, height) = style.measure("abcdefghij");
+        assert_eq!(width, chars(4.0));
+        assert_eq!(height, 3.0 * style.line_height());
+        assert_eq!(TextStyle::new(SIZE, 0xFFFFFF).lines("abcdefghij"), vec!["abcdefghij"]);
+    }
+}
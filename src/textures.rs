@@ -1,36 +1,304 @@
 extern crate image;
 
-use image::{ImageReader, Pixel};
-use image::{DynamicImage, GenericImageView};
+use image::{ImageError, ImageReader, Pixel};
+use image::GenericImageView;
+
+// Cómo se leen los píxeles de una pared: el más cercano (pixelado, como siempre) o la mezcla
+// de los cuatro vecinos (suavizado)
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub enum Filter {
+    #[default]
+    Nearest,
+    Bilinear,
+}
+
+// La imagen a un tamaño dado. Los píxeles se guardan por columnas en un solo arreglo: los de
+// una columna (x fija, y de arriba hacia abajo) quedan seguidos, que es como se recorren al
+// dibujar una pared
+pub struct MipLevel {
+    width: u32,
+    height: u32,
+    colors: Vec<u32>,
+    alphas: Option<Vec<u8>>, // Solo en las imágenes con píxeles transparentes
+}
+
+// Índice dentro del rango, repitiendo la textura. Con tamaños potencia de dos basta una máscara
+fn wrap(value: u32, size: u32) -> u32 {
+    if size.is_power_of_two() {
+        value & (size - 1)
+    } else {
+        value % size
+    }
+}
+
+// Píxel en la coordenada `t` (la imagen mide 1.0) de una imagen de `size` píxeles, repitiendo la
+// textura también hacia los negativos
+#[inline]
+fn texel(t: f32, size: u32) -> u32 {
+    let index = (t * size as f32).floor() as i32;
+    if size.is_power_of_two() {
+        index as u32 & (size - 1)
+    } else {
+        index.rem_euclid(size as i32) as u32
+    }
+}
+
+// Mezcla `color` sobre `background` con la opacidad dada (0.0 a 1.0)
+pub fn blend_color(color: u32, background: u32, alpha: f32) -> u32 {
+    let alpha = alpha.clamp(0.0, 1.0);
+    let mix = |shift: u32| {
+        let c = ((color >> shift) & 0xFF) as f32;
+        let b = ((background >> shift) & 0xFF) as f32;
+        ((c * alpha + b * (1.0 - alpha)) as u32) << shift
+    };
+    mix(16) | mix(8) | mix(0)
+}
+
+fn channel(color: u32, shift: u32) -> f32 {
+    ((color >> shift) & 0xFF) as f32
+}
+
+// Una columna de la imagen, seguida en memoria. Al dibujar una stake de pared se busca una
+// sola vez y después cada píxel es una lectura directa
+pub struct Column<'a> {
+    colors: &'a [u32],
+    alphas: Option<&'a [u8]>,
+}
+
+impl Column<'_> {
+    // Color y opacidad a la altura `v`, de 0.0 (arriba) a 1.0 (abajo)
+    #[inline]
+    pub fn pixel(&self, v: f32) -> (u32, u8) {
+        let height = self.colors.len() as u32;
+        let y = texel(v, height) as usize;
+        (self.colors[y], self.alphas.map_or(u8::MAX, |alphas| alphas[y]))
+    }
+}
+
+impl MipLevel {
+    #[inline]
+    fn index(&self, x: u32, y: u32) -> usize {
+        wrap(x, self.width) as usize * self.height as usize + wrap(y, self.height) as usize
+    }
+
+    #[inline]
+    fn pixel(&self, x: u32, y: u32) -> (u32, u8) {
+        let index = self.index(x, y);
+        let alpha = self.alphas.as_ref().map_or(u8::MAX, |alphas| alphas[index]);
+        (self.colors[index], alpha)
+    }
+
+    // La columna en `u`, de 0.0 a 1.0 (se repite fuera de ese rango)
+    #[inline]
+    pub fn column(&self, u: f32) -> Column<'_> {
+        let height = self.height as usize;
+        let start = texel(u, self.width) as usize * height;
+        Column {
+            colors: &self.colors[start..start + height],
+            alphas: self.alphas.as_ref().map(|alphas| &alphas[start..start + height]),
+        }
+    }
+
+    // Color y opacidad en el punto (u, v), de 0.0 a 1.0 (se repite fuera de ese rango)
+    #[inline]
+    pub fn sample(&self, u: f32, v: f32, filter: Filter) -> (u32, u8) {
+        match filter {
+            Filter::Nearest => self.column(u).pixel(v),
+            Filter::Bilinear => {
+                // Los centros de los píxeles están en las mitades. Se suma un ancho entero para
+                // no truncar números negativos en el borde de la izquierda y de arriba
+                let x = u.rem_euclid(1.0) * self.width as f32 - 0.5 + self.width as f32;
+                let y = v.rem_euclid(1.0) * self.height as f32 - 0.5 + self.height as f32;
+                let (x0, y0) = (x as u32, y as u32);
+                let (fx, fy) = (x - x0 as f32, y - y0 as f32);
+                let corners = [
+                    (self.pixel(x0, y0), (1.0 - fx) * (1.0 - fy)),
+                    (self.pixel(x0 + 1, y0), fx * (1.0 - fy)),
+                    (self.pixel(x0, y0 + 1), (1.0 - fx) * fy),
+                    (self.pixel(x0 + 1, y0 + 1), fx * fy),
+                ];
+
+                // Igual que en las copias chicas, los píxeles transparentes no aportan color
+                let alpha: f32 = corners.iter().map(|&((_, alpha), weight)| alpha as f32 * weight).sum();
+                let mix = |shift: u32| {
+                    let value: f32 = corners.iter().map(|&((color, alpha), weight)| channel(color, shift) * weight * alpha as f32).sum();
+                    if alpha > 0.0 { ((value / alpha + 0.5) as u32).min(0xFF) << shift } else { 0 }
+                };
+                (mix(16) | mix(8) | mix(0), (alpha + 0.5) as u8)
+            }
+        }
+    }
+
+    // La mitad de tamaño, promediando cada bloque de 2x2. Los píxeles transparentes no aportan
+    // color, para que los bordes de una reja no se oscurezcan a lo lejos
+    fn half(&self) -> MipLevel {
+        let (width, height) = ((self.width / 2).max(1), (self.height / 2).max(1));
+        let mut colors = Vec::with_capacity((width * height) as usize);
+        let mut alphas = Vec::with_capacity((width * height) as usize);
+
+        for x in 0..width {
+            for y in 0..height {
+                let mut sum = [0.0; 3];
+                let mut alpha_sum = 0.0;
+                let mut count = 0.0;
+                for (dx, dy) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+                    let (sx, sy) = ((x * 2 + dx).min(self.width - 1), (y * 2 + dy).min(self.height - 1));
+                    let (color, alpha) = self.pixel(sx, sy);
+                    let weight = alpha as f32 / 255.0;
+                    for (sum, shift) in sum.iter_mut().zip([16, 8, 0]) {
+                        *sum += channel(color, shift) * weight;
+                    }
+                    alpha_sum += weight;
+                    count += 1.0;
+                }
+                let mix = |sum: f32| if alpha_sum > 0.0 { (sum / alpha_sum) as u32 } else { 0 };
+                colors.push((mix(sum[0]) << 16) | (mix(sum[1]) << 8) | mix(sum[2]));
+                alphas.push((alpha_sum / count * 255.0).round() as u8);
+            }
+        }
+
+        let alphas = self.alphas.is_some().then_some(alphas);
+        MipLevel { width, height, colors, alphas }
+    }
+}
 
 pub struct Texture {
     pub width: u32,
     pub height: u32,
-    pub color_array: Vec<Vec<u32>>,
+    levels: Vec<MipLevel>, // La imagen original y sus copias a la mitad, hasta 1x1
 }
 
 impl Texture {
     pub fn new(file_path: &str) -> Texture {
-        let img = ImageReader::open(file_path).unwrap().decode().unwrap();
+        Texture::load(file_path).unwrap()
+    }
+
+    pub fn load(file_path: &str) -> Result<Texture, ImageError> {
+        let img = ImageReader::open(file_path)?.decode()?;
         let width = img.width();
         let height = img.height();
-        let mut color_array = vec![vec![0; height as usize]; width as usize];
+        let mut colors = Vec::with_capacity((width * height) as usize);
+        let mut alphas = Vec::with_capacity((width * height) as usize);
 
         for x in 0..width {
             for y in 0..height {
-                let pixel = img.get_pixel(x, y).to_rgb();
+                let pixel = img.get_pixel(x, y).to_rgba();
                 let r = pixel[0];
                 let g = pixel[1];
                 let b = pixel[2];
-                let color = ((r as u32) << 16) | ((g as u32) << 8) | (b as u32);
-                color_array[x as usize][y as usize] = color;
+                colors.push(((r as u32) << 16) | ((g as u32) << 8) | (b as u32));
+                alphas.push(pixel[3]);
             }
         }
 
-        Texture { width, height, color_array }
+        // Las imágenes opacas no necesitan guardar su transparencia
+        let alphas = alphas.iter().any(|&alpha| alpha < u8::MAX).then_some(alphas);
+        Ok(Texture::from_level(MipLevel { width, height, colors, alphas }))
+    }
+
+    // Arma las copias a la mitad de la imagen original, hasta 1x1
+    fn from_level(full: MipLevel) -> Texture {
+        let (width, height) = (full.width, full.height);
+        let mut levels = vec![full];
+        while let Some(last) = levels.last().filter(|level| level.width > 1 || level.height > 1) {
+            levels.push(last.half());
+        }
+        Texture { width, height, levels }
     }
 
+    #[inline]
     pub fn get_pixel_color(&self, x: u32, y: u32) -> u32 {
-        self.color_array[x as usize % self.width as usize][y as usize % self.height as usize]
+        self.levels[0].pixel(x, y).0
+    }
+
+    // Opacidad del píxel, de 0 (transparente) a 255 (opaco)
+    pub fn get_pixel_alpha(&self, x: u32, y: u32) -> u8 {
+        self.levels[0].pixel(x, y).1
+    }
+
+    // Si tiene algún píxel por el que se pueda ver lo que hay detrás
+    pub fn is_transparent(&self) -> bool {
+        self.levels[0].alphas.is_some()
+    }
+
+    // La copia que corresponde a la distancia: `texels` es cuántos píxeles de la imagen caen en
+    // un píxel de la pantalla. A lo lejos se lee una copia más chica, que ya los tiene
+    // promediados y no parpadea. Conviene elegirla una vez por pared y no en cada píxel
+    pub fn mip(&self, texels: f32) -> &MipLevel {
+        let lod = texels.max(1.0).log2().round() as usize;
+        &self.levels[lod.min(self.levels.len() - 1)]
+    }
+
+    pub fn sample(&self, u: f32, v: f32, texels: f32, filter: Filter) -> (u32, u8) {
+        self.mip(texels).sample(u, v, filter)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Los colores van por columnas: primero (0, 0) y (0, 1), después (1, 0) y (1, 1)
+    fn level(width: u32, height: u32, colors: Vec<u32>, alphas: Option<Vec<u8>>) -> MipLevel {
+        MipLevel { width, height, colors, alphas }
+    }
+
+    fn checker() -> MipLevel {
+        level(2, 2, vec![0x000000, 0x0000FF, 0xFF0000, 0x00FF00], None)
+    }
+
+    #[test]
+    fn wraps_out_of_range_coordinates() {
+        assert_eq!(wrap(7, 4), 3);
+        assert_eq!(wrap(7, 3), 1);
+        assert_eq!(texel(1.25, 4), 1);
+        assert_eq!(texel(-0.25, 4), 3);
+        assert_eq!(texel(-0.1, 3), 2);
+        assert_eq!(texel(1.0, 3), 0);
+    }
+
+    #[test]
+    fn nearest_sampling_repeats_the_texture() {
+        let texture = checker();
+        assert_eq!(texture.sample(0.75, 0.25, Filter::Nearest), (0xFF0000, 255));
+        assert_eq!(texture.sample(-0.25, 0.25, Filter::Nearest), (0xFF0000, 255));
+        assert_eq!(texture.sample(0.25, 1.75, Filter::Nearest), (0x0000FF, 255));
+        assert_eq!(texture.sample(-1.75, -0.25, Filter::Nearest), (0x0000FF, 255));
+    }
+
+    #[test]
+    fn halves_down_to_one_pixel() {
+        let texture = Texture::from_level(level(8, 2, vec![0xFFFFFF; 16], None));
+        let sizes: Vec<(u32, u32)> = texture.levels.iter().map(|level| (level.width, level.height)).collect();
+        assert_eq!(sizes, vec![(8, 2), (4, 1), (2, 1), (1, 1)]);
+        assert_eq!(texture.mip(1.0).width, 8);
+        assert_eq!(texture.mip(4.0).width, 2);
+        assert_eq!(texture.mip(1000.0).width, 1);
+        assert!(!texture.is_transparent());
+    }
+
+    #[test]
+    fn halving_ignores_the_color_of_transparent_pixels() {
+        let half = level(2, 2, vec![0xFF0000, 0xFFFFFF, 0xFF0000, 0xFFFFFF], Some(vec![255, 0, 255, 0])).half();
+        assert_eq!(half.colors, vec![0xFF0000]);
+        assert_eq!(half.alphas, Some(vec![128]));
+    }
+
+    #[test]
+    fn bilinear_sampling_mixes_the_four_neighbors() {
+        let texture = checker();
+        // En el centro de un píxel se lee solo ese píxel; entre los cuatro, el promedio
+        assert_eq!(texture.sample(0.25, 0.25, Filter::Bilinear), (0x000000, 255));
+        assert_eq!(texture.sample(0.75, 0.25, Filter::Bilinear), (0xFF0000, 255));
+        assert_eq!(texture.sample(0.5, 0.5, Filter::Bilinear), (0x404040, 255));
+        // En el borde se mezcla con el lado opuesto, como al repetir la textura
+        assert_eq!(texture.sample(0.0, 0.25, Filter::Bilinear), texture.sample(1.0, 0.25, Filter::Bilinear));
+    }
+
+    #[test]
+    fn bilinear_sampling_weights_colors_by_alpha() {
+        let texture = level(2, 2, vec![0x000000, 0x0000FF, 0xFF0000, 0x00FF00], Some(vec![255, 255, 0, 255]));
+        assert_eq!(texture.sample(0.5, 0.5, Filter::Bilinear), (0x005555, 191));
+        assert_eq!(texture.sample(0.75, 0.25, Filter::Bilinear).1, 0);
     }
 }
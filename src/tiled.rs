@@ -0,0 +1,538 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::Path;
+use std::str::FromStr;
+use roxmltree::Node;
+use serde_json::Value;
+use crate::items::ItemKind;
+use crate::level_map::{default_light_color, default_radius, Cell, Entity, FloorMap, LevelMap, Spawn, WarpTarget};
+use crate::maze::{self, MazeError, GOAL};
+use crate::player::SPAWN_ANGLE;
+
+// Importa mapas del editor Tiled (.tmx o .tmj/.json) al formato de celdas:
+//
+// - Capas de baldosas: cada baldosa vacía es piso. Las demás son pared ('|'), salvo que
+//   la baldosa tenga la propiedad `char` con el carácter de la celda (" " para piso,
+//   "D" para puerta, "g" para meta...). Las propiedades `texture` y `texture.north`
+//   (`.south`, `.east`, `.west`) agregan la textura a la paleta; si la baldosa no trae
+//   `char`, se le asigna una letra libre. Las capas posteriores tapan a las anteriores.
+// - Capas de objetos, según su tipo (o clase): `spawn` (propiedad `angle` en grados),
+//   `goal` (todas las celdas que cubre), `ghost` (`rate`), `item` (propiedad `item` o el
+//   nombre del objeto, con su carácter o su nombre), `light` (`radius`, `color`) y `warp`
+//   (`target_x`, `target_y` en celdas, y opcionalmente `floor` y `level`).
+// - Propiedades del mapa: `name`, `author` y `theme`, y las entradas de paleta `floor`,
+//   `ceiling`, `default` y `requires`.
+//
+// Las capas ocultas se ignoran. Los datos de las capas deben guardarse como CSV o XML.
+
+// Letras para las baldosas con textura que no eligen su carácter
+const AUTO_CHARS: &str = "ABCEFGHIJKMNOPQRTUVWXYZ";
+const WALL: char = '|';
+// Tiled guarda el volteo y la rotación de cada baldosa en los bits altos del gid
+const GID_MASK: u32 = 0x0FFF_FFFF;
+
+type Properties = HashMap<String, String>;
+
+struct Object {
+    kind: String, // Tipo o clase, en minúsculas
+    name: String,
+    x: f32, // Esquina superior izquierda, en píxeles
+    y: f32,
+    width: f32,
+    height: f32,
+    properties: Properties,
+}
+
+// Lo que interesa de un mapa de Tiled, sin importar de qué formato venga
+#[derive(Default)]
+struct TiledMap {
+    width: usize,
+    height: usize,
+    tile_width: f32,
+    tile_height: f32,
+    tiles: Vec<u32>,
+    tile_properties: HashMap<u32, Properties>,
+    objects: Vec<Object>,
+    properties: Properties,
+}
+
+fn error(message: impl Into<String>) -> MazeError {
+    MazeError::Format(format!("Tiled: {}", message.into()))
+}
+
+pub fn from_tmx(contents: &str, path: &str) -> Result<LevelMap, MazeError> {
+    read_tmx(contents, path)?.into_level(path)
+}
+
+pub fn from_json(value: &Value, path: &str) -> Result<LevelMap, MazeError> {
+    read_json(value, path)?.into_level(path)
+}
+
+impl TiledMap {
+    fn new(width: usize, height: usize, tile_width: f32, tile_height: f32, properties: Properties) -> Result<TiledMap, MazeError> {
+        if width == 0 || height == 0 || tile_width <= 0.0 || tile_height <= 0.0 {
+            return Err(error("el mapa no tiene tamaño"));
+        }
+        Ok(TiledMap { width, height, tile_width, tile_height, tiles: vec![0; width * height], properties, ..Default::default() })
+    }
+
+    fn add_layer(&mut self, gids: &[u32]) -> Result<(), MazeError> {
+        if gids.len() != self.tiles.len() {
+            return Err(error(format!("una capa tiene {} baldosas y se esperaban {}", gids.len(), self.tiles.len())));
+        }
+        for (tile, &gid) in self.tiles.iter_mut().zip(gids) {
+            if gid & GID_MASK != 0 {
+                *tile = gid & GID_MASK;
+            }
+        }
+        Ok(())
+    }
+
+    fn add_tileset(&mut self, first_gid: u32, source: &str, path: &str) -> Result<(), MazeError> {
+        // Los tilesets externos se buscan junto al mapa
+        let file = Path::new(path).with_file_name(source);
+        let file = file.to_string_lossy();
+        let contents = maze::read_file(&file)?;
+        if source.ends_with(".tsx") {
+            let doc = roxmltree::Document::parse(&contents).map_err(|e| error(format!("{}: {}", file, e)))?;
+            self.add_tmx_tiles(doc.root_element(), first_gid);
+        } else {
+            let value = serde_json::from_str(&contents).map_err(|e| error(format!("{}: {}", file, e)))?;
+            self.add_json_tiles(&value, first_gid);
+        }
+        Ok(())
+    }
+
+    // Celda que contiene el centro del objeto
+    fn center_cell(&self, object: &Object) -> (usize, usize) {
+        let x = (object.x + object.width / 2.0) / self.tile_width;
+        let y = (object.y + object.height / 2.0) / self.tile_height;
+        (x as usize, y as usize)
+    }
+
+    // Celdas que toca el objeto; al menos la de su esquina
+    fn covered_cells(&self, object: &Object) -> Vec<Cell> {
+        let x0 = (object.x / self.tile_width) as usize;
+        let y0 = (object.y / self.tile_height) as usize;
+        let x1 = (((object.x + object.width) / self.tile_width).ceil() as usize).max(x0 + 1);
+        let y1 = (((object.y + object.height) / self.tile_height).ceil() as usize).max(y0 + 1);
+        (y0..y1).flat_map(|y| (x0..x1).map(move |x| Cell { x, y })).collect()
+    }
+
+    fn into_level(self, path: &str) -> Result<LevelMap, MazeError> {
+        let mut palette = BTreeMap::new();
+        for key in ["floor", "ceiling", "default", "requires"] {
+            if let Some(value) = self.properties.get(key) {
+                palette.insert(key.to_string(), value.clone());
+            }
+        }
+
+        // Las letras automáticas no deben chocar con las que eligió el autor
+        let chosen: HashSet<char> = self.tile_properties.values().filter_map(|p| p.get("char")?.chars().next()).collect();
+        let mut auto = AUTO_CHARS.chars().filter(|c| !chosen.contains(c));
+
+        let mut chars: HashMap<u32, char> = HashMap::new();
+        let mut grid = Vec::with_capacity(self.height);
+        let mut goals = Vec::new();
+        for (y, row) in self.tiles.chunks(self.width).enumerate() {
+            let mut line = Vec::with_capacity(self.width);
+            for (x, &gid) in row.iter().enumerate() {
+                let cell = match chars.get(&gid) {
+                    Some(&cell) => cell,
+                    None if gid == 0 => ' ',
+                    None => {
+                        let empty = Properties::new();
+                        let properties = self.tile_properties.get(&gid).unwrap_or(&empty);
+                        let textures: Vec<(&String, &String)> = properties.iter().filter(|(key, _)| key.starts_with("texture")).collect();
+                        let cell = match properties.get("char").and_then(|c| c.chars().next()) {
+                            Some(cell) => cell,
+                            None if !textures.is_empty() => auto.next().ok_or_else(|| error("hay demasiadas baldosas con texturas distintas"))?,
+                            None => WALL,
+                        };
+                        for (key, texture) in textures {
+                            if cell == ' ' {
+                                eprintln!("Tiled: se ignora la textura {} de una baldosa de piso", texture);
+                                continue;
+                            }
+                            palette.insert(format!("{}{}", cell, &key["texture".len()..]), texture.clone());
+                        }
+                        chars.insert(gid, cell);
+                        cell
+                    }
+                };
+                if cell == GOAL {
+                    goals.push(Cell { x, y });
+                }
+                line.push(cell);
+            }
+            grid.push(line);
+        }
+
+        let mut spawn = None;
+        let mut entities = Vec::new();
+        for object in &self.objects {
+            let (x, y) = self.center_cell(object);
+            let number = |key: &str| object.properties.get(key).and_then(|value| value.parse::<f32>().ok());
+            match object.kind.as_str() {
+                "spawn" | "player" => {
+                    if spawn.is_some() {
+                        return Err(MazeError::DuplicateSpawn { line: y + 1, column: x + 1 });
+                    }
+                    let angle = number("angle").map_or(SPAWN_ANGLE, f32::to_radians);
+                    spawn = Some(Spawn { x, y, floor: 0, angle });
+                }
+                "goal" => {
+                    for cell in self.covered_cells(object) {
+                        if let Some(slot) = grid.get_mut(cell.y).and_then(|row| row.get_mut(cell.x)) {
+                            *slot = GOAL;
+                        }
+                        if !goals.contains(&cell) {
+                            goals.push(cell);
+                        }
+                    }
+                }
+                "ghost" | "enemy" => entities.push(Entity::Ghost { x, y, rate: number("rate") }),
+                "item" => {
+                    let name = object.properties.get("item").unwrap_or(&object.name);
+                    let item = item_cell(name).ok_or_else(|| error(format!("'{}' no es un objeto", name)))?;
+                    entities.push(Entity::Item { x, y, item });
+                }
+                "light" => entities.push(Entity::Light {
+                    x,
+                    y,
+                    radius: number("radius").unwrap_or_else(default_radius),
+                    color: object.properties.get("color").cloned().unwrap_or_else(default_light_color),
+                }),
+                "warp" => {
+                    let cell = |key: &str| {
+                        let value = object.properties.get(key).ok_or_else(|| error(format!("el panel '{}' no tiene {}", object.name, key)))?;
+                        value.parse::<usize>().map_err(|_| error(format!("{} del panel '{}' no es un número: {}", key, object.name, value)))
+                    };
+                    let to = WarpTarget {
+                        x: cell("target_x")?,
+                        y: cell("target_y")?,
+                        floor: if object.properties.contains_key("floor") { cell("floor")? } else { 0 },
+                        level: object.properties.get("level").cloned(),
+                    };
+                    entities.push(Entity::Warp { x, y, to });
+                }
+                kind => eprintln!("Tiled: se ignora el objeto '{}' de tipo '{}'", object.name, kind),
+            }
+        }
+
+        let name = match self.properties.get("name") {
+            Some(name) => name.clone(),
+            None => Path::new(path).file_stem().map_or(path.into(), |stem| stem.to_string_lossy()).into_owned(),
+        };
+        Ok(LevelMap {
+            name,
+            author: self.properties.get("author").cloned().unwrap_or_default(),
+            theme: self.properties.get("theme").cloned(),
+            palette,
+            ground: FloorMap { tiles: grid.iter().map(|row| row.iter().collect()).collect(), goals, entities, walls: Vec::new() },
+            spawn: spawn.ok_or(MazeError::MissingSpawn)?,
+            floors: Vec::new(),
+        })
+    }
+
+    fn add_tmx_tiles(&mut self, tileset: Node, first_gid: u32) {
+        for tile in tileset.children().filter(|n| n.has_tag_name("tile")) {
+            if let Some(id) = tile.attribute("id").and_then(|id| id.parse::<u32>().ok()) {
+                self.tile_properties.insert(first_gid + id, tmx_properties(tile));
+            }
+        }
+    }
+
+    fn add_json_tiles(&mut self, tileset: &Value, first_gid: u32) {
+        for tile in tileset["tiles"].as_array().into_iter().flatten() {
+            if let Some(id) = tile["id"].as_u64() {
+                self.tile_properties.insert(first_gid + id as u32, json_properties(tile));
+            }
+        }
+    }
+}
+
+// Acepta el carácter del objeto o su nombre ("k", "Llave")
+fn item_cell(name: &str) -> Option<char> {
+    let mut chars = name.chars();
+    if let (Some(cell), None) = (chars.next(), chars.next()) {
+        if ItemKind::from_cell(cell).is_some() {
+            return Some(cell);
+        }
+    }
+    ItemKind::ALL.iter().find(|kind| kind.name().to_lowercase() == name.trim().to_lowercase()).map(|kind| kind.cell())
+}
+
+// --- TMX (XML) ---
+
+fn read_tmx(contents: &str, path: &str) -> Result<TiledMap, MazeError> {
+    let doc = roxmltree::Document::parse(contents).map_err(|e| error(e.to_string()))?;
+    let root = doc.root_element();
+    if !root.has_tag_name("map") {
+        return Err(error("falta el elemento <map>"));
+    }
+    if root.attribute("infinite") == Some("1") {
+        return Err(error("los mapas infinitos no están soportados"));
+    }
+
+    let mut map = TiledMap::new(
+        attribute(root, "width")?,
+        attribute(root, "height")?,
+        attribute(root, "tilewidth")?,
+        attribute(root, "tileheight")?,
+        tmx_properties(root),
+    )?;
+    for tileset in root.children().filter(|n| n.has_tag_name("tileset")) {
+        let first_gid = attribute(tileset, "firstgid")?;
+        match tileset.attribute("source") {
+            Some(source) => map.add_tileset(first_gid, source, path)?,
+            None => map.add_tmx_tiles(tileset, first_gid),
+        }
+    }
+    read_tmx_layers(root, &mut map)?;
+    Ok(map)
+}
+
+fn read_tmx_layers(parent: Node, map: &mut TiledMap) -> Result<(), MazeError> {
+    for layer in parent.children().filter(|n| n.is_element() && n.attribute("visible") != Some("0")) {
+        match layer.tag_name().name() {
+            "layer" => {
+                let data = layer.children().find(|n| n.has_tag_name("data")).ok_or_else(|| error("una capa no tiene datos"))?;
+                let gids = match data.attribute("encoding") {
+                    Some("csv") => data
+                        .text()
+                        .unwrap_or("")
+                        .split(',')
+                        .map(str::trim)
+                        .filter(|gid| !gid.is_empty())
+                        .map(|gid| gid.parse().map_err(|_| error(format!("'{}' no es una baldosa", gid))))
+                        .collect::<Result<Vec<u32>, _>>()?,
+                    None => data
+                        .children()
+                        .filter(|n| n.has_tag_name("tile"))
+                        .map(|tile| tile.attribute("gid").map_or(Ok(0), |gid| gid.parse().map_err(|_| error(format!("'{}' no es una baldosa", gid)))))
+                        .collect::<Result<Vec<u32>, _>>()?,
+                    Some(encoding) => return Err(error(format!("la codificación '{}' no está soportada; guarda el mapa como CSV", encoding))),
+                };
+                map.add_layer(&gids)?;
+            }
+            "objectgroup" => {
+                for object in layer.children().filter(|n| n.has_tag_name("object")) {
+                    let number = |name: &str| object.attribute(name).and_then(|value| value.parse::<f32>().ok()).unwrap_or(0.0);
+                    let height = number("height");
+                    // Los objetos con baldosa se anclan por abajo
+                    let y = if object.has_attribute("gid") { number("y") - height } else { number("y") };
+                    map.objects.push(Object {
+                        kind: object.attribute("type").or(object.attribute("class")).unwrap_or("").to_lowercase(),
+                        name: object.attribute("name").unwrap_or("").to_string(),
+                        x: number("x"),
+                        y,
+                        width: number("width"),
+                        height,
+                        properties: tmx_properties(object),
+                    });
+                }
+            }
+            "group" => read_tmx_layers(layer, map)?,
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+fn attribute<T: FromStr>(node: Node, name: &str) -> Result<T, MazeError> {
+    node.attribute(name)
+        .and_then(|value| value.parse().ok())
+        .ok_or_else(|| error(format!("<{}> no tiene un '{}' válido", node.tag_name().name(), name)))
+}
+
+fn tmx_properties(node: Node) -> Properties {
+    node.children()
+        .filter(|n| n.has_tag_name("properties"))
+        .flat_map(|properties| properties.children().filter(|n| n.has_tag_name("property")))
+        .filter_map(|property| {
+            // Los textos de varias líneas van dentro del elemento en vez de en `value`
+            let value = property.attribute("value").or(property.text()).unwrap_or("");
+            Some((property.attribute("name")?.to_string(), value.to_string()))
+        })
+        .collect()
+}
+
+// --- JSON ---
+
+fn read_json(value: &Value, path: &str) -> Result<TiledMap, MazeError> {
+    if value["infinite"].as_bool() == Some(true) {
+        return Err(error("los mapas infinitos no están soportados"));
+    }
+
+    let mut map = TiledMap::new(
+        json_number(value, "width")? as usize,
+        json_number(value, "height")? as usize,
+        json_number(value, "tilewidth")? as f32,
+        json_number(value, "tileheight")? as f32,
+        json_properties(value),
+    )?;
+    for tileset in value["tilesets"].as_array().into_iter().flatten() {
+        let first_gid = json_number(tileset, "firstgid")? as u32;
+        match tileset["source"].as_str() {
+            Some(source) => map.add_tileset(first_gid, source, path)?,
+            None => map.add_json_tiles(tileset, first_gid),
+        }
+    }
+    read_json_layers(&value["layers"], &mut map)?;
+    Ok(map)
+}
+
+fn read_json_layers(layers: &Value, map: &mut TiledMap) -> Result<(), MazeError> {
+    for layer in layers.as_array().into_iter().flatten().filter(|layer| layer["visible"].as_bool() != Some(false)) {
+        match layer["type"].as_str() {
+            Some("tilelayer") => {
+                if let Some(encoding) = layer["encoding"].as_str().filter(|&encoding| encoding != "csv") {
+                    return Err(error(format!("la codificación '{}' no está soportada; guarda el mapa como CSV", encoding)));
+                }
+                let gids = layer["data"]
+                    .as_array()
+                    .ok_or_else(|| error("una capa no tiene datos"))?
+                    .iter()
+                    .map(|gid| gid.as_u64().map(|gid| gid as u32).ok_or_else(|| error(format!("'{}' no es una baldosa", gid))))
+                    .collect::<Result<Vec<u32>, _>>()?;
+                map.add_layer(&gids)?;
+            }
+            Some("objectgroup") => {
+                for object in layer["objects"].as_array().into_iter().flatten() {
+                    let number = |name: &str| object[name].as_f64().unwrap_or(0.0) as f32;
+                    let height = number("height");
+                    let y = if object["gid"].is_u64() { number("y") - height } else { number("y") };
+                    map.objects.push(Object {
+                        kind: object["type"].as_str().or(object["class"].as_str()).unwrap_or("").to_lowercase(),
+                        name: object["name"].as_str().unwrap_or("").to_string(),
+                        x: number("x"),
+                        y,
+                        width: number("width"),
+                        height,
+                        properties: json_properties(object),
+                    });
+                }
+            }
+            Some("group") => read_json_layers(&layer["layers"], map)?,
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+fn json_number(value: &Value, key: &str) -> Result<f64, MazeError> {
+    value[key].as_f64().ok_or_else(|| error(format!("falta el número '{}'", key)))
+}
+
+fn json_properties(value: &Value) -> Properties {
+    value["properties"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|property| {
+            let value = match &property["value"] {
+                Value::String(text) => text.clone(),
+                other => other.to_string(),
+            };
+            Some((property["name"].as_str()?.to_string(), value))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TMX: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.10" orientation="orthogonal" width="5" height="3" tilewidth="16" tileheight="16" infinite="0">
+ <properties>
+  <property name="name" value="Cripta"/>
+  <property name="requires" value="s"/>
+ </properties>
+ <tileset firstgid="1" name="muros" tilewidth="16" tileheight="16" tilecount="3" columns="3">
+  <tile id="1"><properties><property name="char" value="D"/></properties></tile>
+  <tile id="2"><properties><property name="texture" value="sprites/rejaSprite.png"/></properties></tile>
+ </tileset>
+ <layer id="1" name="muros" width="5" height="3">
+  <data encoding="csv">
+3,1,1,1,1,
+1,0,2,0,1,
+1,1,1,1,1
+</data>
+ </layer>
+ <objectgroup id="2" name="cosas">
+  <object id="1" name="inicio" type="spawn" x="16" y="16" width="16" height="16">
+   <properties><property name="angle" type="float" value="90"/></properties>
+  </object>
+  <object id="2" name="meta" type="goal" x="48" y="16" width="16" height="16"/>
+  <object id="3" name="Llave" class="item" x="20" y="20"/>
+ </objectgroup>
+</map>"#;
+
+    fn json() -> Value {
+        serde_json::json!({
+            "width": 5, "height": 3, "tilewidth": 16, "tileheight": 16,
+            "properties": [{ "name": "name", "type": "string", "value": "Cripta" }],
+            "tilesets": [{
+                "firstgid": 1,
+                "tiles": [
+                    { "id": 1, "properties": [{ "name": "char", "type": "string", "value": "D" }] },
+                    { "id": 2, "properties": [{ "name": "texture", "type": "string", "value": "sprites/rejaSprite.png" }] }
+                ]
+            }],
+            "layers": [
+                // La puerta está volteada: los bits altos del gid no cambian la baldosa
+                { "type": "tilelayer", "data": [3, 1, 1, 1, 1, 1, 0, 0x8000_0002u32, 0, 1, 1, 1, 1, 1, 1] },
+                { "type": "objectgroup", "objects": [
+                    { "name": "inicio", "type": "spawn", "x": 16, "y": 16, "width": 16, "height": 16 },
+                    { "name": "meta", "type": "goal", "x": 48, "y": 16, "width": 16, "height": 16 },
+                    { "name": "f", "type": "ghost", "x": 40, "y": 40, "properties": [{ "name": "rate", "type": "float", "value": 1.5 }] }
+                ] }
+            ]
+        })
+    }
+
+    #[test]
+    fn imports_csv_layers_and_objects_from_tmx() {
+        let map = from_tmx(TMX, "cripta.tmx").unwrap();
+        assert_eq!(map.name, "Cripta");
+        assert_eq!(map.ground.tiles, ["A||||", "| Dg|", "|||||"]);
+        assert_eq!((map.spawn.x, map.spawn.y), (1, 1));
+        assert!((map.spawn.angle - std::f32::consts::FRAC_PI_2).abs() < 1e-6);
+        assert!(map.ground.goals == [Cell { x: 3, y: 1 }]);
+        assert!(matches!(map.ground.entities[..], [Entity::Item { x: 1, y: 1, item: 'k' }]));
+        // La baldosa con textura y sin carácter recibe una letra libre y su entrada en la paleta
+        assert_eq!(map.palette.get("A").map(String::as_str), Some("sprites/rejaSprite.png"));
+        assert_eq!(map.palette.get("requires").map(String::as_str), Some("s"));
+    }
+
+    #[test]
+    fn imports_xml_tile_data() {
+        let rows = ["3,1,1,1,1", "1,0,2,0,1", "1,1,1,1,1"];
+        let tiles: String = rows.join(",").split(',').map(|gid| format!("<tile gid=\"{}\"/>", gid)).collect();
+        let csv = TMX[TMX.find("<data").unwrap()..TMX.find("</data>").unwrap() + "</data>".len()].to_string();
+        let xml = TMX.replace(&csv, &format!("<data>{}</data>", tiles));
+        assert_eq!(from_tmx(&xml, "cripta.tmx").unwrap().ground.tiles, ["A||||", "| Dg|", "|||||"]);
+    }
+
+    #[test]
+    fn imports_the_json_format() {
+        let map = from_json(&json(), "cripta.tmj").unwrap();
+        assert_eq!(map.ground.tiles, ["A||||", "| Dg|", "|||||"]);
+        assert!(matches!(map.ground.entities[..], [Entity::Ghost { x: 2, y: 2, rate: Some(rate) }] if rate == 1.5));
+    }
+
+    #[test]
+    fn rejects_unsupported_or_broken_maps() {
+        let base64 = TMX.replace(r#"<data encoding="csv">"#, r#"<data encoding="base64">"#);
+        assert!(matches!(from_tmx(&base64, "cripta.tmx"), Err(MazeError::Format(e)) if e.contains("base64")));
+
+        let mut short = json();
+        short["layers"][0]["data"] = serde_json::json!([1, 1, 1]);
+        assert!(matches!(from_json(&short, "cripta.tmj"), Err(MazeError::Format(e)) if e.contains("3 baldosas")));
+
+        let mut no_spawn = json();
+        no_spawn["layers"][1]["objects"].as_array_mut().unwrap().remove(0);
+        assert!(matches!(from_json(&no_spawn, "cripta.tmj"), Err(MazeError::MissingSpawn)));
+    }
+}
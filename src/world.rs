@@ -0,0 +1,192 @@
+use nalgebra::Vector2;
+use crate::doors::Doors;
+use crate::enemy::Enemy;
+use crate::items::{Inventory, Item, ItemKind};
+use crate::level_map::{parse_color, Entity, FloorMap, LevelMap, WarpTarget};
+use crate::maze::{STAIRS_DOWN, STAIRS_UP};
+use crate::palette::Palette;
+use crate::segments::Segment;
+
+const REPEL_RADIUS: f32 = 2.5; // Alcance del Scope Silph para ahuyentar fantasmas
+
+// Luz puntual que tiñe las paredes y el piso cercanos
+pub struct Light {
+    pub pos: Vector2<f32>,
+    pub radius: f32,
+    pub color: u32,
+}
+
+// Panel que lleva al jugador a otra celda al pisarlo
+pub struct Warp {
+    pub cell: (usize, usize),
+    pub to: WarpTarget,
+}
+
+// Un piso del nivel: su mapa y sus entidades. Solo se simula el piso donde está el jugador
+pub struct Floor {
+    pub maze: Vec<Vec<char>>,
+    pub doors: Doors,
+    pub enemies: Vec<Enemy>,
+    pub items: Vec<Item>,
+    pub lights: Vec<Light>,
+    pub warps: Vec<Warp>,
+    pub walls: Vec<Segment>, // Paredes delgadas
+    pub goals: Vec<Vector2<f32>>, // Centros de las celdas de meta
+}
+
+// Todo lo que contiene un nivel cargado: sus pisos, sus texturas y sus entidades
+pub struct World {
+    pub floors: Vec<Floor>,  // De abajo hacia arriba
+    pub floor: usize,        // Piso donde está el jugador
+    pub palette: Palette,
+    pub spawn: Vector2<f32>,
+    pub spawn_floor: usize,
+    pub spawn_angle: f32,
+    pub goal_requires: Vec<ItemKind>, // Objetos necesarios para entrar a la meta
+}
+
+pub fn cell_center((x, y): (usize, usize)) -> Vector2<f32> {
+    Vector2::new(x as f32 + 0.5, y as f32 + 0.5)
+}
+
+impl Floor {
+    fn new(map: &FloorMap) -> Floor {
+        let maze = map.grid();
+        let doors = Doors::from_maze(&maze);
+
+        // Cada fantasma anima a su propio ritmo, salvo que el mapa lo indique
+        let mut items = Vec::new();
+        let mut enemies = Vec::new();
+        let mut lights = Vec::new();
+        let mut warps = Vec::new();
+        for entity in &map.entities {
+            match *entity {
+                Entity::Item { x, y, item } => {
+                    if let Some(kind) = ItemKind::from_cell(item) {
+                        items.push(Item { kind, pos: cell_center((x, y)) });
+                    }
+                }
+                Entity::Ghost { x, y, rate } => {
+                    let pos = cell_center((x, y));
+                    let rate = rate.unwrap_or(0.8 + 0.1 * (enemies.len() % 5) as f32);
+                    enemies.push(Enemy::new(pos.x, pos.y, rate));
+                }
+                Entity::Light { x, y, radius, ref color } => {
+                    let color = parse_color(color).unwrap_or(0xFFD27F);
+                    lights.push(Light { pos: cell_center((x, y)), radius, color });
+                }
+                Entity::Warp { x, y, ref to } => warps.push(Warp { cell: (x, y), to: to.clone() }),
+            }
+        }
+
+        let goals = map.goals.iter().map(|goal| cell_center((goal.x, goal.y))).collect();
+
+        Floor { maze, doors, enemies, items, lights, warps, walls: map.walls.clone(), goals }
+    }
+
+    pub fn warp_at(&self, x: usize, y: usize) -> Option<&Warp> {
+        self.warps.iter().find(|warp| warp.cell == (x, y))
+    }
+}
+
+impl World {
+    pub fn new(map: LevelMap, palette: Palette) -> World {
+        let floors = map.all_floors().map(Floor::new).collect();
+        let spawn = cell_center((map.spawn.x, map.spawn.y));
+        let goal_requires = palette.goal_requires.clone();
+        World {
+            floors,
+            floor: map.spawn.floor,
+            palette,
+            spawn,
+            spawn_floor: map.spawn.floor,
+            spawn_angle: map.spawn.angle,
+            goal_requires,
+        }
+    }
+
+    // El piso donde está el jugador
+    pub fn current(&self) -> &Floor {
+        &self.floors[self.floor]
+    }
+
+    pub fn current_mut(&mut self) -> &mut Floor {
+        &mut self.floors[self.floor]
+    }
+
+    // Si el jugador está sobre una escalera, lo cambia de piso y devuelve el nuevo.
+    // Llega a la misma celda del otro piso, sobre la escalera que va en sentido contrario
+    pub fn take_stairs(&mut self, pos: &Vector2<f32>) -> Option<usize> {
+        let cell = self.current().maze.get(pos.y as usize).and_then(|row| row.get(pos.x as usize)).copied();
+        let floor = match cell {
+            Some(STAIRS_UP) if self.floor + 1 < self.floors.len() => self.floor + 1,
+            Some(STAIRS_DOWN) if self.floor > 0 => self.floor - 1,
+            _ => return None,
+        };
+        self.floor = floor;
+        Some(floor)
+    }
+
+    // Agrega objetos (por su carácter en el mapa) a los requisitos de la meta
+    pub fn require_items(&mut self, cells: &str) {
+        for kind in cells.chars().filter_map(ItemKind::from_cell) {
+            if !self.goal_requires.contains(&kind) {
+                self.goal_requires.push(kind);
+            }
+        }
+    }
+
+    // La meta es un edificio sólido: se alcanza al tocar una de sus esquinas, salvo que una
+    // pared delgada la separe del jugador
+    pub fn reached_goal(&self, pos: &Vector2<f32>) -> bool {
+        let floor = self.current();
+        floor.goals.iter().any(|&goal| {
+            let touching = [(-0.5, -0.5), (0.5, -0.5), (-0.5, 0.5), (0.5, 0.5)]
+                .iter()
+                .any(|&(dx, dy)| (pos - goal - Vector2::new(dx, dy)).norm() < 0.5);
+            touching && !floor.walls.iter().any(|wall| wall.crosses(*pos, goal))
+        })
+    }
+
+    pub fn missing_requirements(&self, inventory: &Inventory) -> Vec<ItemKind> {
+        self.goal_requires.iter().copied().filter(|&kind| !inventory.has(kind)).collect()
+    }
+
+    // Avanza la simulación y devuelve los segundos de contacto con fantasmas en este paso
+    pub fn update(&mut self, dt: f32, player_pos: &Vector2<f32>) -> f32 {
+        let floor = self.current_mut();
+        let touching = floor.enemies.iter_mut().map(|enemy| enemy.update(dt, player_pos)).filter(|&touches| touches).count();
+        floor.doors.update(dt);
+        touching as f32 * dt
+    }
+
+    // Ahuyenta a los fantasmas cercanos que estén frente al jugador; devuelve cuántos huyeron
+    pub fn repel_ghosts(&mut self, player_pos: &Vector2<f32>, angle: f32) -> usize {
+        let facing = Vector2::new(angle.cos(), angle.sin());
+        let mut repelled = 0;
+
+        for enemy in self.current_mut().enemies.iter_mut() {
+            let to_enemy = enemy.pos - player_pos;
+            let distance = to_enemy.norm();
+            if distance < REPEL_RADIUS && (distance < 0.5 || to_enemy.dot(&facing) / distance > 0.5) {
+                enemy.repel();
+                repelled += 1;
+            }
+        }
+
+        repelled
+    }
+
+    // Quita del mapa los objetos que toca el jugador y los devuelve
+    pub fn pick_up_items(&mut self, player_pos: &Vector2<f32>) -> Vec<ItemKind> {
+        let mut picked = Vec::new();
+        self.current_mut().items.retain(|item| {
+            let touching = (player_pos - item.pos).norm() < 0.5;
+            if touching {
+                picked.push(item.kind);
+            }
+            !touching
+        });
+        picked
+    }
+}